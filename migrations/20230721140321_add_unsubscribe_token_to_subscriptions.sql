@@ -0,0 +1,12 @@
+-- Add migration script here
+BEGIN;
+    ALTER TABLE subscriptions ADD COLUMN unsubscribe_token TEXT NULL;
+    -- Existing subscribers get a random token as well. `random()` is not
+    -- cryptographically secure; `gen_random_uuid()` is.
+    UPDATE subscriptions
+        SET unsubscribe_token = replace(gen_random_uuid()::text, '-', '')
+        WHERE unsubscribe_token IS NULL;
+    ALTER TABLE subscriptions ALTER COLUMN unsubscribe_token SET NOT NULL;
+    ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_unsubscribe_token_key
+        UNIQUE (unsubscribe_token);
+COMMIT;
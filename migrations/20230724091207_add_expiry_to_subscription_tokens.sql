@@ -0,0 +1,12 @@
+-- Add migration script here
+BEGIN;
+    ALTER TABLE subscription_tokens
+        ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+    -- Tokens issued before this migration get a fresh validity window
+    ALTER TABLE subscription_tokens
+        ADD COLUMN expires_at timestamptz NOT NULL DEFAULT now() + interval '24 hours';
+    ALTER TABLE subscription_tokens ALTER COLUMN expires_at DROP DEFAULT;
+    ALTER TABLE subscription_tokens ADD COLUMN used_at timestamptz NULL;
+    CREATE INDEX subscription_tokens_subscriber_id_idx
+        ON subscription_tokens (subscriber_id);
+COMMIT;
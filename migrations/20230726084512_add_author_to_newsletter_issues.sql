@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Issues published before this migration have no known author.
+ALTER TABLE newsletter_issues
+    ADD COLUMN author_user_id uuid NULL REFERENCES users (user_id);
+CREATE INDEX newsletter_issues_published_at_idx
+    ON newsletter_issues (published_at DESC);
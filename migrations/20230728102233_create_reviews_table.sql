@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE reviews(
+    review_id uuid NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    content TEXT NOT NULL,
+    -- 'pending', 'approved' or 'rejected'
+    status TEXT NOT NULL,
+    submitted_at timestamptz NOT NULL,
+    moderated_at timestamptz NULL,
+    moderated_by uuid NULL
+        REFERENCES users (user_id),
+    PRIMARY KEY (review_id)
+);
+CREATE INDEX reviews_status_idx ON reviews (status);
@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE review_tokens(
+    review_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    created_at timestamptz NOT NULL DEFAULT now(),
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (review_token)
+);
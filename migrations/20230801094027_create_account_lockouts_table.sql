@@ -0,0 +1,9 @@
+-- Add migration script here
+-- Keyed by username rather than user id: unknown usernames get locked
+-- as well, so that a lockout does not reveal which accounts exist.
+CREATE TABLE account_lockouts(
+    username TEXT NOT NULL,
+    locked_at timestamptz NOT NULL,
+    locked_until timestamptz NOT NULL,
+    PRIMARY KEY (username)
+);
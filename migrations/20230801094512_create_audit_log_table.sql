@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE audit_log(
+    audit_log_id uuid NOT NULL,
+    -- e.g. 'login_failed', 'account_locked', 'account_unlocked'
+    event TEXT NOT NULL,
+    username TEXT NULL,
+    ip_address TEXT NULL,
+    -- The admin who performed the action, if any.
+    actor_user_id uuid NULL
+        REFERENCES users (user_id),
+    details TEXT NULL,
+    occurred_at timestamptz NOT NULL,
+    PRIMARY KEY (audit_log_id)
+);
+CREATE INDEX audit_log_occurred_at_idx ON audit_log (occurred_at);
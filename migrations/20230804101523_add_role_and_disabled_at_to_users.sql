@@ -0,0 +1,8 @@
+-- Add migration script here
+-- Users created before roles existed could do everything: they become admins.
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'admin';
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'viewer';
+ALTER TABLE users ADD CONSTRAINT users_role_check
+    CHECK (role IN ('admin', 'editor', 'viewer'));
+-- Disabled users can neither log in nor use an existing session.
+ALTER TABLE users ADD COLUMN disabled_at timestamptz NULL;
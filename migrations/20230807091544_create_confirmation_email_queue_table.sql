@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE confirmation_email_queue(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    n_retries INTEGER NOT NULL DEFAULT 0,
+    execute_after timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY(subscriber_id)
+);
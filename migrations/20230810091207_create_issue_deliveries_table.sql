@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE issue_deliveries(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    -- 'delivered' or 'failed'
+    outcome TEXT NOT NULL,
+    attempted_at timestamptz NOT NULL,
+    PRIMARY KEY(newsletter_issue_id, subscriber_email)
+);
+CREATE INDEX issue_deliveries_subscriber_email_idx
+    ON issue_deliveries (subscriber_email);
@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE privacy_tokens(
+    privacy_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    created_at timestamptz NOT NULL DEFAULT now(),
+    expires_at timestamptz NOT NULL,
+    PRIMARY KEY (privacy_token)
+);
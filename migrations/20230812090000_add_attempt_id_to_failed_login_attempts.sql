@@ -0,0 +1,5 @@
+-- Add migration script here
+-- Attempts are recorded before the password is checked, and forgotten
+-- again when it was not a failure after all.
+ALTER TABLE failed_login_attempts
+    ADD COLUMN attempt_id uuid PRIMARY KEY DEFAULT gen_random_uuid();
@@ -0,0 +1,245 @@
+use axum::{response::Html, Extension, Json};
+use hyper::StatusCode;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, OpenApi as OpenApiDocument, Ref};
+use utoipa::{Modify, OpenApi, ToSchema};
+use crate::routes::{self, FieldError};
+use crate::templates::Templates;
+use crate::utils::e500;
+
+/// The OpenAPI document of every public endpoint, generated from the
+/// handlers and their payloads. The admin area is left out.
+/// `docs/openapi.json` is a copy of it, kept up to date by a test.
+///
+/// `#[utoipa::path]` refers to schemas by their name in `components`,
+/// without checking it: a test makes sure every reference resolves.
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "The public endpoints of the newsletter.\n\n\
+        Form posts must carry the `csrf_token` field of the page that rendered \
+        the form (or send it in an `X-CSRF-Token` header), along with the \
+        signed `_csrf` cookie set with that page. JSON requests are exempt."),
+    paths(
+        routes::home,
+        routes::blog,
+        routes::blog_issue,
+        routes::reviews,
+        routes::request_review_link,
+        routes::review_form,
+        routes::submit_review,
+        routes::privacy_page,
+        routes::request_privacy_link,
+        routes::privacy_dashboard,
+        routes::export_personal_data,
+        routes::erase_personal_data,
+        routes::subscribe,
+        routes::subscribe_api,
+        routes::confirm,
+        routes::unsubscribe_form,
+        routes::unsubscribe,
+        routes::publish_newsletter,
+        routes::login_form,
+        routes::login,
+        routes::health_check,
+        routes::liveness,
+        routes::readiness,
+        crate::metrics::metrics,
+    ),
+    components(schemas(
+        ErrorBody,
+        ValidationErrors,
+        SubscriptionCreated,
+        Liveness,
+        Readiness,
+        PersonalDataExport,
+        routes::FormData,
+        FieldError,
+        routes::BodyData,
+        routes::Content,
+    )),
+    modifiers(&BasicAuth, &FormBodies),
+    tags(
+        (name = "pages", description = "HTML pages and the forms they post."),
+        (name = "reviews", description = "Reviews written by subscribers."),
+        (name = "privacy", description = "Subscribers downloading or erasing their data."),
+        (name = "subscriptions", description = "Subscribing and unsubscribing."),
+        (name = "newsletters", description = "Publishing issues."),
+        (name = "operations", description = "Probes and metrics."),
+    )
+)]
+pub struct ApiDoc;
+
+/// The body of most error responses.
+#[derive(ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+/// Every invalid field of a subscription.
+#[derive(ToSchema)]
+pub struct ValidationErrors {
+    pub error: String,
+    /// Missing when the body itself could not be parsed.
+    pub fields: Option<Vec<FieldError>>,
+}
+
+#[derive(ToSchema)]
+pub struct SubscriptionCreated {
+    #[schema(example = "pending_confirmation")]
+    pub status: String,
+}
+
+#[derive(ToSchema)]
+pub struct Liveness {
+    #[schema(example = "ok")]
+    pub status: String,
+}
+
+#[derive(ToSchema)]
+pub struct Readiness {
+    /// `ok`, `degraded` or `down`.
+    #[schema(example = "ok")]
+    pub status: String,
+    /// The outcome of each check, by name.
+    #[schema(value_type = Object)]
+    pub checks: serde_json::Value,
+}
+
+/// Everything we keep about a subscriber.
+#[derive(ToSchema)]
+pub struct PersonalDataExport {
+    #[schema(value_type = String, format = DateTime)]
+    pub exported_at: String,
+    /// Their `id`, `email`, `name`, `status` and `subscribed_at`.
+    #[schema(value_type = Object)]
+    pub subscriber: serde_json::Value,
+    /// When the confirmation links were sent, expired and used.
+    #[schema(value_type = Vec<Object>)]
+    pub confirmation_tokens: Vec<serde_json::Value>,
+    /// When the review links were sent, expired and used.
+    #[schema(value_type = Vec<Object>)]
+    pub review_tokens: Vec<serde_json::Value>,
+    #[schema(value_type = Vec<Object>)]
+    pub reviews: Vec<serde_json::Value>,
+    /// The issues we tried to send, `delivered` or `failed`.
+    #[schema(value_type = Vec<Object>)]
+    pub deliveries: Vec<serde_json::Value>,
+    /// The issues still waiting to be sent.
+    #[schema(value_type = Vec<Object>)]
+    pub pending_deliveries: Vec<serde_json::Value>,
+}
+
+struct BasicAuth;
+
+impl Modify for BasicAuth {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "basic",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+        );
+    }
+}
+
+/// `#[utoipa::path]` takes a single content type per request body:
+/// the API also accepts forms.
+struct FormBodies;
+
+impl Modify for FormBodies {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let body = openapi
+            .paths
+            .paths
+            .get_mut("/api/v1/subscriptions")
+            .and_then(|item| item.operations.values_mut().next())
+            .and_then(|operation| operation.request_body.as_mut());
+        if let Some(body) = body {
+            body.content.insert(
+                "application/x-www-form-urlencoded".into(),
+                ContentBuilder::new()
+                    .schema(Ref::from_schema_name("SubscriptionRequest"))
+                    .build(),
+            );
+        }
+    }
+}
+
+pub async fn openapi_json() -> Json<OpenApiDocument> {
+    Json(ApiDoc::openapi())
+}
+
+/// Swagger UI, for the local environment only.
+pub async fn swagger_ui_page(
+    Extension(templates): Extension<Templates>,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let mut context = tera::Context::new();
+    context.insert("spec_url", "/api-docs/openapi.json");
+    templates.render("swagger_ui.html", &context).map(Html).map_err(e500)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use serde_json::Value;
+    use utoipa::OpenApi;
+    use super::ApiDoc;
+
+    fn spec() -> Value {
+        serde_json::to_value(ApiDoc::openapi()).unwrap()
+    }
+
+    #[test]
+    fn the_checked_in_spec_is_up_to_date() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("docs/openapi.json");
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            let generated = ApiDoc::openapi().to_pretty_json().unwrap();
+            std::fs::write(&path, generated + "\n").unwrap();
+        }
+
+        let checked_in: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert!(
+            checked_in == spec(),
+            "docs/openapi.json does not match the handlers anymore. \
+            Run `UPDATE_OPENAPI=1 cargo test --lib api_docs` and review the diff."
+        );
+    }
+
+    #[test]
+    fn every_reference_points_to_a_schema() {
+        fn references(value: &Value, found: &mut Vec<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(reference)) = map.get("$ref") {
+                        found.push(reference.clone());
+                    }
+                    map.values().for_each(|value| references(value, found));
+                }
+                Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+                _ => {}
+            }
+        }
+        let spec = spec();
+        let mut found = Vec::new();
+        references(&spec, &mut found);
+
+        assert!(!found.is_empty());
+        for reference in found {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            assert!(
+                spec["components"]["schemas"].get(name).is_some(),
+                "{} does not point to a schema.",
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn the_admin_area_is_not_documented() {
+        let spec = spec();
+        let paths = spec["paths"].as_object().unwrap();
+
+        assert!(paths.keys().all(|path| !path.starts_with("/admin")));
+    }
+}
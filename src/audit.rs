@@ -0,0 +1,45 @@
+use std::net::IpAddr;
+use chrono::Utc;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// Something worth keeping a record of: who did it, to whom and from where.
+#[derive(Debug, Default)]
+pub struct AuditEvent<'a> {
+    pub event: &'a str,
+    pub username: Option<&'a str>,
+    pub ip_address: Option<IpAddr>,
+    pub actor_user_id: Option<Uuid>,
+    pub details: Option<String>,
+}
+
+impl<'a> AuditEvent<'a> {
+    pub fn new(event: &'a str) -> Self {
+        Self { event, ..Default::default() }
+    }
+}
+
+#[tracing::instrument(name = "Record an audit event", skip(executor), fields(event = %event.event))]
+pub async fn record_audit_event(
+    executor: impl PgExecutor<'_>,
+    event: AuditEvent<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO audit_log
+                (audit_log_id, event, username, ip_address, actor_user_id, details, occurred_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        event.event,
+        event.username,
+        event.ip_address.map(|ip| ip.to_string()),
+        event.actor_user_id,
+        event.details,
+        Utc::now(),
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+use anyhow::Context;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use hyper::{header::LOCATION, StatusCode};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::domain::Role;
+use crate::flash_messages::FlashMessage;
+use crate::session::TypedSession;
+use crate::templates::ErrorPage;
+use super::UserId;
+
+/// Something only some roles are allowed to do.
+pub trait Permission: Send + Sync + 'static {
+    /// The least privileged role that is granted the permission.
+    const REQUIRED_ROLE: Role;
+
+    fn is_granted_to(role: Role) -> bool {
+        role >= Self::REQUIRED_ROLE
+    }
+}
+
+/// Send newsletter issues to every subscriber.
+#[derive(Debug)]
+pub struct PublishNewsletters;
+
+impl Permission for PublishNewsletters {
+    const REQUIRED_ROLE: Role = Role::Editor;
+}
+
+/// Approve or reject the reviews sent by subscribers.
+#[derive(Debug)]
+pub struct ModerateReviews;
+
+impl Permission for ModerateReviews {
+    const REQUIRED_ROLE: Role = Role::Editor;
+}
+
+/// Browse, export, confirm and unsubscribe subscribers.
+#[derive(Debug)]
+pub struct ManageSubscribers;
+
+impl Permission for ManageSubscribers {
+    const REQUIRED_ROLE: Role = Role::Editor;
+}
+
+/// Remove a subscriber and everything attached to them for good.
+#[derive(Debug)]
+pub struct DeleteSubscribers;
+
+impl Permission for DeleteSubscribers {
+    const REQUIRED_ROLE: Role = Role::Admin;
+}
+
+/// Create, disable, unlock and change the role of users.
+#[derive(Debug)]
+pub struct ManageUsers;
+
+impl Permission for ManageUsers {
+    const REQUIRED_ROLE: Role = Role::Admin;
+}
+
+/// The user attached to the current session, as currently stored:
+/// role changes and disabled accounts take effect on their next request.
+#[derive(Clone, Debug)]
+pub struct CurrentUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: Role,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    Arc<PgPool>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Loaded once per request, even if several extractors ask for it.
+        if let Some(user) = parts.extensions.get::<CurrentUser>() {
+            return Ok(user.clone());
+        }
+        let user_id = UserId::from_request_parts(parts, state).await?;
+        let pool = Arc::<PgPool>::from_ref(state);
+
+        match get_enabled_user(&pool, *user_id).await {
+            Ok(Some(user)) => {
+                parts.extensions.insert(user.clone());
+                Ok(user)
+            },
+            Ok(None) => {
+                let session = TypedSession::from_request_parts(parts, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                session.log_out();
+                FlashMessage::error("Your account has been disabled.").send();
+                Err((StatusCode::SEE_OTHER, [(LOCATION, "/login")]).into_response())
+            },
+            Err(e) => {
+                tracing::error!("\nServer error: {:?}", e);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                    .into_response())
+            }
+        }
+    }
+}
+
+/// The current user, if their role grants them the permission `P`.
+/// Other users get a 403.
+#[derive(Debug)]
+pub struct Authorized<P> {
+    pub user: CurrentUser,
+    permission: PhantomData<P>,
+}
+
+impl<P> std::ops::Deref for Authorized<P> {
+    type Target = CurrentUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user
+    }
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for Authorized<P>
+where
+    Arc<PgPool>: FromRef<S>,
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = CurrentUser::from_request_parts(parts, state).await?;
+        if !P::is_granted_to(user.role) {
+            tracing::warn!(user_id = %user.user_id, role = %user.role, "\nPermission denied.");
+            let page = ErrorPage::new("Forbidden", "You are not allowed to do this.")
+                .back_to("/admin/dashboard", "Back to the dashboard");
+            return Err((StatusCode::FORBIDDEN, page).into_response());
+        }
+        Ok(Self { user, permission: PhantomData })
+    }
+}
+
+/// `None` if the user does not exist (anymore) or has been disabled.
+#[tracing::instrument(name = "Get enabled user", skip(pool))]
+pub async fn get_enabled_user(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<CurrentUser>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+            SELECT username, role FROM users
+                WHERE user_id = $1 AND disabled_at IS NULL
+        "#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a user.")?;
+
+    row.map(|row| {
+        let role = Role::parse(&row.role).map_err(anyhow::Error::msg)?;
+        Ok(CurrentUser { user_id, username: row.username, role })
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::Role;
+    use super::{DeleteSubscribers, ManageSubscribers, ManageUsers, Permission, PublishNewsletters};
+
+    #[test]
+    fn publishing_requires_an_editor() {
+        assert!(!PublishNewsletters::is_granted_to(Role::Viewer));
+        assert!(PublishNewsletters::is_granted_to(Role::Editor));
+        assert!(PublishNewsletters::is_granted_to(Role::Admin));
+    }
+
+    #[test]
+    fn managing_subscribers_requires_an_editor() {
+        assert!(!ManageSubscribers::is_granted_to(Role::Viewer));
+        assert!(ManageSubscribers::is_granted_to(Role::Editor));
+        assert!(ManageSubscribers::is_granted_to(Role::Admin));
+    }
+
+    #[test]
+    fn deleting_subscribers_requires_an_admin() {
+        assert!(!DeleteSubscribers::is_granted_to(Role::Viewer));
+        assert!(!DeleteSubscribers::is_granted_to(Role::Editor));
+        assert!(DeleteSubscribers::is_granted_to(Role::Admin));
+    }
+
+    #[test]
+    fn managing_users_requires_an_admin() {
+        assert!(!ManageUsers::is_granted_to(Role::Viewer));
+        assert!(!ManageUsers::is_granted_to(Role::Editor));
+        assert!(ManageUsers::is_granted_to(Role::Admin));
+    }
+}
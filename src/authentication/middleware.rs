@@ -0,0 +1,52 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use hyper::{header::LOCATION, StatusCode};
+use uuid::Uuid;
+use crate::session::TypedSession;
+
+/// The id of the user attached to the current session.
+/// Anonymous requests are redirected to the login form.
+#[derive(Copy, Clone, Debug)]
+pub struct UserId(Uuid);
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::ops::Deref for UserId {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for UserId
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = TypedSession::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        match session.get_user_id() {
+            Ok(Some(user_id)) => Ok(UserId(user_id)),
+            Ok(None) => Err((StatusCode::SEE_OTHER, [(LOCATION, "/login")]).into_response()),
+            Err(e) => {
+                tracing::error!("\nFailed to read the user id from the session: {:?}", e);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                    .into_response())
+            }
+        }
+    }
+}
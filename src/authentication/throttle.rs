@@ -0,0 +1,422 @@
+use std::net::IpAddr;
+use std::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::audit::{record_audit_event, AuditEvent};
+use super::{validate_credentials, AuthError, Credentials};
+
+/// How hard we push back on somebody guessing passwords.
+#[derive(Clone, Debug)]
+pub struct LoginThrottle {
+    /// Failures (per username) that lock the account.
+    pub max_failures: u32,
+    /// How long a locked account stays locked.
+    pub lockout_duration: Duration,
+    /// How far back failures are taken into account.
+    pub failure_window: Duration,
+    /// The delay after the first failure, doubled after every other one.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl LoginThrottle {
+    /// How long to wait before checking a password, given the number
+    /// of recent failures for that username or IP address.
+    pub fn delay(&self, n_failures: u32) -> Duration {
+        if n_failures == 0 {
+            return Duration::ZERO;
+        }
+        let exponent = (n_failures - 1).min(16);
+        self.base_delay
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_delay)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ThrottledAuthError {
+    #[error("Too many failed login attempts, try again later.")]
+    AccountLocked { retry_after: Duration },
+    #[error(transparent)]
+    AuthError(#[from] AuthError),
+}
+
+/// `validate_credentials`, slowed down after failed attempts and refused
+/// altogether while the account is locked.
+/// The password is checked even for locked accounts, so that the response
+/// time does not tell whether the username exists.
+#[tracing::instrument(
+    name = "Validate credentials with throttling",
+    skip(credentials, throttle, pool),
+    fields(username = %credentials.username)
+)]
+pub async fn validate_credentials_throttled(
+    credentials: Credentials,
+    ip_address: IpAddr,
+    throttle: &LoginThrottle,
+    pool: &PgPool,
+) -> Result<Uuid, ThrottledAuthError> {
+    let username = credentials.username.clone();
+    let (admission, n_failures) = admit_attempt(pool, &username, ip_address, throttle)
+        .await
+        .map_err(AuthError::UnexpectedError)?;
+    tokio::time::sleep(throttle.delay(n_failures)).await;
+
+    let outcome = validate_credentials(credentials, pool).await;
+
+    let attempt_id = match admission {
+        Admission::Admitted { attempt_id } => attempt_id,
+        Admission::Refused { retry_after } => {
+            record_audit_event(pool, AuditEvent {
+                username: Some(&username),
+                ip_address: Some(ip_address),
+                details: Some("The account is locked.".into()),
+                ..AuditEvent::new("login_failed")
+            })
+            .await
+            .context("Failed to record a rejected login attempt.")
+            .map_err(AuthError::UnexpectedError)?;
+            return Err(ThrottledAuthError::AccountLocked { retry_after });
+        },
+    };
+
+    match outcome {
+        Ok(user_id) => {
+            clear_failures(pool, &username)
+                .await
+                .map_err(AuthError::UnexpectedError)?;
+            Ok(user_id)
+        },
+        Err(AuthError::InvalidCredentials(e)) => {
+            record_failure(pool, &username, ip_address, throttle)
+                .await
+                .map_err(AuthError::UnexpectedError)?;
+            Err(AuthError::InvalidCredentials(e).into())
+        },
+        Err(e) => {
+            forget_attempt(pool, attempt_id)
+                .await
+                .map_err(AuthError::UnexpectedError)?;
+            Err(e.into())
+        },
+    }
+}
+
+/// Lift a lockout and forget the failures that led to it.
+/// Returns `false` if the account was not locked.
+#[tracing::instrument(name = "Unlock an account", skip(pool))]
+pub async fn unlock_account(
+    pool: &PgPool,
+    username: &str,
+    admin_user_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let n_deleted = sqlx::query!(
+        r#"
+            DELETE FROM account_lockouts
+                WHERE username = $1 AND locked_until > now()
+        "#,
+        username
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete a lockout.")?
+    .rows_affected();
+    if n_deleted == 0 {
+        return Ok(false);
+    }
+    sqlx::query!(
+        r#"DELETE FROM failed_login_attempts WHERE username = $1"#,
+        username
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete failed login attempts.")?;
+    record_audit_event(&mut *transaction, AuditEvent {
+        username: Some(username),
+        actor_user_id: Some(admin_user_id),
+        ..AuditEvent::new("account_unlocked")
+    })
+    .await
+    .context("Failed to record an unlock.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to unlock an account.")?;
+
+    Ok(true)
+}
+
+pub struct Lockout {
+    pub username: String,
+    pub locked_at: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>,
+}
+
+/// Accounts that are currently locked, most recent first.
+#[tracing::instrument(skip_all)]
+pub async fn get_active_lockouts(pool: &PgPool) -> Result<Vec<Lockout>, anyhow::Error> {
+    let lockouts = sqlx::query_as!(
+        Lockout,
+        r#"
+            SELECT username, locked_at, locked_until FROM account_lockouts
+                WHERE locked_until > now()
+                ORDER BY locked_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the active lockouts.")?;
+
+    Ok(lockouts)
+}
+
+enum Admission {
+    /// The attempt is recorded as a failure until the password proves otherwise.
+    Admitted { attempt_id: Uuid },
+    Refused { retry_after: Duration },
+}
+
+/// Let the attempt through, unless the account is locked or enough guesses
+/// to lock it are already being checked. Also returns the larger of the
+/// username's and the IP address' recent failures, to slow the attempt down.
+/// Attempts on the same username take turns here: concurrent guesses see
+/// each other's failures, and no more than `max_failures` are ever checked.
+#[tracing::instrument(skip(pool, throttle))]
+async fn admit_attempt(
+    pool: &PgPool,
+    username: &str,
+    ip_address: IpAddr,
+    throttle: &LoginThrottle,
+) -> Result<(Admission, u32), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    // Released with the transaction. `pg_advisory_xact_lock` returns `void`,
+    // which the query macros cannot describe.
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind(username)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to lock the login attempts of a username.")?;
+
+    let lockout_end = sqlx::query_scalar!(
+        r#"SELECT locked_until FROM account_lockouts WHERE username = $1"#,
+        username
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to check whether an account is locked.")?;
+    let failures = sqlx::query!(
+        r#"
+            SELECT
+                COUNT(*) FILTER (WHERE username = $1) AS "by_username!",
+                COUNT(*) FILTER (WHERE ip_address = $2) AS "by_ip_address!",
+                COUNT(*) FILTER (
+                    WHERE username = $1 AND ($4::timestamptz IS NULL OR attempted_at > $4)
+                ) AS "since_lockout!"
+                FROM failed_login_attempts
+                WHERE attempted_at > now() - $3 * interval '1 second'
+                    AND (username = $1 OR ip_address = $2)
+        "#,
+        username,
+        ip_address.to_string(),
+        throttle.failure_window.as_secs() as f64,
+        lockout_end,
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to count failed login attempts.")?;
+    let n_failures = failures.by_username.max(failures.by_ip_address) as u32;
+
+    let admission = match lockout_end.filter(|&locked_until| locked_until > Utc::now()) {
+        Some(locked_until) => Admission::Refused {
+            retry_after: (locked_until - Utc::now()).to_std().unwrap_or_default(),
+        },
+        // The lockout is on its way, if the guesses in flight are wrong.
+        None if failures.since_lockout >= throttle.max_failures as i64 => Admission::Refused {
+            retry_after: throttle.lockout_duration,
+        },
+        None => {
+            let attempt_id = Uuid::new_v4();
+            sqlx::query!(
+                r#"
+                    INSERT INTO failed_login_attempts (attempt_id, username, ip_address, attempted_at)
+                        VALUES ($1, $2, $3, now())
+                "#,
+                attempt_id,
+                username,
+                ip_address.to_string()
+            )
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to record a login attempt.")?;
+            Admission::Admitted { attempt_id }
+        },
+    };
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to admit a login attempt.")?;
+
+    Ok((admission, n_failures))
+}
+
+/// An attempt that failed for reasons of our own is not held against anyone.
+#[tracing::instrument(skip(pool))]
+async fn forget_attempt(pool: &PgPool, attempt_id: Uuid) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM failed_login_attempts WHERE attempt_id = $1"#,
+        attempt_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to forget a login attempt.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool, throttle))]
+async fn record_failure(
+    pool: &PgPool,
+    username: &str,
+    ip_address: IpAddr,
+    throttle: &LoginThrottle,
+) -> Result<(), anyhow::Error> {
+    // The attempt itself was recorded by `admit_attempt`.
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    record_audit_event(&mut *transaction, AuditEvent {
+        username: Some(username),
+        ip_address: Some(ip_address),
+        details: Some("Invalid credentials.".into()),
+        ..AuditEvent::new("login_failed")
+    })
+    .await
+    .context("Failed to audit a failed login attempt.")?;
+
+    let n_failures = sqlx::query_scalar!(
+        r#"
+            SELECT COUNT(*) AS "count!" FROM failed_login_attempts
+                WHERE username = $1 AND attempted_at > now() - $2 * interval '1 second'
+        "#,
+        username,
+        throttle.failure_window.as_secs() as f64
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to count failed login attempts.")?;
+    if n_failures >= throttle.max_failures as i64 {
+        let locked_until = Utc::now() + chrono::Duration::from_std(throttle.lockout_duration)
+            .context("The lockout duration is out of range.")?;
+        sqlx::query!(
+            r#"
+                INSERT INTO account_lockouts (username, locked_at, locked_until)
+                    VALUES ($1, now(), $2)
+                    ON CONFLICT (username) DO UPDATE
+                        SET locked_at = now(), locked_until = EXCLUDED.locked_until
+            "#,
+            username,
+            locked_until
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to lock an account.")?;
+        record_audit_event(&mut *transaction, AuditEvent {
+            username: Some(username),
+            ip_address: Some(ip_address),
+            details: Some(format!("{} failed attempts.", n_failures)),
+            ..AuditEvent::new("account_locked")
+        })
+        .await
+        .context("Failed to audit a lockout.")?;
+        tracing::warn!(username, "\nAccount locked after {} failed login attempts.", n_failures);
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to record a failed login attempt.")?;
+
+    Ok(())
+}
+
+/// Forget the failures that fell out of the window: those of unknown
+/// usernames, or of addresses that never came back, are never cleared
+/// by a successful login.
+#[tracing::instrument(name = "Delete stale failed login attempts", skip(pool))]
+pub async fn delete_stale_login_failures(
+    pool: &PgPool,
+    failure_window: Duration,
+) -> Result<u64, anyhow::Error> {
+    let n_deleted = sqlx::query!(
+        r#"
+            DELETE FROM failed_login_attempts
+                WHERE attempted_at < now() - $1 * interval '1 second'
+        "#,
+        failure_window.as_secs() as f64
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete stale failed login attempts.")?
+    .rows_affected();
+
+    Ok(n_deleted)
+}
+
+#[tracing::instrument(skip(pool))]
+async fn clear_failures(pool: &PgPool, username: &str) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM failed_login_attempts WHERE username = $1"#,
+        username
+    )
+    .execute(pool)
+    .await
+    .context("Failed to clear failed login attempts.")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::LoginThrottle;
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle {
+            max_failures: 5,
+            lockout_duration: Duration::from_secs(900),
+            failure_window: Duration::from_secs(900),
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn there_is_no_delay_without_failures() {
+        assert_eq!(throttle().delay(0), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_with_every_failure() {
+        let throttle = throttle();
+        assert_eq!(throttle.delay(1), Duration::from_millis(200));
+        assert_eq!(throttle.delay(2), Duration::from_millis(400));
+        assert_eq!(throttle.delay(3), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn the_delay_is_capped() {
+        let throttle = throttle();
+        assert_eq!(throttle.delay(10), Duration::from_secs(5));
+        assert_eq!(throttle.delay(u32::MAX), Duration::from_secs(5));
+    }
+}
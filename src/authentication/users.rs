@@ -0,0 +1,254 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::audit::{record_audit_event, AuditEvent};
+use crate::domain::{NewPassword, Role};
+use crate::telemetry::spawn_blocking_with_tracing;
+use super::compute_password_hash;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CreateUserError {
+    #[error("This username is already taken.")]
+    UsernameTaken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+pub struct User {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub disabled_at: Option<DateTime<Utc>>,
+}
+
+/// Every user, enabled or not, in alphabetical order.
+#[tracing::instrument(skip_all)]
+pub async fn get_users(pool: &PgPool) -> Result<Vec<User>, anyhow::Error> {
+    sqlx::query!(
+        r#"
+            SELECT user_id, username, role, disabled_at FROM users
+                ORDER BY username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the users.")?
+    .into_iter()
+    .map(|row| {
+        let role = Role::parse(&row.role).map_err(anyhow::Error::msg)?;
+        Ok(User {
+            user_id: row.user_id,
+            username: row.username,
+            role,
+            disabled_at: row.disabled_at,
+        })
+    })
+    .collect()
+}
+
+/// `actor_user_id` is the admin creating the account, if any.
+#[tracing::instrument(skip(pool, password))]
+pub async fn create_user(
+    pool: &PgPool,
+    username: &str,
+    password: NewPassword,
+    role: Role,
+    actor_user_id: Option<Uuid>,
+) -> Result<Uuid, CreateUserError> {
+    let password: Secret<String> = password.into();
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn a blocking task.")?
+        .context("Failed to hash the password.")?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let user_id = Uuid::new_v4();
+    let n_inserted = sqlx::query!(
+        r#"
+            INSERT INTO users (user_id, username, password_hash, role)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (username) DO NOTHING
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        role.as_str(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to insert a new user.")?
+    .rows_affected();
+    if n_inserted == 0 {
+        return Err(CreateUserError::UsernameTaken);
+    }
+    record_audit_event(&mut *transaction, AuditEvent {
+        username: Some(username),
+        actor_user_id,
+        details: Some(format!("role: {}", role)),
+        ..AuditEvent::new("user_created")
+    })
+    .await
+    .context("Failed to record the creation of a user.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to create a user.")?;
+
+    Ok(user_id)
+}
+
+/// `false` if there is no such user.
+#[tracing::instrument(skip(pool))]
+pub async fn change_role(
+    pool: &PgPool,
+    user_id: Uuid,
+    role: Role,
+    actor_user_id: Option<Uuid>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let Some(row) = sqlx::query!(
+        r#"UPDATE users SET role = $1 WHERE user_id = $2 RETURNING username"#,
+        role.as_str(),
+        user_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to change the role of a user.")?
+    else {
+        return Ok(false);
+    };
+    record_audit_event(&mut *transaction, AuditEvent {
+        username: Some(&row.username),
+        actor_user_id,
+        details: Some(format!("role: {}", role)),
+        ..AuditEvent::new("user_role_changed")
+    })
+    .await
+    .context("Failed to record a role change.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change a role.")?;
+
+    Ok(true)
+}
+
+/// Disable (or enable back) an account. `false` if there is no such user.
+#[tracing::instrument(skip(pool))]
+pub async fn set_user_disabled(
+    pool: &PgPool,
+    user_id: Uuid,
+    disabled: bool,
+    actor_user_id: Option<Uuid>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    // Disabling twice keeps the original date.
+    let Some(row) = sqlx::query!(
+        r#"
+            UPDATE users
+                SET disabled_at = CASE WHEN $1::boolean THEN COALESCE(disabled_at, now()) END
+                WHERE user_id = $2
+                RETURNING username
+        "#,
+        disabled,
+        user_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to change the status of a user.")?
+    else {
+        return Ok(false);
+    };
+    let event = if disabled { "user_disabled" } else { "user_enabled" };
+    record_audit_event(&mut *transaction, AuditEvent {
+        username: Some(&row.username),
+        actor_user_id,
+        ..AuditEvent::new(event)
+    })
+    .await
+    .context("Failed to record a status change.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change the status of a user.")?;
+
+    Ok(true)
+}
+
+/// Overwrite the password of `username`, e.g. when they are locked out
+/// of their account: the lockout and past failed logins are cleared too.
+/// `false` if there is no such user.
+#[tracing::instrument(skip(pool, password))]
+pub async fn reset_password(
+    pool: &PgPool,
+    username: &str,
+    password: NewPassword,
+    actor_user_id: Option<Uuid>,
+) -> Result<bool, anyhow::Error> {
+    let password: Secret<String> = password.into();
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn a blocking task.")?
+        .context("Failed to hash the password.")?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let n_updated = sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE username = $2"#,
+        password_hash.expose_secret(),
+        username
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to reset the password of a user.")?
+    .rows_affected();
+    if n_updated == 0 {
+        return Ok(false);
+    }
+    // Otherwise a locked out user would still be turned away with the new password.
+    let n_unlocked = sqlx::query!(
+        r#"
+            DELETE FROM account_lockouts
+                WHERE username = $1 AND locked_until > now()
+        "#,
+        username
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete a lockout.")?
+    .rows_affected();
+    sqlx::query!(
+        r#"DELETE FROM failed_login_attempts WHERE username = $1"#,
+        username
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete failed login attempts.")?;
+    record_audit_event(&mut *transaction, AuditEvent {
+        username: Some(username),
+        actor_user_id,
+        details: (n_unlocked > 0).then(|| "The account was unlocked.".into()),
+        ..AuditEvent::new("password_reset")
+    })
+    .await
+    .context("Failed to record a password reset.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset a password.")?;
+
+    Ok(true)
+}
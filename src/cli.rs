@@ -0,0 +1,282 @@
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use crate::authentication::{self, CreateUserError};
+use crate::configuration::Settings;
+use crate::domain::{NewPassword, Role, SubscriberEmail};
+use crate::startup::get_connection_pool;
+use crate::subscribers::{
+    get_subscribers, ImportReport, ImportStatus, SubscriberFilter, SubscriberImport,
+};
+use crate::templates::Templates;
+
+/// The newsletter server, and a few operational tasks.
+/// Every command reads the same configuration as the server.
+#[derive(Parser, Debug)]
+#[command(name = "myweb", version)]
+pub struct Cli {
+    /// `serve` when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Start the HTTP server and the delivery worker.
+    Serve,
+    /// Apply the pending database migrations.
+    Migrate,
+    /// Create a user; their password is prompted for.
+    CreateUser {
+        #[arg(long)]
+        username: String,
+        #[arg(long, default_value = "admin", value_parser = Role::parse)]
+        role: Role,
+        /// Read the password from the first line of stdin instead of prompting.
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Set a new password for an existing user.
+    ResetPassword {
+        #[arg(long)]
+        username: String,
+        /// Read the password from the first line of stdin instead of prompting.
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Print the subscribers as tab-separated values.
+    ListSubscribers {
+        #[arg(long, value_parser = crate::subscribers::STATUSES)]
+        status: Option<String>,
+    },
+    /// Import subscribers from a CSV file with `email` and `name` columns.
+    /// The rows that were left out are printed as tab-separated values.
+    ImportSubscribers {
+        /// `-` reads the file from stdin.
+        path: PathBuf,
+        /// `pending` subscribers are sent a confirmation email by the delivery worker.
+        #[arg(long, default_value = "pending", value_parser = ImportStatus::parse)]
+        status: ImportStatus,
+    },
+    /// Send an email through the configured backend.
+    SendTestEmail {
+        #[arg(long)]
+        to: String,
+    },
+}
+
+/// Run any command but `serve`, which `main` takes care of.
+pub async fn run(command: Command, configuration: Settings) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    match command {
+        Command::Serve => unreachable!("`serve` is handled by `main`."),
+        Command::Migrate => {
+            sqlx::migrate!("./migrations")
+                .run(&pool)
+                .await
+                .context("Failed to migrate the database.")?;
+            eprintln!("The database is up to date.");
+        }
+        Command::CreateUser { username, role, password_stdin } => {
+            let password = read_new_password(password_stdin)?;
+            match authentication::create_user(&pool, &username, password, role, None).await {
+                Ok(_) => eprintln!("{} has been added as {}.", username, role),
+                Err(CreateUserError::UsernameTaken) => {
+                    anyhow::bail!("{} is already taken.", username)
+                }
+                Err(CreateUserError::UnexpectedError(e)) => return Err(e),
+            }
+        }
+        Command::ResetPassword { username, password_stdin } => {
+            let password = read_new_password(password_stdin)?;
+            if !authentication::reset_password(&pool, &username, password, None).await? {
+                anyhow::bail!("There is no user named {}.", username);
+            }
+            eprintln!("The password of {} has been reset.", username);
+        }
+        Command::ListSubscribers { status } => {
+            let filter = SubscriberFilter { status, ..Default::default() };
+            println!("email\tname\tstatus\tsubscribed_at");
+            for subscriber in get_subscribers(&pool, &filter, None, 0).await? {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    subscriber.email,
+                    &subscriber.name,
+                    subscriber.status,
+                    subscriber.subscribed_at.to_rfc3339(),
+                );
+            }
+        }
+        Command::ImportSubscribers { path, status } => {
+            let report = import_subscribers(&pool, &path, status).await?;
+            println!("row\temail\treason");
+            for rejected in &report.rejected {
+                println!(
+                    "{}\t{}\t{}",
+                    rejected.row,
+                    rejected.email.as_deref().unwrap_or_default(),
+                    rejected.reason,
+                );
+            }
+            if report.n_rejected > report.rejected.len() as u64 {
+                eprintln!("Only the first {} rejected rows are listed.", report.rejected.len());
+            }
+            eprintln!(
+                "{} subscribers imported as {}, {} duplicates skipped, {} rows rejected.",
+                report.n_imported,
+                status.as_str(),
+                report.n_duplicates,
+                report.n_rejected,
+            );
+        }
+        Command::SendTestEmail { to } => {
+            let recipient = SubscriberEmail::parse(to).map_err(anyhow::Error::msg)?;
+            let templates = Templates::new(false).context("Failed to load the templates.")?;
+            send_test_email(&configuration, &templates, &recipient).await?;
+            eprintln!("A test email has been sent to {}.", recipient.as_ref());
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+async fn import_subscribers(
+    pool: &PgPool,
+    path: &Path,
+    status: ImportStatus,
+) -> Result<ImportReport, anyhow::Error> {
+    let mut input: Box<dyn AsyncRead + Unpin> = if path == Path::new("-") {
+        Box::new(tokio::io::stdin())
+    } else {
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}.", path.display()))?;
+        Box::new(file)
+    };
+
+    let mut import = SubscriberImport::begin(pool, status).await?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n_read = input
+            .read(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read {}.", path.display()))?;
+        if n_read == 0 {
+            break;
+        }
+        import.feed(&buffer[..n_read]).await?;
+    }
+    Ok(import.finish(None).await?)
+}
+
+#[tracing::instrument(skip(configuration, templates))]
+async fn send_test_email(
+    configuration: &Settings,
+    templates: &Templates,
+    recipient: &SubscriberEmail,
+) -> Result<(), anyhow::Error> {
+    let mut context = tera::Context::new();
+    context.insert("base_url", &configuration.application.base_url);
+    let html_body = templates
+        .render("emails/test_email.html", &context)
+        .context("Failed to render the HTML body of the test email.")?;
+    let plain_body = templates
+        .render("emails/test_email.txt", &context)
+        .context("Failed to render the text body of the test email.")?;
+
+    configuration
+        .email_client
+        .clone()
+        .client()
+        .send_email(recipient, "Test email", &html_body, &plain_body)
+        .await
+        .context("Failed to send the test email.")?;
+    Ok(())
+}
+
+fn read_new_password(from_stdin: bool) -> Result<NewPassword, anyhow::Error> {
+    let password = if from_stdin {
+        let mut line = String::new();
+        std::io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("Failed to read the password from stdin.")?;
+        Secret::new(line.trim_end_matches(['\r', '\n']).to_owned())
+    } else {
+        let password = Secret::new(
+            rpassword::prompt_password("Password: ").context("Failed to read the password.")?,
+        );
+        let password_check = rpassword::prompt_password("Confirm password: ")
+            .context("Failed to read the password.")?;
+        if password.expose_secret() != &password_check {
+            anyhow::bail!("The two passwords do not match.");
+        }
+        password
+    };
+    NewPassword::parse(password).map_err(anyhow::Error::msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser};
+    use crate::domain::Role;
+    use crate::subscribers::ImportStatus;
+    use super::{Cli, Command};
+
+    #[test]
+    fn the_cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn serve_is_the_default_command() {
+        let cli = Cli::try_parse_from(["myweb"]).unwrap();
+
+        assert_eq!(cli.command, None);
+    }
+
+    #[test]
+    fn create_user_makes_admins_by_default() {
+        let cli = Cli::try_parse_from(["myweb", "create-user", "--username", "ursula"]).unwrap();
+
+        assert_eq!(
+            cli.command,
+            Some(Command::CreateUser {
+                username: "ursula".into(),
+                role: Role::Admin,
+                password_stdin: false,
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        let args = ["myweb", "create-user", "--username", "ursula", "--role", "owner"];
+
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn imported_subscribers_are_pending_by_default() {
+        let cli = Cli::try_parse_from(["myweb", "import-subscribers", "subscribers.csv"]).unwrap();
+
+        assert_eq!(
+            cli.command,
+            Some(Command::ImportSubscribers {
+                path: "subscribers.csv".into(),
+                status: ImportStatus::Pending,
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_subscriber_statuses_are_rejected() {
+        let args = ["myweb", "list-subscribers", "--status", "bounced"];
+
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+}
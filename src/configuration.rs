@@ -1,27 +1,143 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use secrecy::{Secret, ExposeSecret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use sqlx::ConnectOptions;
 
-use crate::domain::SubscriberEmail;
+use crate::authentication::LoginThrottle;
+use crate::domain::{SubscriberEmail, SubscriberEmailError};
+use crate::rate_limit::{RateLimitLayer, RateLimiter};
+use crate::email_client::{
+    EmailClient, EmailSender, FileEmailSender, InMemoryEmailSender, PostmarkEmailSender,
+    RetryPolicy, SmtpEmailSender,
+};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    #[serde(default)]
+    pub telemetry: TelemetrySettings,
+    pub login_throttle: LoginThrottleSettings,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct LoginThrottleSettings {
+    pub max_failures: u32,
+    pub lockout_seconds: u64,
+    pub failure_window_seconds: u64,
+    pub base_delay_milliseconds: u64,
+    pub max_delay_milliseconds: u64,
+}
+
+impl LoginThrottleSettings {
+    pub fn throttle(&self) -> LoginThrottle {
+        LoginThrottle {
+            max_failures: self.max_failures,
+            lockout_duration: std::time::Duration::from_secs(self.lockout_seconds),
+            failure_window: std::time::Duration::from_secs(self.failure_window_seconds),
+            base_delay: std::time::Duration::from_millis(self.base_delay_milliseconds),
+            max_delay: std::time::Duration::from_millis(self.max_delay_milliseconds),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct TelemetrySettings {
+    /// Where to export traces over OTLP/HTTP, e.g.
+    /// `http://localhost:4318/v1/traces`. Nothing is exported when unset,
+    /// but the trace context of incoming requests is still propagated.
+    pub otlp_endpoint: Option<String>,
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
+    #[serde(default)]
+    pub backend: EmailBackend,
     pub base_url: String,
     pub sender_email: String,
     pub authorization_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    pub max_retries: u32,
+    pub retry_base_delay_milliseconds: u64,
+    pub retry_max_delay_milliseconds: u64,
+    pub smtp: Option<SmtpSettings>,
+    pub file_path: Option<String>,
+    /// Whether `/health_check/ready` checks that the backend is reachable.
+    #[serde(default)]
+    pub probe_on_readiness: bool,
+}
+
+/// Where outgoing emails end up.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailBackend {
+    /// Postmark's HTTP API, at `base_url`.
+    #[default]
+    Postmark,
+    /// An SMTP relay, see `smtp`.
+    Smtp,
+    /// An mbox file at `file_path`.
+    File,
+    /// Nowhere: emails are only kept in memory.
+    InMemory,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
 }
 
 impl EmailClientSettings {
-    pub fn sender(&self) -> Result<SubscriberEmail, String> {
+    pub fn client(self) -> EmailClient {
+        let sender_email = self.sender()
+            .expect("Invalid sender email address.");
+        let timeout = self.timeout();
+        let retry_policy = self.retry_policy();
+
+        let backend: Arc<dyn EmailSender> = match self.backend {
+            EmailBackend::Postmark => {
+                let base_url = reqwest::Url::parse(&self.base_url)
+                    .expect("Failed to parse URL");
+                Arc::new(PostmarkEmailSender::new(base_url, self.authorization_token, timeout))
+            },
+            EmailBackend::Smtp => {
+                let smtp = self.smtp
+                    .expect("The `smtp` settings are required by the SMTP backend.");
+                let sender = SmtpEmailSender::new(smtp.host, smtp.port, timeout);
+                match (smtp.username, smtp.password) {
+                    (Some(username), Some(password)) => {
+                        Arc::new(sender.with_credentials(username, password))
+                    },
+                    _ => Arc::new(sender),
+                }
+            },
+            EmailBackend::File => {
+                let path = self.file_path
+                    .expect("`file_path` is required by the file backend.");
+                Arc::new(FileEmailSender::new(path))
+            },
+            EmailBackend::InMemory => Arc::new(InMemoryEmailSender::new()),
+        };
+
+        EmailClient::new(sender_email, backend, retry_policy)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(
+            self.max_retries,
+            std::time::Duration::from_millis(self.retry_base_delay_milliseconds),
+            std::time::Duration::from_millis(self.retry_max_delay_milliseconds),
+        )
+    }
+
+    pub fn sender(&self) -> Result<SubscriberEmail, SubscriberEmailError> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
 
@@ -36,6 +152,45 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Rate limits of public routes, keyed by route path.
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RouteRateLimits>,
+    /// Re-read `templates/` on every render, to edit pages without restarting.
+    #[serde(default)]
+    pub reload_templates: bool,
+    /// Serve Swagger UI at `/api-docs`, on top of the OpenAPI document.
+    #[serde(default)]
+    pub swagger_ui: bool,
+}
+
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct RouteRateLimits {
+    /// Requests from the same IP address.
+    pub per_ip: Option<RateLimitSettings>,
+    /// Requests whose form has the same `email` field.
+    pub per_email: Option<RateLimitSettings>,
+}
+
+/// `burst` requests at once, then one more every `period_seconds`.
+#[derive(serde::Deserialize, Clone)]
+pub struct RateLimitSettings {
+    pub burst: u32,
+    pub period_seconds: u64,
+}
+
+impl RateLimitSettings {
+    pub fn limiter(&self) -> RateLimiter {
+        RateLimiter::new(self.burst, std::time::Duration::from_secs(self.period_seconds))
+    }
+}
+
+impl RouteRateLimits {
+    pub fn layer(&self) -> RateLimitLayer {
+        RateLimitLayer::new(
+            self.per_ip.as_ref().map(RateLimitSettings::limiter),
+            self.per_email.as_ref().map(RateLimitSettings::limiter),
+        )
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -65,7 +220,7 @@ impl DatabaseSettings {
         PgConnectOptions::new()
             .host(&self.host)
             .username(&self.username)
-            .password(&self.password.expose_secret())
+            .password(self.password.expose_secret())
             .port(self.port)
             .ssl_mode(ssl_mode)
     }
@@ -0,0 +1,169 @@
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::{EmailClient, SendEmailError};
+use crate::issue_delivery_worker::{retry_backoff, ExecutionOutcome, MAX_RETRIES};
+use crate::routes::{generate_subscription_token, send_confirmation_email, store_token};
+use crate::templates::Templates;
+
+/// Have the delivery worker send a confirmation email to a subscriber,
+/// for when there are too many of them to do it during the request.
+#[tracing::instrument(skip(transaction))]
+pub async fn enqueue_confirmation_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO confirmation_email_queue (subscriber_id)
+                VALUES ($1)
+                ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Send the oldest queued confirmation email. Its token is issued on the
+/// way out, so that it does not expire while waiting in the queue, and
+/// only committed along with the task once the email has been sent.
+#[tracing::instrument(skip_all, fields(subscriber_id=tracing::field::Empty), err)]
+pub async fn try_send_confirmation_email(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Templates,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    // `SKIP LOCKED`, as for the issues: several workers can share the queue.
+    let task = sqlx::query!(
+        r#"
+            SELECT q.subscriber_id, q.n_retries, s.email, s.name, s.status
+                FROM confirmation_email_queue q
+                JOIN subscriptions s ON s.id = q.subscriber_id
+                WHERE q.execute_after <= now()
+                FOR UPDATE OF q
+                SKIP LOCKED
+                LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let Some(task) = task else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("subscriber_id", display(&task.subscriber_id));
+
+    if task.status != "pending_confirmation" {
+        tracing::info!("Skipping a subscriber that is no longer pending.");
+        return delete_task(transaction, task.subscriber_id).await;
+    }
+    let new_subscriber = match (SubscriberEmail::parse(task.email), SubscriberName::parse(task.name)) {
+        (Ok(email), Ok(name)) => NewSubscriber { email, name },
+        _ => {
+            tracing::error!("Skipping a subscriber. Their stored contact details are invalid.");
+            return delete_task(transaction, task.subscriber_id).await;
+        }
+    };
+
+    // Stored before the email goes out, so that a failure to store it
+    // never leaves the subscriber with a dead link.
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, task.subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token for a subscriber.")?;
+    if let Err(e) = send_confirmation_email(
+        email_client,
+        templates,
+        new_subscriber,
+        base_url,
+        &subscription_token,
+    )
+    .await
+    {
+        let is_transient = e
+            .downcast_ref::<SendEmailError>()
+            .is_some_and(SendEmailError::is_transient);
+        discard_token(&mut transaction, &subscription_token).await?;
+        if is_transient && task.n_retries + 1 < MAX_RETRIES {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                n_retries = task.n_retries,
+                "Failed to send a confirmation email. Rescheduling it.",
+            );
+            return reschedule_task(transaction, task.subscriber_id, task.n_retries).await;
+        }
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            n_retries = task.n_retries,
+            "Failed to send a confirmation email. Giving up.",
+        );
+        return delete_task(transaction, task.subscriber_id).await;
+    }
+
+    delete_task(transaction, task.subscriber_id).await
+}
+
+/// The token of an email that could not be sent: nobody holds it.
+#[tracing::instrument(skip_all)]
+async fn discard_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscription_token = $1"#,
+        subscription_token,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to discard an unsent confirmation token.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn delete_task(
+    mut transaction: Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM confirmation_email_queue WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn reschedule_task(
+    mut transaction: Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    n_retries: i32,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    sqlx::query!(
+        r#"
+            UPDATE confirmation_email_queue
+                SET
+                    n_retries = n_retries + 1,
+                    execute_after = now() + $2 * interval '1 second'
+                WHERE subscriber_id = $1
+        "#,
+        subscriber_id,
+        retry_backoff(n_retries).as_secs() as f64,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
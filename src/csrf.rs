@@ -0,0 +1,230 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, State},
+    http::request::Parts,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, Key, SameSite, SignedCookieJar};
+use hyper::{header::CONTENT_TYPE, Body, HeaderMap, Method, Request, StatusCode};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use crate::utils::{read_body, MAX_BODY_SIZE};
+
+const CSRF_COOKIE_NAME: &str = "_csrf";
+/// The form field that carries the token.
+pub const CSRF_FIELD_NAME: &str = "csrf_token";
+/// Clients that do not post forms can send the token in this header instead.
+pub const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+
+/// The CSRF token of the current client, to be embedded in every form
+/// that posts back to us (see `templates/partials/csrf_field.html`).
+#[derive(Clone, Debug)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<CsrfToken>().cloned().ok_or_else(|| {
+            tracing::error!("Tried to render a CSRF token outside of `csrf_middleware`.");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+        })
+    }
+}
+
+/// What the handler of a multipart upload checks the token of the form
+/// against: uploads are streamed to their handler instead of being
+/// buffered by `csrf_middleware`, so the handler reads the `csrf_token`
+/// field itself, before any field it acts upon.
+#[derive(Clone, Debug)]
+pub struct UploadCsrfCheck {
+    expected: Option<String>,
+    // The upload came with a valid token in the `X-CSRF-Token` header.
+    verified: bool,
+}
+
+impl UploadCsrfCheck {
+    /// Whether the upload may proceed, given the token of its form field.
+    pub fn accepts(&self, submitted: Option<&str>) -> bool {
+        self.verified || is_valid(&self.expected, &submitted.map(ToOwned::to_owned))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for UploadCsrfCheck
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Only multipart posts get one: anything else has nothing to pass.
+        Ok(parts
+            .extensions
+            .get::<UploadCsrfCheck>()
+            .cloned()
+            .unwrap_or(UploadCsrfCheck { expected: None, verified: false }))
+    }
+}
+
+/// The answer to a state-changing request without a valid token.
+pub fn reject_invalid_token() -> Response {
+    (StatusCode::FORBIDDEN, "Invalid or missing CSRF token.").into_response()
+}
+
+/// Double-submit cookies: every client gets a random token in a signed
+/// cookie, and state-changing form posts must send the same token back in
+/// a form field (or header; uploads see `UploadCsrfCheck`). Another site
+/// can make a browser post a form to us, but it can neither read nor forge
+/// the cookie.
+/// Non-form requests (e.g. JSON) cannot be sent cross-site without CORS,
+/// they are let through.
+pub async fn csrf_middleware(
+    State(key): State<Key>,
+    mut request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let jar = SignedCookieJar::from_headers(request.headers(), key);
+    // The signature check in `SignedCookieJar::get` discards forged cookies.
+    let expected = jar
+        .get(CSRF_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+
+    if is_state_changing(request.method()) && is_multipart(&request) {
+        // Never read the token from the URL: it ends up in logs.
+        let verified = is_valid(&expected, &header_token(request.headers()));
+        request.extensions_mut().insert(UploadCsrfCheck {
+            expected: expected.clone(),
+            verified,
+        });
+    } else if is_state_changing(request.method()) && is_form(&request) {
+        let (parts, body) = request.into_parts();
+        let header_token = header_token(&parts.headers);
+        let bytes = match read_body(body, MAX_BODY_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(response) => return response,
+        };
+        let submitted = header_token.or_else(|| form_token(&bytes));
+
+        if !is_valid(&expected, &submitted) {
+            tracing::warn!("\nRejected a form post without a valid CSRF token.");
+            return reject_invalid_token();
+        }
+        request = Request::from_parts(parts, Body::from(bytes));
+    }
+
+    let (token, jar) = match expected {
+        Some(token) => (token, jar),
+        None => {
+            let token = generate_csrf_token();
+            let jar = jar.add(csrf_cookie(token.clone()));
+            (token, jar)
+        }
+    };
+    request.extensions_mut().insert(CsrfToken(token));
+
+    let response = next.run(request).await;
+    (jar, response).into_response()
+}
+
+fn header_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CSRF_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned)
+}
+
+fn is_valid(expected: &Option<String>, submitted: &Option<String>) -> bool {
+    matches!(
+        (expected, submitted),
+        (Some(expected), Some(submitted)) if constant_time_eq(expected, submitted)
+    )
+}
+
+fn is_state_changing(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+}
+
+fn content_type(request: &Request<Body>) -> String {
+    request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn is_multipart(request: &Request<Body>) -> bool {
+    content_type(request).starts_with("multipart/form-data")
+}
+
+// The content types a cross-site HTML form can send.
+fn is_form(request: &Request<Body>) -> bool {
+    let content_type = content_type(request);
+    // Browsers send forms without a body with no content type at all.
+    content_type.is_empty()
+        || content_type.starts_with("application/x-www-form-urlencoded")
+        || content_type.starts_with("text/plain")
+}
+
+#[derive(serde::Deserialize)]
+struct TokenField {
+    csrf_token: Option<String>,
+}
+
+fn form_token(body: &[u8]) -> Option<String> {
+    serde_urlencoded::from_bytes::<TokenField>(body)
+        .ok()
+        .and_then(|form| form.csrf_token)
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn csrf_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(CSRF_COOKIE_NAME, token)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+fn generate_csrf_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constant_time_eq, form_token};
+
+    #[test]
+    fn the_token_is_read_from_the_form() {
+        let body = b"username=ursula&csrf_token=abc123&password=secret";
+        assert_eq!(form_token(body).as_deref(), Some("abc123"));
+        assert_eq!(form_token(b"username=ursula"), None);
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(constant_time_eq("abc123", "abc123"));
+        assert!(!constant_time_eq("abc123", "abc124"));
+        assert!(!constant_time_eq("abc123", "abc1234"));
+    }
+}
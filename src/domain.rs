@@ -1,7 +1,17 @@
 mod subscriber_name;
 mod subscriber_email;
 mod new_subscriber;
+mod new_password;
+mod review_rating;
+mod review_content;
+mod new_review;
+mod role;
 
 pub use new_subscriber::NewSubscriber;
-pub use subscriber_name::SubscriberName;
-pub use subscriber_email::SubscriberEmail;
\ No newline at end of file
+pub use new_password::NewPassword;
+pub use subscriber_name::{SubscriberName, SubscriberNameError};
+pub use subscriber_email::{SubscriberEmail, SubscriberEmailError};
+pub use review_rating::ReviewRating;
+pub use review_content::ReviewContent;
+pub use new_review::NewReview;
+pub use role::Role;
@@ -0,0 +1,97 @@
+use secrecy::{ExposeSecret, Secret};
+use unicode_segmentation::UnicodeSegmentation;
+
+const MIN_LENGTH: usize = 12;
+const MAX_LENGTH: usize = 128;
+
+/// A password that satisfies our strength rules.
+/// It can only be built through `NewPassword::parse`.
+#[derive(Debug)]
+pub struct NewPassword(Secret<String>);
+
+impl NewPassword {
+    pub fn parse(s: Secret<String>) -> Result<NewPassword, String> {
+        let password = s.expose_secret();
+        let length = password.graphemes(true).count();
+        if length < MIN_LENGTH {
+            return Err(format!(
+                "The new password must be at least {} characters long.",
+                MIN_LENGTH
+            ));
+        }
+        if length > MAX_LENGTH {
+            return Err(format!(
+                "The new password must be at most {} characters long.",
+                MAX_LENGTH
+            ));
+        }
+
+        let character_classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_numeric()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if character_classes.iter().filter(|&&present| present).count() < 3 {
+            return Err(
+                "The new password must mix at least three of: lowercase letters, \
+                uppercase letters, digits and symbols.".into()
+            );
+        }
+
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<Secret<String>> for NewPassword {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+impl From<NewPassword> for Secret<String> {
+    fn from(p: NewPassword) -> Self {
+        p.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::NewPassword;
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn parse(s: &str) -> Result<NewPassword, String> {
+        NewPassword::parse(Secret::new(s.to_string()))
+    }
+
+    #[test]
+    fn a_password_shorter_than_12_graphemes_is_rejected() {
+        assert_err!(parse("aB3$aB3$aB3"));
+    }
+
+    #[test]
+    fn a_password_longer_than_128_graphemes_is_rejected() {
+        assert_err!(parse(&"aB3$".repeat(33)));
+    }
+
+    #[test]
+    fn a_password_with_a_single_character_class_is_rejected() {
+        assert_err!(parse("onlylowercaseletters"));
+    }
+
+    #[test]
+    fn a_password_with_two_character_classes_is_rejected() {
+        assert_err!(parse("lowercaseand12345"));
+    }
+
+    #[test]
+    fn a_long_mixed_password_is_accepted() {
+        assert_ok!(parse("Correct-Horse-Battery-Staple-42"));
+    }
+
+    #[test]
+    fn a_uuid_is_accepted() {
+        assert_ok!(parse(&uuid::Uuid::new_v4().to_string()));
+    }
+}
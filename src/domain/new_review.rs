@@ -0,0 +1,7 @@
+use crate::domain::review_content::ReviewContent;
+use crate::domain::review_rating::ReviewRating;
+
+pub struct NewReview {
+    pub rating: ReviewRating,
+    pub content: ReviewContent,
+}
@@ -0,0 +1,43 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub struct ReviewContent(String);
+
+impl ReviewContent {
+    pub fn parse(s: String) -> Result<ReviewContent, String> {
+        if s.trim().is_empty() {
+            return Err("The review must not be empty.".into());
+        }
+        if s.graphemes(true).count() > 2000 {
+            return Err("The review must be at most 2000 characters long.".into());
+        }
+        Ok(Self(s.trim().to_owned()))
+    }
+}
+
+impl AsRef<str> for ReviewContent {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::ReviewContent;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn a_2000_grapheme_long_review_is_valid() {
+        assert_ok!(ReviewContent::parse("ё".repeat(2000)));
+    }
+
+    #[test]
+    fn a_review_longer_than_2000_graphemes_is_rejected() {
+        assert_err!(ReviewContent::parse("a".repeat(2001)));
+    }
+
+    #[test]
+    fn whitespace_reviews_are_rejected() {
+        assert_err!(ReviewContent::parse(" \n\t".into()));
+    }
+}
@@ -0,0 +1,38 @@
+#[derive(Debug, Clone, Copy)]
+pub struct ReviewRating(i16);
+
+impl ReviewRating {
+    pub const MAX: i16 = 5;
+
+    pub fn parse(rating: i16) -> Result<ReviewRating, String> {
+        if (1..=Self::MAX).contains(&rating) {
+            Ok(Self(rating))
+        } else {
+            Err(format!("The rating must be between 1 and {}.", Self::MAX))
+        }
+    }
+
+    pub fn value(&self) -> i16 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::ReviewRating;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn ratings_from_1_to_5_are_valid() {
+        for rating in 1..=5 {
+            assert_ok!(ReviewRating::parse(rating));
+        }
+    }
+
+    #[test]
+    fn ratings_out_of_range_are_rejected() {
+        for rating in [-1, 0, 6] {
+            assert_err!(ReviewRating::parse(rating));
+        }
+    }
+}
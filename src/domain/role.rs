@@ -0,0 +1,64 @@
+/// What a user is allowed to do. Each role can do everything the
+/// previous ones can: viewer < editor < admin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::Editor, Role::Admin];
+
+    pub fn parse(role: &str) -> Result<Role, String> {
+        match role {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!(
+                "{} is not a valid role. Use either `viewer`, `editor` or `admin`.",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::Role;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn roles_roundtrip_through_their_name() {
+        for role in Role::ALL {
+            assert_ok_eq!(Role::parse(role.as_str()), role);
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        for role in ["", "Admin", "owner"] {
+            assert_err!(Role::parse(role));
+        }
+    }
+
+    #[test]
+    fn admins_can_do_what_editors_can() {
+        assert!(Role::Admin > Role::Editor);
+        assert!(Role::Editor > Role::Viewer);
+    }
+}
@@ -10,12 +10,30 @@ impl std::fmt::Display for SubscriberEmail {
     }
 }
 
+/// The rejected address.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("{0} is not a valid subscriber email.")]
+pub struct SubscriberEmailError(String);
+
+impl SubscriberEmailError {
+    /// A stable identifier for API clients, unlike the message.
+    pub fn code(&self) -> &'static str {
+        "email_invalid"
+    }
+}
+
+impl From<SubscriberEmailError> for String {
+    fn from(e: SubscriberEmailError) -> Self {
+        e.to_string()
+    }
+}
+
 impl SubscriberEmail {
-    pub fn parse(s: String) -> Result<SubscriberEmail, String> {
+    pub fn parse(s: String) -> Result<SubscriberEmail, SubscriberEmailError> {
         if validate_email(&s) {
             Ok(Self(s))
         } else {
-            Err(format!("{} is not a valid subscriber email.", s))
+            Err(SubscriberEmailError(s))
         }
     }
 }
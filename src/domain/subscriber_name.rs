@@ -1,19 +1,47 @@
 use unicode_segmentation::UnicodeSegmentation;
 
+const MAX_LENGTH: usize = 256;
+const FORBIDDEN_CHARACTERS: [char; 9] = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
+
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
-impl SubscriberName {
-    pub fn parse(s: String) -> Result<SubscriberName, String> {
-        let is_empty_or_whitespace = s.trim().is_empty();
+/// Why a name was rejected.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriberNameError {
+    #[error("The name cannot be empty.")]
+    Empty,
+    #[error("The name cannot be longer than {} characters.", MAX_LENGTH)]
+    TooLong,
+    #[error("The name cannot contain any of / ( ) \" < > \\ {{ }}.")]
+    ForbiddenCharacters,
+}
 
-        let is_too_long = s.graphemes(true).count() > 256;
+impl SubscriberNameError {
+    /// A stable identifier for API clients, unlike the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Empty => "name_empty",
+            Self::TooLong => "name_too_long",
+            Self::ForbiddenCharacters => "name_forbidden_characters",
+        }
+    }
+}
 
-        let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
-        let contains_forbidden_characters = s.chars().any(|g| forbidden_characters.contains(&g));
+impl From<SubscriberNameError> for String {
+    fn from(e: SubscriberNameError) -> Self {
+        e.to_string()
+    }
+}
 
-        if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
-            Err(format!("{} is not a valid subscriber name.", s))
+impl SubscriberName {
+    pub fn parse(s: String) -> Result<SubscriberName, SubscriberNameError> {
+        if s.trim().is_empty() {
+            Err(SubscriberNameError::Empty)
+        } else if s.graphemes(true).count() > MAX_LENGTH {
+            Err(SubscriberNameError::TooLong)
+        } else if s.chars().any(|c| FORBIDDEN_CHARACTERS.contains(&c)) {
+            Err(SubscriberNameError::ForbiddenCharacters)
         } else {
             Ok(Self(s))
         }
@@ -28,8 +56,8 @@ impl AsRef<str> for SubscriberName {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::SubscriberName;
-    use claims::{assert_err, assert_ok};
+    use crate::domain::{SubscriberName, SubscriberNameError};
+    use claims::{assert_err_eq, assert_ok};
 
     #[test]
     fn a_256_grapheme_long_name_is_valid() {
@@ -40,26 +68,29 @@ mod tests {
     #[test]
     fn a_name_longer_than_256_graphemes_is_rejected() {
         let name = "a".repeat(257);
-        assert_err!(SubscriberName::parse(name));
+        assert_err_eq!(SubscriberName::parse(name), SubscriberNameError::TooLong);
     }
 
     #[test]
     fn empty_names_are_rejected() {
         let name = "".to_string();
-        assert_err!(SubscriberName::parse(name));
+        assert_err_eq!(SubscriberName::parse(name), SubscriberNameError::Empty);
     }
 
     #[test]
     fn whitespace_names_are_rejected() {
         let name = " ".to_string();
-        assert_err!(SubscriberName::parse(name));
+        assert_err_eq!(SubscriberName::parse(name), SubscriberNameError::Empty);
     }
 
     #[test]
     fn names_containing_invalid_characters_are_rejected() {
         for name in &['/', '(', ')', '"', '<', '>', '\\', '{', '}'] {
             let name = name.to_string();
-            assert_err!(SubscriberName::parse(name));
+            assert_err_eq!(
+                SubscriberName::parse(name),
+                SubscriberNameError::ForbiddenCharacters
+            );
         }
     }
 
@@ -1,219 +1,264 @@
+mod error;
+mod file;
+mod in_memory;
+mod mime;
+mod postmark;
+mod retry;
+mod smtp;
+
+use std::sync::Arc;
+use axum::async_trait;
+use tracing::Span;
 use crate::domain::SubscriberEmail;
-use reqwest::Client;
-use secrecy::{Secret, ExposeSecret};
+use crate::metrics::{EMAILS_FAILED, EMAILS_SENT};
+
+pub use error::SendEmailError;
+pub use file::FileEmailSender;
+pub use in_memory::InMemoryEmailSender;
+pub use postmark::PostmarkEmailSender;
+pub use retry::RetryPolicy;
+pub use smtp::SmtpEmailSender;
+
+/// A fully addressed email, ready to be handed over to a backend.
+#[derive(Clone, Debug)]
+pub struct Email {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// A way of delivering emails: an HTTP API, an SMTP relay, a local file...
+#[async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), SendEmailError>;
+
+    /// Check that the backend is reachable, without sending anything.
+    async fn probe(&self) -> Result<(), SendEmailError> {
+        Ok(())
+    }
+}
 
 #[derive(Clone)]
 pub struct EmailClient {
-    http_client: Client,
-    base_url: reqwest::Url,
     sender: SubscriberEmail,
-    authorization_token: Secret<String>,
+    backend: Arc<dyn EmailSender>,
+    retry_policy: RetryPolicy,
 }
 
 impl EmailClient {
     pub fn new(
-        base_url: reqwest::Url,
         sender: SubscriberEmail,
-        authorization_token: Secret<String>,
-        timeout: std::time::Duration,
+        backend: Arc<dyn EmailSender>,
+        retry_policy: RetryPolicy,
     ) -> Self {
-        let http_client = Client::builder()
-            .timeout(timeout)
-            .build()
-            .unwrap();
+        Self { sender, backend, retry_policy }
+    }
 
-        Self {
-            http_client,
-            base_url,
-            sender,
-            authorization_token,
-        }
+    pub async fn probe(&self) -> Result<(), SendEmailError> {
+        self.backend.probe().await
     }
+
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
-        let url = self.base_url.join("email")
-            .expect("Failed to join url.");
-        let request_body = SendEmailRequest {
-            from: self.sender.as_ref(),
-            to: recipient.as_ref(),
-            subject,
-            html_body: html_content,
-            text_body: text_content,
-        };
-
-        let _builder = self
-            .http_client
-            .post(url)
-            .header(
-                "X-Postmark-Server-Token",
-                self.authorization_token.expose_secret()
-            )
-            .json(&request_body)
-            .send()
-            .await?
-            .error_for_status()?;
+    ) -> Result<(), SendEmailError> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
 
-        Ok(())
-    }   
-}
+    /// Same as `send_email`, with extra MIME headers (e.g. `List-Unsubscribe`).
+    /// Transient failures are retried according to the client's `RetryPolicy`.
+    #[tracing::instrument(
+        name = "Send an email",
+        skip_all,
+        fields(email.attempts = tracing::field::Empty)
+    )]
+    pub async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), SendEmailError> {
+        let email = Email {
+            from: self.sender.as_ref().to_owned(),
+            to: recipient.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            html_content: html_content.to_owned(),
+            text_content: text_content.to_owned(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        };
 
-#[derive(serde::Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct SendEmailRequest<'a> {
-    from: &'a str,
-    to: &'a str,
-    subject: &'a str,
-    html_body: &'a str,
-    text_body: &'a str,
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let outcome = self.backend.send(&email).await;
+            Span::current().record("email.attempts", attempt);
+            let Err(e) = outcome else {
+                EMAILS_SENT.inc();
+                return Ok(());
+            };
+            let Some(delay) = self.retry_policy.delay_before_retry(attempt, &e) else {
+                EMAILS_FAILED.inc();
+                return Err(e);
+            };
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                attempt,
+                retry_in_ms = delay.as_millis() as u64,
+                "Failed to send an email. Retrying.",
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use axum::async_trait;
+    use claims::assert_err;
     use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
-    use claims::{assert_ok, assert_err};
-    use fake::faker::{
-        internet::en::SafeEmail,
-        lorem::en::{Paragraph, Sentence}
-    };
-    use fake::{Fake, Faker};
-    use secrecy::Secret;
-    use wiremock::{
-        Mock, MockServer, ResponseTemplate,
-        matchers::{header_exists, header, path, method, any},
-        Request
+    use crate::email_client::{
+        Email, EmailClient, EmailSender, InMemoryEmailSender, RetryPolicy, SendEmailError,
     };
 
-    struct SendEmailBodyMatcher;
-
-    impl wiremock::Match for SendEmailBodyMatcher {
-        fn matches(&self, request: &Request) -> bool {
-            let result: Result<serde_json::Value, _> =
-                serde_json::from_slice(&request.body);
-            if let Ok(body) = result {
-                body.get("From").is_some()
-                    && body.get("To").is_some()
-                    && body.get("Subject").is_some()
-                    && body.get("HtmlBody").is_some()
-                    && body.get("TextBody").is_some()
-            } else {
-                false
-            }
-        }   
+    /// Fails with the scripted errors, in order, then succeeds.
+    struct FlakySender {
+        failures: Mutex<Vec<SendEmailError>>,
+        attempts: Mutex<u32>,
     }
 
-    fn subject() -> String {
-        Sentence(1..2).fake()
+    impl FlakySender {
+        fn new(mut failures: Vec<SendEmailError>) -> Self {
+            failures.reverse();
+            Self { failures: Mutex::new(failures), attempts: Mutex::new(0) }
+        }
+
+        fn attempts(&self) -> u32 {
+            *self.attempts.lock().unwrap()
+        }
     }
 
-    fn content() -> String {
-        Paragraph(1..10).fake()
+    #[async_trait]
+    impl EmailSender for FlakySender {
+        async fn send(&self, _email: &Email) -> Result<(), SendEmailError> {
+            *self.attempts.lock().unwrap() += 1;
+            match self.failures.lock().unwrap().pop() {
+                Some(e) => Err(e),
+                None => Ok(()),
+            }
+        }
+    }
+
+    fn address(email: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(email.into()).unwrap()
     }
 
-    fn email() -> SubscriberEmail {
-        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy::new(2, Duration::from_millis(1), Duration::from_millis(10))
     }
 
-    fn email_client(base_url: String) -> EmailClient {
-        let base_url = reqwest::Url::parse(&base_url).unwrap();
-        EmailClient::new(
-            base_url,
-            email(),
-            Secret::new(Faker.fake()),
-    std::time::Duration::from_millis(200)
-        )
+    fn unavailable() -> SendEmailError {
+        SendEmailError::Unavailable { reason: "503".into(), retry_after: None }
     }
 
     #[tokio::test]
-    async fn send_email_sends_the_expected_request() {
+    async fn send_email_hands_the_email_over_to_the_backend() {
         // Arrange
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(header_exists("X-Postmark-Server-Token"))
-            .and(header("Content-Type", "application/json"))
-            .and(path("/email"))
-            .and(method("POST"))
-            .and(SendEmailBodyMatcher)
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
+        let backend = InMemoryEmailSender::new();
+        let email_client = EmailClient::new(
+            address("sender@example.com"),
+            Arc::new(backend.clone()),
+            retry_policy(),
+        );
 
         // Act
-        let _ = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
-
-        // Assert 
+        email_client
+            .send_email_with_headers(
+                &address("recipient@example.com"),
+                "Subject",
+                "<p>Html</p>",
+                "Text",
+                &[("X-Test", "1")],
+            )
+            .await
+            .unwrap();
 
+        // Assert
+        let sent = backend.sent_emails();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].from, "sender@example.com");
+        assert_eq!(sent[0].to, "recipient@example.com");
+        assert_eq!(sent[0].subject, "Subject");
+        assert_eq!(sent[0].headers, vec![("X-Test".to_string(), "1".to_string())]);
     }
 
     #[tokio::test]
-    async fn send_email_succeeds_if_the_server_returns_200() {
+    async fn transient_failures_are_retried() {
         // Arrange
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(any())
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
+        let backend = Arc::new(FlakySender::new(vec![
+            SendEmailError::Timeout,
+            SendEmailError::RateLimited { retry_after: Some(Duration::from_millis(5)) },
+        ]));
+        let email_client =
+            EmailClient::new(address("sender@example.com"), backend.clone(), retry_policy());
 
         // Act
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(&address("recipient@example.com"), "Subject", "Html", "Text")
             .await;
 
         // Assert
-        assert_ok!(outcome);
+        assert!(outcome.is_ok());
+        assert_eq!(backend.attempts(), 3);
     }
 
     #[tokio::test]
-    async fn send_email_fails_if_server_returns_500() {
+    async fn retries_stop_after_the_configured_number_of_attempts() {
         // Arrange
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(any())
-            .respond_with(ResponseTemplate::new(500))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
+        let backend = Arc::new(FlakySender::new(vec![unavailable(), unavailable(), unavailable()]));
+        let email_client =
+            EmailClient::new(address("sender@example.com"), backend.clone(), retry_policy());
 
         // Act
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(&address("recipient@example.com"), "Subject", "Html", "Text")
             .await;
-        
+
+        // Assert
         assert_err!(outcome);
+        assert_eq!(backend.attempts(), 3);
     }
 
     #[tokio::test]
-    async fn send_email_times_out_if_the_server_takes_too_long() {
+    async fn permanent_failures_are_not_retried() {
         // Arrange
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        let response = ResponseTemplate::new(200)
-            .set_delay(std::time::Duration::from_secs(180));
-        Mock::given(any())
-            .respond_with(response)
-            .expect(1)
-            .mount(&mock_server)
-            .await;
+        let backend = Arc::new(FlakySender::new(vec![
+            SendEmailError::Rejected("Invalid recipient.".into()),
+        ]));
+        let email_client =
+            EmailClient::new(address("sender@example.com"), backend.clone(), retry_policy());
 
         // Act
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(&address("recipient@example.com"), "Subject", "Html", "Text")
             .await;
+
         // Assert
-        assert_err!(outcome);
+        assert!(matches!(outcome, Err(SendEmailError::Rejected(_))));
+        assert_eq!(backend.attempts(), 1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+use crate::routes::error_chain_fmt;
+
+#[derive(thiserror::Error)]
+pub enum SendEmailError {
+    /// HTTP 429: we are sending too much, too fast.
+    #[error("The email provider is rate limiting us.")]
+    RateLimited { retry_after: Option<Duration> },
+    /// HTTP 5xx or SMTP 4xx: the provider is having trouble, try again later.
+    #[error("The email provider is temporarily unavailable: {reason}")]
+    Unavailable { reason: String, retry_after: Option<Duration> },
+    #[error("Timed out while sending the email.")]
+    Timeout,
+    #[error("Failed to reach the email provider.")]
+    Connection(#[source] anyhow::Error),
+    /// HTTP 4xx or SMTP 5xx: sending the same email again will not help,
+    /// e.g. the recipient does not exist.
+    #[error("The email provider rejected the email: {0}")]
+    Rejected(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SendEmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl SendEmailError {
+    /// Whether trying again later has a chance of succeeding.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RateLimited { .. }
+            | Self::Unavailable { .. }
+            | Self::Timeout
+            | Self::Connection(_) => true,
+            Self::Rejected(_) | Self::UnexpectedError(_) => false,
+        }
+    }
+
+    /// How long the provider asked us to wait before trying again.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after } | Self::Unavailable { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use anyhow::Context;
+use axum::async_trait;
+use chrono::Utc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use super::{mime::render_message, Email, EmailSender, SendEmailError};
+
+/// Appends every email to a local mbox file, which any mail client can open.
+/// Meant for local development.
+pub struct FileEmailSender {
+    path: PathBuf,
+    // Appends from concurrent requests must not interleave.
+    lock: Mutex<()>,
+}
+
+impl FileEmailSender {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSender for FileEmailSender {
+    async fn send(&self, email: &Email) -> Result<(), SendEmailError> {
+        let message = render_message(email)?.replace("\r\n", "\n");
+        let entry = format!(
+            "From {} {}\n{}\n",
+            email.from,
+            Utc::now().format("%a %b %e %H:%M:%S %Y"),
+            message
+        );
+
+        let _guard = self.lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {}.", self.path.display()))?;
+        file.write_all(entry.as_bytes())
+            .await
+            .with_context(|| format!("Failed to write to {}.", self.path.display()))?;
+        // Tokio only hands the data over to the OS on flush.
+        file.flush()
+            .await
+            .with_context(|| format!("Failed to write to {}.", self.path.display()))?;
+
+        Ok(())
+    }
+
+    async fn probe(&self) -> Result<(), SendEmailError> {
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {}.", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::email_client::{Email, EmailSender, FileEmailSender};
+
+    fn email(subject: &str) -> Email {
+        Email {
+            from: "sender@example.com".into(),
+            to: "recipient@example.com".into(),
+            subject: subject.into(),
+            html_content: "<p>Hello</p>".into(),
+            text_content: "Hello".into(),
+            headers: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn emails_are_appended_to_the_mbox_file() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("{}.mbox", uuid::Uuid::new_v4()));
+        let sender = FileEmailSender::new(&path);
+
+        // Act
+        sender.send(&email("First")).await.unwrap();
+        sender.send(&email("Second")).await.unwrap();
+
+        // Assert
+        let mbox = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mbox.matches("\nFrom sender@example.com ").count() + 1, 2);
+        assert!(mbox.starts_with("From sender@example.com "));
+        assert!(mbox.contains("Subject: First\n"));
+        assert!(mbox.contains("Subject: Second\n"));
+        assert!(!mbox.contains('\r'));
+    }
+}
@@ -0,0 +1,29 @@
+use std::sync::{Arc, Mutex};
+use axum::async_trait;
+use super::{Email, EmailSender, SendEmailError};
+
+/// Keeps every email in memory instead of delivering it.
+/// Clones share the same mailbox, so a test can keep one around
+/// to inspect what the application sent.
+#[derive(Clone, Default)]
+pub struct InMemoryEmailSender {
+    emails: Arc<Mutex<Vec<Email>>>,
+}
+
+impl InMemoryEmailSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sent_emails(&self) -> Vec<Email> {
+        self.emails.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl EmailSender for InMemoryEmailSender {
+    async fn send(&self, email: &Email) -> Result<(), SendEmailError> {
+        self.emails.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
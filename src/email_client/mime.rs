@@ -0,0 +1,125 @@
+use base64::Engine;
+use chrono::Utc;
+use uuid::Uuid;
+use super::Email;
+
+/// Render `email` as an RFC 5322 `multipart/alternative` message,
+/// with CRLF line endings.
+/// Both parts are base64-encoded: no line can then start with a `.`
+/// (SMTP) or with `From ` (mbox), and none is ever too long.
+pub fn render_message(email: &Email) -> Result<String, anyhow::Error> {
+    let boundary = format!("=_{}", Uuid::new_v4().simple());
+    let domain = email.from.rsplit('@').next().unwrap_or("localhost");
+
+    let mut headers = vec![
+        ("From".to_string(), email.from.clone()),
+        ("To".to_string(), email.to.clone()),
+        ("Subject".to_string(), encode_header_value(&email.subject)),
+        ("Date".to_string(), Utc::now().to_rfc2822()),
+        ("Message-ID".to_string(), format!("<{}@{}>", Uuid::new_v4(), domain)),
+        ("MIME-Version".to_string(), "1.0".to_string()),
+    ];
+    headers.extend(email.headers.iter().cloned());
+    headers.push((
+        "Content-Type".to_string(),
+        format!("multipart/alternative; boundary=\"{}\"", boundary),
+    ));
+
+    let mut message = String::new();
+    for (name, value) in &headers {
+        if [name, value].iter().any(|s| s.contains(['\r', '\n'])) {
+            anyhow::bail!("The `{}` header contains a line break.", name);
+        }
+        message.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    message.push_str("\r\n");
+    for (content_type, body) in [
+        ("text/plain", &email.text_content),
+        ("text/html", &email.html_content),
+    ] {
+        message.push_str(&format!(
+            "--{}\r\n\
+            Content-Type: {}; charset=utf-8\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\n\
+            {}",
+            boundary,
+            content_type,
+            base64_lines(body)
+        ));
+    }
+    message.push_str(&format!("--{}--\r\n", boundary));
+
+    Ok(message)
+}
+
+// RFC 2047 encoded-word for anything that is not plain ASCII.
+fn encode_header_value(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_owned()
+    } else {
+        format!(
+            "=?utf-8?B?{}?=",
+            base64::engine::general_purpose::STANDARD.encode(value)
+        )
+    }
+}
+
+fn base64_lines(content: &str) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(content);
+    encoded
+        .as_bytes()
+        .chunks(76)
+        .map(|line| format!("{}\r\n", std::str::from_utf8(line).unwrap()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_err;
+    use crate::email_client::Email;
+    use super::render_message;
+
+    fn email() -> Email {
+        Email {
+            from: "sender@example.com".into(),
+            to: "recipient@example.com".into(),
+            subject: "Hello".into(),
+            html_content: "<p>Hello</p>".into(),
+            text_content: "Hello".into(),
+            headers: vec![],
+        }
+    }
+
+    #[test]
+    fn the_message_carries_both_parts_and_the_extra_headers() {
+        let mut email = email();
+        email.headers.push(("List-Unsubscribe".into(), "<https://example.com>".into()));
+
+        let message = render_message(&email).unwrap();
+
+        assert!(message.contains("To: recipient@example.com\r\n"));
+        assert!(message.contains("List-Unsubscribe: <https://example.com>\r\n"));
+        assert!(message.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(message.contains("Content-Type: text/html; charset=utf-8"));
+        // "<p>Hello</p>", base64-encoded
+        assert!(message.contains("PHA+SGVsbG88L3A+"));
+    }
+
+    #[test]
+    fn non_ascii_subjects_are_encoded() {
+        let mut email = email();
+        email.subject = "Héllo".into();
+
+        let message = render_message(&email).unwrap();
+
+        assert!(message.contains("Subject: =?utf-8?B?SMOpbGxv?=\r\n"));
+    }
+
+    #[test]
+    fn header_injection_is_rejected() {
+        let mut email = email();
+        email.subject = "Hello\r\nBcc: victim@example.com".into();
+
+        assert_err!(render_message(&email));
+    }
+}
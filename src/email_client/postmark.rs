@@ -0,0 +1,419 @@
+use std::time::Duration;
+use axum::async_trait;
+use chrono::Utc;
+use reqwest::{header::{HeaderMap, RETRY_AFTER}, Client, StatusCode};
+use secrecy::{Secret, ExposeSecret};
+use super::{Email, EmailSender, SendEmailError};
+use crate::telemetry::inject_trace_context;
+
+/// Sends emails through Postmark's JSON API.
+pub struct PostmarkEmailSender {
+    http_client: Client,
+    base_url: reqwest::Url,
+    authorization_token: Secret<String>,
+}
+
+impl PostmarkEmailSender {
+    pub fn new(
+        base_url: reqwest::Url,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap();
+
+        Self {
+            http_client,
+            base_url,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSender for PostmarkEmailSender {
+    async fn send(&self, email: &Email) -> Result<(), SendEmailError> {
+        let url = self.base_url.join("email")
+            .expect("Failed to join url.");
+        let request_body = SendEmailRequest {
+            from: &email.from,
+            to: &email.to,
+            subject: &email.subject,
+            html_body: &email.html_content,
+            text_body: &email.text_content,
+            headers: email.headers
+                .iter()
+                .map(|(name, value)| EmailHeader { name, value })
+                .collect(),
+        };
+
+        let mut trace_headers = HeaderMap::new();
+        inject_trace_context(&mut trace_headers);
+        let response = self.http_client
+            .post(url)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret()
+            )
+            .headers(trace_headers)
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(classify_transport_error)?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(SendEmailError::RateLimited { retry_after });
+        }
+        if status.is_server_error() {
+            return Err(SendEmailError::Unavailable {
+                reason: status.to_string(),
+                retry_after,
+            });
+        }
+        // Postmark explains 4xx errors in the body, e.g.
+        // `{"ErrorCode": 300, "Message": "Invalid 'To' address: ..."}`
+        let message = response
+            .json::<PostmarkError>()
+            .await
+            .map(|body| body.message)
+            .unwrap_or_else(|_| status.to_string());
+        Err(SendEmailError::Rejected(message))
+    }
+
+    async fn probe(&self) -> Result<(), SendEmailError> {
+        let url = self.base_url.join("server")
+            .expect("Failed to join url.");
+        let response = self.http_client
+            .get(url)
+            .header("Accept", "application/json")
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret()
+            )
+            .send()
+            .await
+            .map_err(classify_transport_error)?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status.is_server_error() {
+            Err(SendEmailError::Unavailable { reason: status.to_string(), retry_after: None })
+        } else {
+            Err(SendEmailError::Rejected(status.to_string()))
+        }
+    }
+}
+
+fn classify_transport_error(e: reqwest::Error) -> SendEmailError {
+    if e.is_timeout() {
+        SendEmailError::Timeout
+    } else {
+        SendEmailError::Connection(e.into())
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkError {
+    message: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<EmailHeader<'a>>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct EmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::email_client::{Email, EmailSender, PostmarkEmailSender, SendEmailError};
+    use claims::{assert_ok, assert_err};
+    use fake::faker::{
+        internet::en::SafeEmail,
+        lorem::en::{Paragraph, Sentence}
+    };
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header_exists, header, path, method, any},
+        Request
+    };
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> =
+                serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }   
+    }
+
+    fn email() -> Email {
+        Email {
+            from: SafeEmail().fake(),
+            to: SafeEmail().fake(),
+            subject: Sentence(1..2).fake(),
+            html_content: Paragraph(1..10).fake(),
+            text_content: Paragraph(1..10).fake(),
+            headers: vec![],
+        }
+    }
+
+    fn postmark(base_url: String) -> PostmarkEmailSender {
+        let base_url = reqwest::Url::parse(&base_url).unwrap();
+        PostmarkEmailSender::new(
+            base_url,
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200)
+        )
+    }
+
+    #[tokio::test]
+    async fn send_sends_the_expected_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = postmark.send(&email()).await;
+
+        // Assert 
+
+    }
+
+    #[tokio::test]
+    async fn send_forwards_the_headers() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+        let mut email = email();
+        email.headers.push((
+            "List-Unsubscribe-Post".into(),
+            "List-Unsubscribe=One-Click".into(),
+        ));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = postmark.send(&email).await;
+
+        // Assert
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body["Headers"],
+            serde_json::json!([
+                {"Name": "List-Unsubscribe-Post", "Value": "List-Unsubscribe=One-Click"}
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn send_succeeds_if_the_server_returns_200() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = postmark.send(&email()).await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_fails_if_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = postmark.send(&email()).await;
+        
+        assert_err!(&outcome);
+        assert!(outcome.unwrap_err().is_transient());
+    }
+
+    #[tokio::test]
+    async fn send_times_out_if_the_server_takes_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        let response = ResponseTemplate::new(200)
+            .set_delay(std::time::Duration::from_secs(180));
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = postmark.send(&email()).await;
+        // Assert
+        assert!(matches!(outcome, Err(SendEmailError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn a_429_is_reported_as_rate_limiting_with_its_retry_after() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "7"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = postmark.send(&email()).await;
+
+        // Assert
+        match outcome {
+            Err(SendEmailError::RateLimited { retry_after }) => {
+                assert_eq!(retry_after, Some(Duration::from_secs(7)));
+            },
+            other => panic!("Expected a rate limiting error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_4xx_is_reported_as_a_permanent_rejection() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "ErrorCode": 300,
+                "Message": "Invalid 'To' address."
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = postmark.send(&email()).await;
+
+        // Assert
+        let error = outcome.unwrap_err();
+        assert!(!error.is_transient());
+        assert!(error.to_string().contains("Invalid 'To' address."));
+    }
+
+    #[tokio::test]
+    async fn probe_checks_that_postmark_is_reachable() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(path("/server"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = postmark.probe().await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn probe_fails_if_postmark_is_unavailable() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let postmark = postmark(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = postmark.probe().await;
+
+        // Assert
+        assert!(outcome.unwrap_err().is_transient());
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+use rand::Rng;
+use super::SendEmailError;
+
+/// How `EmailClient` retries transient failures.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self { max_retries, base_delay, max_delay }
+    }
+
+    /// How long to wait before another attempt, after `attempt` attempts
+    /// failed, the last one with `error`. `None` means giving up.
+    pub fn delay_before_retry(&self, attempt: u32, error: &SendEmailError) -> Option<Duration> {
+        if !error.is_transient() || attempt > self.max_retries {
+            return None;
+        }
+        match error.retry_after() {
+            // Not worth blocking the caller for: somebody else
+            // (e.g. the delivery queue) will try again later.
+            Some(retry_after) if retry_after > self.max_delay => None,
+            Some(retry_after) => Some(retry_after),
+            None => {
+                // Exponential backoff with "full jitter", so that
+                // concurrent senders do not retry in lockstep.
+                let exponent = attempt.saturating_sub(1).min(16);
+                let cap = self.base_delay
+                    .saturating_mul(2u32.pow(exponent))
+                    .min(self.max_delay);
+                Some(cap.mul_f64(rand::thread_rng().gen_range(0.0..=1.0)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::RetryPolicy;
+    use crate::email_client::SendEmailError;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new(3, Duration::from_millis(100), Duration::from_secs(1))
+    }
+
+    #[test]
+    fn backoff_is_capped_and_grows_with_each_attempt() {
+        for attempt in 1..=3 {
+            let cap = Duration::from_millis(100 * 2u64.pow(attempt - 1));
+            for _ in 0..50 {
+                let delay = policy()
+                    .delay_before_retry(attempt, &SendEmailError::Timeout)
+                    .unwrap();
+                assert!(delay <= cap);
+            }
+        }
+    }
+
+    #[test]
+    fn no_retry_after_the_last_attempt() {
+        assert!(policy().delay_before_retry(4, &SendEmailError::Timeout).is_none());
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let error = SendEmailError::Rejected("Invalid recipient.".into());
+        assert!(policy().delay_before_retry(1, &error).is_none());
+    }
+
+    #[test]
+    fn retry_after_is_honoured() {
+        let error = SendEmailError::RateLimited { retry_after: Some(Duration::from_millis(700)) };
+        assert_eq!(policy().delay_before_retry(1, &error), Some(Duration::from_millis(700)));
+    }
+
+    #[test]
+    fn retry_after_longer_than_the_max_delay_gives_up() {
+        let error = SendEmailError::RateLimited { retry_after: Some(Duration::from_secs(60)) };
+        assert!(policy().delay_before_retry(1, &error).is_none());
+    }
+}
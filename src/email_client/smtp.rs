@@ -0,0 +1,275 @@
+use std::time::Duration;
+use anyhow::Context;
+use axum::async_trait;
+use base64::Engine;
+use secrecy::{ExposeSecret, Secret};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use super::{mime::render_message, Email, EmailSender, SendEmailError};
+
+/// Talks plain SMTP (RFC 5321) to a relay, e.g. a local Postfix or MailHog.
+/// There is no STARTTLS support: the relay has to be reachable over a
+/// trusted network.
+pub struct SmtpEmailSender {
+    host: String,
+    port: u16,
+    credentials: Option<(String, Secret<String>)>,
+    timeout: Duration,
+}
+
+impl SmtpEmailSender {
+    pub fn new(host: String, port: u16, timeout: Duration) -> Self {
+        Self {
+            host,
+            port,
+            credentials: None,
+            timeout,
+        }
+    }
+
+    /// Authenticate with `AUTH PLAIN` before sending.
+    pub fn with_credentials(mut self, username: String, password: Secret<String>) -> Self {
+        self.credentials = Some((username, password));
+        self
+    }
+
+    async fn deliver(&self, email: &Email) -> Result<(), SendEmailError> {
+        let message = render_message(email)?;
+        let stream = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .with_context(|| format!("Failed to connect to {}:{}.", self.host, self.port))
+            .map_err(SendEmailError::Connection)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        read_reply(&mut reader, "CONNECT", &[220]).await?;
+        command(&mut writer, &mut reader, "EHLO localhost", &[250]).await?;
+        if let Some((username, password)) = &self.credentials {
+            let token = base64::engine::general_purpose::STANDARD.encode(format!(
+                "\0{}\0{}",
+                username,
+                password.expose_secret()
+            ));
+            command(&mut writer, &mut reader, &format!("AUTH PLAIN {}", token), &[235]).await?;
+        }
+        command(&mut writer, &mut reader, &format!("MAIL FROM:<{}>", email.from), &[250]).await?;
+        command(&mut writer, &mut reader, &format!("RCPT TO:<{}>", email.to), &[250, 251]).await?;
+        command(&mut writer, &mut reader, "DATA", &[354]).await?;
+        // `render_message` ends with CRLF and never produces a line
+        // starting with a dot, so the message needs no dot-stuffing.
+        writer
+            .write_all(message.as_bytes())
+            .await
+            .map_err(|e| SendEmailError::Connection(e.into()))?;
+        command(&mut writer, &mut reader, ".", &[250]).await?;
+        // The email has been accepted, whatever happens next.
+        let _ = command(&mut writer, &mut reader, "QUIT", &[221]).await;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmailSender for SmtpEmailSender {
+    async fn send(&self, email: &Email) -> Result<(), SendEmailError> {
+        tokio::time::timeout(self.timeout, self.deliver(email))
+            .await
+            .map_err(|_| SendEmailError::Timeout)?
+    }
+
+    async fn probe(&self) -> Result<(), SendEmailError> {
+        let greet = async {
+            let stream = TcpStream::connect((self.host.as_str(), self.port))
+                .await
+                .map_err(|e| SendEmailError::Connection(e.into()))?;
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+            read_reply(&mut reader, "CONNECT", &[220]).await?;
+            let _ = command(&mut writer, &mut reader, "QUIT", &[221]).await;
+            Ok(())
+        };
+        tokio::time::timeout(self.timeout, greet)
+            .await
+            .map_err(|_| SendEmailError::Timeout)?
+    }
+}
+
+async fn command<W, R>(
+    writer: &mut W,
+    reader: &mut BufReader<R>,
+    line: &str,
+    expected: &[u16],
+) -> Result<(), SendEmailError>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    writer
+        .write_all(format!("{}\r\n", line).as_bytes())
+        .await
+        .map_err(|e| SendEmailError::Connection(e.into()))?;
+    let verb = line.split(' ').next().unwrap_or_default();
+    read_reply(reader, verb, expected).await
+}
+
+// Replies may span several lines: `250-first`, `250-second`, `250 last`.
+// 4xx replies are transient failures, 5xx ones are permanent.
+async fn read_reply<R>(
+    reader: &mut BufReader<R>,
+    verb: &str,
+    expected: &[u16],
+) -> Result<(), SendEmailError>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        let mut line = String::new();
+        let n_read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| SendEmailError::Connection(e.into()))?;
+        if n_read == 0 {
+            return Err(SendEmailError::Connection(anyhow::anyhow!(
+                "The SMTP server closed the connection."
+            )));
+        }
+        let code: u16 = line
+            .get(..3)
+            .and_then(|code| code.parse().ok())
+            .with_context(|| format!("Malformed SMTP reply: {:?}", line))?;
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        if expected.contains(&code) {
+            return Ok(());
+        }
+        let reason = format!("`{}` got {}", verb, line.trim_end());
+        return Err(match code {
+            400..=499 => SendEmailError::Unavailable { reason, retry_after: None },
+            500..=599 => SendEmailError::Rejected(reason),
+            _ => anyhow::anyhow!("Unexpected SMTP reply: {}", reason).into(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use claims::assert_ok;
+    use secrecy::Secret;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+    use crate::email_client::{Email, EmailSender, SendEmailError, SmtpEmailSender};
+
+    fn email() -> Email {
+        Email {
+            from: "sender@example.com".into(),
+            to: "recipient@example.com".into(),
+            subject: "Hello".into(),
+            html_content: "<p>Hello</p>".into(),
+            text_content: "Hello".into(),
+            headers: vec![],
+        }
+    }
+
+    /// A single-connection SMTP server that answers `rcpt_reply` to
+    /// `RCPT TO` and returns the commands it received.
+    async fn fake_smtp_server(rcpt_reply: &'static str) -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+            let mut commands = Vec::new();
+            let mut in_data = false;
+            writer.write_all(b"220 fake ESMTP\r\n").await.unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        writer.write_all(b"250 queued\r\n").await.unwrap();
+                    }
+                    continue;
+                }
+                commands.push(line.trim_end().to_owned());
+                let reply = match line.split([' ', ':', '\r']).next().unwrap() {
+                    "EHLO" => "250-fake\r\n250 AUTH PLAIN\r\n",
+                    "AUTH" => "235 ok\r\n",
+                    "MAIL" => "250 ok\r\n",
+                    "RCPT" => rcpt_reply,
+                    "DATA" => {
+                        in_data = true;
+                        "354 go ahead\r\n"
+                    },
+                    "QUIT" => {
+                        writer.write_all(b"221 bye\r\n").await.unwrap();
+                        break;
+                    },
+                    _ => "500 what?\r\n",
+                };
+                writer.write_all(reply.as_bytes()).await.unwrap();
+            }
+            commands
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn send_goes_through_the_smtp_conversation() {
+        // Arrange
+        let (port, server) = fake_smtp_server("250 ok\r\n").await;
+        let sender = SmtpEmailSender::new("127.0.0.1".into(), port, Duration::from_secs(5))
+            .with_credentials("user".into(), Secret::new("password".into()));
+
+        // Act
+        let outcome = sender.send(&email()).await;
+
+        // Assert
+        assert_ok!(outcome);
+        let commands = server.await.unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                "EHLO localhost".to_string(),
+                // "\0user\0password", base64-encoded
+                "AUTH PLAIN AHVzZXIAcGFzc3dvcmQ=".to_string(),
+                "MAIL FROM:<sender@example.com>".to_string(),
+                "RCPT TO:<recipient@example.com>".to_string(),
+                "DATA".to_string(),
+                "QUIT".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn send_fails_if_the_recipient_is_rejected() {
+        // Arrange
+        let (port, _server) = fake_smtp_server("550 no such user\r\n").await;
+        let sender = SmtpEmailSender::new("127.0.0.1".into(), port, Duration::from_secs(5));
+
+        // Act
+        let outcome = sender.send(&email()).await;
+
+        // Assert
+        assert!(matches!(outcome, Err(SendEmailError::Rejected(_))));
+    }
+
+    #[tokio::test]
+    async fn a_4xx_reply_is_a_transient_failure() {
+        // Arrange
+        let (port, _server) = fake_smtp_server("451 try again later\r\n").await;
+        let sender = SmtpEmailSender::new("127.0.0.1".into(), port, Duration::from_secs(5));
+
+        // Act
+        let outcome = sender.send(&email()).await;
+
+        // Assert
+        assert!(outcome.unwrap_err().is_transient());
+    }
+}
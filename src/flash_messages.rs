@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, State},
+    http::request::Parts,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, Key, SameSite, SignedCookieJar};
+use base64::Engine;
+use hyper::Request;
+
+const FLASH_COOKIE_NAME: &str = "_flash";
+
+tokio::task_local! {
+    static OUTGOING_MESSAGES: Arc<Mutex<Vec<FlashMessage>>>;
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct FlashMessage {
+    level: Level,
+    content: String,
+}
+
+impl FlashMessage {
+    pub fn new(level: Level, content: impl Into<String>) -> Self {
+        Self { level, content: content.into() }
+    }
+
+    pub fn info(content: impl Into<String>) -> Self {
+        Self::new(Level::Info, content)
+    }
+
+    pub fn warning(content: impl Into<String>) -> Self {
+        Self::new(Level::Warning, content)
+    }
+
+    pub fn error(content: impl Into<String>) -> Self {
+        Self::new(Level::Error, content)
+    }
+
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Attach the message to the response of the current request.
+    /// It will be shown (once) by the next handler that reads
+    /// `IncomingFlashMessages`.
+    pub fn send(self) {
+        let result = OUTGOING_MESSAGES.try_with(|messages| {
+            messages.lock().unwrap().push(self);
+        });
+        if result.is_err() {
+            tracing::error!("Tried to send a flash message outside of `flash_messages_middleware`.");
+        }
+    }
+}
+
+/// The flash messages that were sent along with the previous response.
+/// They are cleared as soon as the current response goes out.
+/// `templates/base.html` shows them on every page.
+#[derive(serde::Serialize, Clone, Default)]
+pub struct IncomingFlashMessages(Vec<FlashMessage>);
+
+impl IncomingFlashMessages {
+    pub fn iter(&self) -> impl Iterator<Item = &FlashMessage> {
+        self.0.iter()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IncomingFlashMessages
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<IncomingFlashMessages>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+pub async fn flash_messages_middleware<B>(
+    State(key): State<Key>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let jar = SignedCookieJar::from_headers(request.headers(), key);
+    // The signature check in `SignedCookieJar::get` discards forged cookies.
+    let incoming = jar
+        .get(FLASH_COOKIE_NAME)
+        .map(|cookie| decode_messages(cookie.value()))
+        .unwrap_or_default();
+    let had_incoming = !incoming.is_empty();
+    request.extensions_mut().insert(IncomingFlashMessages(incoming));
+
+    let outgoing = Arc::new(Mutex::new(Vec::new()));
+    let response = OUTGOING_MESSAGES
+        .scope(outgoing.clone(), next.run(request))
+        .await;
+
+    let outgoing = std::mem::take(&mut *outgoing.lock().unwrap());
+    let jar = if !outgoing.is_empty() {
+        jar.add(flash_cookie(encode_messages(&outgoing)))
+    } else if had_incoming {
+        jar.remove(Cookie::named(FLASH_COOKIE_NAME))
+    } else {
+        jar
+    };
+
+    (jar, response).into_response()
+}
+
+fn flash_cookie(value: String) -> Cookie<'static> {
+    Cookie::build(FLASH_COOKIE_NAME, value)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+// Messages are stored as base64-encoded JSON so that the content can never
+// clash with the cookie syntax.
+fn encode_messages(messages: &[FlashMessage]) -> String {
+    let json = serde_json::to_vec(messages).expect("Failed to serialize flash messages.");
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+}
+
+fn decode_messages(value: &str) -> Vec<FlashMessage> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(value)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_messages, encode_messages, FlashMessage, Level};
+
+    #[test]
+    fn messages_survive_a_cookie_roundtrip() {
+        let messages = vec![
+            FlashMessage::info("Saved; see you soon."),
+            FlashMessage::warning("Look \"here\", please"),
+        ];
+
+        let decoded = decode_messages(&encode_messages(&messages));
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].level(), Level::Info);
+        assert_eq!(decoded[0].content(), "Saved; see you soon.");
+        assert_eq!(decoded[1].level(), Level::Warning);
+    }
+
+    #[test]
+    fn garbage_cookie_values_yield_no_messages() {
+        assert!(decode_messages("definitely not base64 json").is_empty());
+    }
+}
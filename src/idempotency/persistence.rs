@@ -0,0 +1,161 @@
+use anyhow::Context;
+use axum::body::{boxed, Full};
+use axum::response::Response;
+use hyper::StatusCode;
+use sqlx::postgres::PgHasArrayType;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use super::IdempotencyKey;
+
+#[derive(Debug, sqlx::Type)]
+#[sqlx(type_name = "header_pair")]
+struct HeaderPairRecord {
+    name: String,
+    value: Vec<u8>,
+}
+
+impl PgHasArrayType for HeaderPairRecord {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_header_pair")
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum NextAction {
+    // The transaction holds the lock on the idempotency row:
+    // concurrent requests with the same key wait until it is committed.
+    StartProcessing(Transaction<'static, Postgres>),
+    ReturnSavedResponse(Response),
+}
+
+#[tracing::instrument(
+    name = "Get saved response",
+    skip(pool, idempotency_key)
+)]
+async fn get_saved_response(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<Response>, anyhow::Error> {
+    let saved_response = sqlx::query!(
+        r#"
+            SELECT
+                response_status_code as "response_status_code!",
+                response_headers as "response_headers!: Vec<HeaderPairRecord>",
+                response_body as "response_body!"
+            FROM idempotency
+            WHERE
+                user_id = $1 AND
+                idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(r) = saved_response {
+        let status_code = StatusCode::from_u16(r.response_status_code.try_into()?)?;
+        let mut response = Response::builder().status(status_code);
+        for HeaderPairRecord { name, value } in r.response_headers {
+            response = response.header(name, value);
+        }
+        let response = response
+            .body(boxed(Full::from(r.response_body)))
+            .context("Failed to rebuild the saved response.")?;
+
+        Ok(Some(response))
+    } else {
+        Ok(None)
+    }
+}
+
+#[tracing::instrument(
+    name = "Save response for idempotency key",
+    skip(transaction, idempotency_key, http_response)
+)]
+pub async fn save_response(
+    mut transaction: Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    http_response: Response,
+) -> Result<Response, anyhow::Error> {
+    let (response_head, body) = http_response.into_parts();
+    let body = hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .context("Failed to read the response body.")?;
+    let status_code = response_head.status.as_u16() as i16;
+    let headers = {
+        let mut h = Vec::with_capacity(response_head.headers.len());
+        for (name, value) in response_head.headers.iter() {
+            let name = name.as_str().to_owned();
+            let value = value.as_bytes().to_owned();
+            h.push(HeaderPairRecord { name, value });
+        }
+        h
+    };
+
+    sqlx::query_unchecked!(
+        r#"
+            UPDATE idempotency
+                SET
+                    response_status_code = $3,
+                    response_headers = $4,
+                    response_body = $5
+                WHERE
+                    user_id = $1 AND
+                    idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code,
+        headers,
+        body.as_ref()
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    let http_response = Response::from_parts(response_head, boxed(Full::from(body)));
+    Ok(http_response)
+}
+
+#[tracing::instrument(
+    name = "Try to start processing an idempotent request",
+    skip(pool, idempotency_key)
+)]
+pub async fn try_processing(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<NextAction, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    // If another request with the same key is still in flight, this insert
+    // blocks until that transaction is committed (or rolled back).
+    let n_inserted_rows = sqlx::query!(
+        r#"
+            INSERT INTO idempotency (
+                user_id,
+                idempotency_key,
+                created_at
+            )
+            VALUES ($1, $2, now())
+            ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+
+    if n_inserted_rows > 0 {
+        Ok(NextAction::StartProcessing(transaction))
+    } else {
+        let saved_response = get_saved_response(pool, idempotency_key, user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
+        Ok(NextAction::ReturnSavedResponse(saved_response))
+    }
+}
@@ -0,0 +1,326 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+use crate::{domain::SubscriberEmail, email_client::EmailClient, templates::Templates};
+use crate::confirmation_queue::try_send_confirmation_email;
+
+// A task that keeps failing is dropped after this many attempts.
+pub(crate) const MAX_RETRIES: i32 = 5;
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+pub async fn run_worker_until_stopped(
+    pool: PgPool,
+    email_client: Arc<EmailClient>,
+    templates: Templates,
+    base_url: String,
+) {
+    worker_loop(pool, email_client, templates, base_url).await
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: Arc<EmailClient>,
+    templates: Templates,
+    base_url: String,
+) {
+    loop {
+        let issue = try_execute_task(&pool, &email_client, &templates, &base_url).await;
+        let confirmation =
+            try_send_confirmation_email(&pool, &email_client, &templates, &base_url).await;
+        match (issue, confirmation) {
+            (Ok(ExecutionOutcome::EmptyQueue), Ok(ExecutionOutcome::EmptyQueue)) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            },
+            (Err(_), _) | (_, Err(_)) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            },
+            _ => {}
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        newsletter_issue_id=tracing::field::Empty,
+        subscriber_email=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Templates,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let task = dequeue_task(pool).await?;
+    let Some((mut transaction, task)) = task else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("newsletter_issue_id", display(&task.newsletter_issue_id))
+        .record("subscriber_email", display(&task.subscriber_email));
+
+    let Some(unsubscribe_token) = get_unsubscribe_token(pool, &task.subscriber_email).await? else {
+        // They unsubscribed after the issue was published.
+        tracing::info!("Skipping a subscriber that is no longer confirmed.");
+        delete_task(transaction, &task).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    };
+
+    match SubscriberEmail::parse(task.subscriber_email.clone()) {
+        Ok(email) => {
+            let issue = get_issue(pool, task.newsletter_issue_id).await?;
+            let unsubscribe_link = format!(
+                "{}/subscriptions/unsubscribe?token={}",
+                base_url,
+                unsubscribe_token
+            );
+            let mut context = tera::Context::new();
+            context.insert("unsubscribe_link", &unsubscribe_link);
+            context.insert("content", &issue.html_content);
+            let html_content = templates
+                .render("emails/newsletter_issue.html", &context)
+                .context("Failed to render the HTML body of an issue.")?;
+            context.insert("content", &issue.text_content);
+            let text_content = templates
+                .render("emails/newsletter_issue.txt", &context)
+                .context("Failed to render the text body of an issue.")?;
+            // RFC 8058 one-click unsubscribe
+            let list_unsubscribe = format!("<{}>", unsubscribe_link);
+            let headers = [
+                ("List-Unsubscribe", list_unsubscribe.as_str()),
+                ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+            ];
+            if let Err(e) = email_client
+                .send_email_with_headers(
+                    &email,
+                    &issue.title,
+                    &html_content,
+                    &text_content,
+                    &headers,
+                )
+                .await
+            {
+                // A rejected email would be rejected again.
+                if e.is_transient() && task.n_retries + 1 < MAX_RETRIES {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        n_retries = task.n_retries,
+                        "Failed to deliver issue to a confirmed subscriber. \
+                        Rescheduling the delivery.",
+                    );
+                    reschedule_task(transaction, &task).await?;
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    n_retries = task.n_retries,
+                    "Failed to deliver issue to a confirmed subscriber. \
+                    Giving up.",
+                );
+                record_delivery(&mut transaction, &task, "failed").await?;
+            } else {
+                record_delivery(&mut transaction, &task, "delivered").await?;
+            }
+        },
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Skipping a confirmed subscriber. \
+                Their stored contact details are invalid.",
+            );
+        }
+    }
+    delete_task(transaction, &task).await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i32,
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    pool: &PgPool,
+) -> Result<Option<(PgTransaction, DeliveryTask)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    // `SKIP LOCKED` lets several workers poll the queue concurrently
+    // without ever picking up the same task twice.
+    let r = sqlx::query!(
+        r#"
+            SELECT newsletter_issue_id, subscriber_email, n_retries
+                FROM issue_delivery_queue
+                WHERE execute_after <= now()
+                FOR UPDATE
+                SKIP LOCKED
+                LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    Ok(r.map(|r| {
+        (
+            transaction,
+            DeliveryTask {
+                newsletter_issue_id: r.newsletter_issue_id,
+                subscriber_email: r.subscriber_email,
+                n_retries: r.n_retries,
+            },
+        )
+    }))
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    mut transaction: PgTransaction,
+    task: &DeliveryTask,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+            DELETE FROM issue_delivery_queue
+                WHERE
+                    newsletter_issue_id = $1 AND
+                    subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Kept so that subscribers can see what we sent them.
+#[tracing::instrument(skip(transaction, task))]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    task: &DeliveryTask,
+    outcome: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO issue_deliveries
+                (newsletter_issue_id, subscriber_email, outcome, attempted_at)
+                VALUES ($1, $2, $3, now())
+                ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+                    SET outcome = EXCLUDED.outcome, attempted_at = EXCLUDED.attempted_at
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        outcome,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    mut transaction: PgTransaction,
+    task: &DeliveryTask,
+) -> Result<(), anyhow::Error> {
+    let backoff = retry_backoff(task.n_retries);
+    sqlx::query!(
+        r#"
+            UPDATE issue_delivery_queue
+                SET
+                    n_retries = n_retries + 1,
+                    execute_after = now() + $3 * interval '1 second'
+                WHERE
+                    newsletter_issue_id = $1 AND
+                    subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        backoff.as_secs() as f64,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+pub(crate) fn retry_backoff(n_retries: i32) -> Duration {
+    // 30s, 1m, 2m, 4m, ...
+    Duration::from_secs(30 * 2u64.pow(n_retries.clamp(0, 10) as u32))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_unsubscribe_token(
+    pool: &PgPool,
+    subscriber_email: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+            SELECT unsubscribe_token FROM subscriptions
+                WHERE email = $1 AND status = 'confirmed'
+        "#,
+        subscriber_email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber's unsubscribe token.")?;
+
+    Ok(row.map(|r| r.unsubscribe_token))
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<NewsletterIssue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+            SELECT title, text_content, html_content
+                FROM newsletter_issues
+                WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve a newsletter issue.")?;
+
+    Ok(issue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::retry_backoff;
+    use std::time::Duration;
+
+    #[test]
+    fn retry_backoff_doubles_on_each_attempt() {
+        assert_eq!(retry_backoff(0), Duration::from_secs(30));
+        assert_eq!(retry_backoff(1), Duration::from_secs(60));
+        assert_eq!(retry_backoff(3), Duration::from_secs(240));
+    }
+}
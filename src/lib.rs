@@ -5,4 +5,17 @@ pub mod startup;
 pub mod telemetry;
 pub mod domain;
 pub mod email_client;
-pub mod authentication;
\ No newline at end of file
+pub mod authentication;
+pub mod issue_delivery_worker;
+pub mod idempotency;
+pub mod session;
+pub mod flash_messages;
+pub mod metrics;
+pub mod audit;
+pub mod rate_limit;
+pub mod csrf;
+pub mod templates;
+pub mod cli;
+pub mod api_docs;
+pub mod subscribers;
+pub mod confirmation_queue;
@@ -1,19 +1,56 @@
+use clap::Parser;
+use myweb::cli::{self, Cli, Command};
+use myweb::configuration::{get_configuration, Settings};
 use myweb::startup::build;
-use myweb::telemetry::{get_subscriber, init_subscriber};
+use myweb::telemetry::{get_subscriber, init_subscriber, tracer, tracer_provider};
 use myweb::utils::shutdown_signal;
-use myweb::configuration::get_configuration;
 
 #[tokio::main]
-async fn main() {
-    let subscriber = get_subscriber("my-web".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-    
+async fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::parse();
     let configuration = get_configuration().expect("Failed to read configuration");
 
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            serve(configuration).await;
+            Ok(())
+        }
+        command => {
+            // Keep stdout for the output of the command itself. The notices
+            // Postgres sends while migrating are not worth a warning.
+            let subscriber = get_subscriber(
+                "my-web".into(),
+                "warn,sqlx::postgres::notice=error".into(),
+                std::io::stderr,
+                None,
+            );
+            init_subscriber(subscriber);
+            cli::run(command, configuration).await
+        }
+    }
+}
+
+async fn serve(configuration: Settings) {
+    let tracer_provider = tracer_provider(
+        configuration.telemetry.otlp_endpoint.as_deref(),
+        "my-web".into(),
+    )
+    .expect("Failed to build the OTLP exporter.");
+    let subscriber = get_subscriber(
+        "my-web".into(),
+        "info".into(),
+        std::io::stdout,
+        Some(tracer(&tracer_provider)),
+    );
+    init_subscriber(subscriber);
+
     let server = build(configuration).await;
 
     server
         .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
+
+    // Export whatever is still buffered before exiting.
+    tracer_provider.force_flush();
 }
@@ -0,0 +1,114 @@
+use std::time::Instant;
+use axum::extract::MatchedPath;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use hyper::{header::CONTENT_TYPE, Request, StatusCode};
+use lazy_static::lazy_static;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::new();
+
+    static ref HTTP_REQUESTS_TOTAL: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("http_requests_total", "Number of HTTP requests served."),
+        &["method", "route", "status"],
+    ));
+    static ref HTTP_REQUEST_DURATION_SECONDS: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("http_request_duration_seconds", "HTTP request latencies in seconds."),
+        &["method", "route", "status"],
+    ));
+
+    pub static ref SUBSCRIPTIONS_CREATED: IntCounter = register(IntCounter::new(
+        "subscriptions_created_total",
+        "Number of new subscribers.",
+    ));
+    pub static ref SUBSCRIPTIONS_CONFIRMED: IntCounter = register(IntCounter::new(
+        "subscriptions_confirmed_total",
+        "Number of subscribers who confirmed their email address.",
+    ));
+    pub static ref NEWSLETTERS_PUBLISHED: IntCounter = register(IntCounter::new(
+        "newsletters_published_total",
+        "Number of newsletter issues published.",
+    ));
+    pub static ref EMAILS_SENT: IntCounter = register(IntCounter::new(
+        "emails_sent_total",
+        "Number of emails handed over to the email backend.",
+    ));
+    pub static ref EMAILS_FAILED: IntCounter = register(IntCounter::new(
+        "emails_failed_total",
+        "Number of emails that could not be sent, retries included.",
+    ));
+    pub static ref LOGIN_FAILURES: IntCounter = register(IntCounter::new(
+        "login_failures_total",
+        "Number of failed login attempts.",
+    ));
+}
+
+/// Register every metric up front, so that they are all exported
+/// from the start instead of appearing on first use.
+pub fn init_metrics() {
+    lazy_static::initialize(&HTTP_REQUESTS_TOTAL);
+    lazy_static::initialize(&HTTP_REQUEST_DURATION_SECONDS);
+    lazy_static::initialize(&SUBSCRIPTIONS_CREATED);
+    lazy_static::initialize(&SUBSCRIPTIONS_CONFIRMED);
+    lazy_static::initialize(&NEWSLETTERS_PUBLISHED);
+    lazy_static::initialize(&EMAILS_SENT);
+    lazy_static::initialize(&EMAILS_FAILED);
+    lazy_static::initialize(&LOGIN_FAILURES);
+}
+
+fn register<M>(metric: prometheus::Result<M>) -> M
+where
+    M: prometheus::core::Collector + Clone + 'static,
+{
+    let metric = metric.expect("Invalid metric definition.");
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("Failed to register a metric.");
+    metric
+}
+
+/// Record the count and latency of every request, labelled by route
+/// template (e.g. `/blog/:issue_id`) rather than by raw path, so that
+/// the number of series stays bounded.
+pub async fn track_metrics<B>(request: Request<B>, next: Next<B>) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".into());
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    HTTP_REQUESTS_TOTAL.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION_SECONDS
+        .with_label_values(&labels)
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Every metric, in Prometheus' text exposition format.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses((status = 200, description = "The metrics.", body = String, content_type = "text/plain")),
+)]
+pub async fn metrics() -> Response {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&REGISTRY.gather(), &mut buffer) {
+        tracing::error!("\nFailed to encode metrics: {:?}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+            .into_response();
+    }
+
+    ([(CONTENT_TYPE, encoder.format_type().to_owned())], buffer).into_response()
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use axum::extract::ConnectInfo;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use hyper::{header::RETRY_AFTER, Body, Request, StatusCode};
+use tower::{Layer, Service};
+use crate::utils::{read_body, MAX_BODY_SIZE};
+
+// Past this many keys, the least recently used tenth is forgotten at once,
+// so that the scan is paid once every thousand new keys, not on each request.
+const MAX_TRACKED_KEYS: usize = 10_000;
+const EVICTED_KEYS: usize = MAX_TRACKED_KEYS / 10;
+
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// A token bucket per key: `burst` requests at once,
+/// then one more every `period`.
+pub struct RateLimiter {
+    burst: f64,
+    period: Duration,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, period: Duration) -> Self {
+        Self {
+            burst: burst as f64,
+            period,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `key`, or tell how long until one is available.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_KEYS && !buckets.contains_key(key) {
+            evict_least_recently_used(&mut buckets);
+        }
+        let bucket = buckets
+            .entry(key.to_owned())
+            .or_insert(TokenBucket { tokens: self.burst, updated_at: now });
+        let tokens = self.refill(bucket, now);
+        bucket.tokens = tokens;
+        bucket.updated_at = now;
+
+        if tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self.period.mul_f64(1.0 - tokens))
+        }
+    }
+
+    fn refill(&self, bucket: &TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        (bucket.tokens + elapsed.as_secs_f64() / self.period.as_secs_f64()).min(self.burst)
+    }
+}
+
+fn evict_least_recently_used(buckets: &mut HashMap<String, TokenBucket>) {
+    let mut last_uses: Vec<(Instant, &String)> = buckets
+        .iter()
+        .map(|(key, bucket)| (bucket.updated_at, key))
+        .collect();
+    last_uses.select_nth_unstable(EVICTED_KEYS - 1);
+    let evicted: Vec<String> = last_uses[..EVICTED_KEYS]
+        .iter()
+        .map(|&(_, key)| key.clone())
+        .collect();
+    for key in evicted {
+        buckets.remove(&key);
+    }
+}
+
+/// Answer 429 to clients that call a route too often, counting requests
+/// per client IP address and per `email` field of the submitted form
+/// (or JSON object).
+#[derive(Clone, Default)]
+pub struct RateLimitLayer {
+    per_ip: Option<Arc<RateLimiter>>,
+    per_email: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimitLayer {
+    pub fn new(per_ip: Option<RateLimiter>, per_email: Option<RateLimiter>) -> Self {
+        Self {
+            per_ip: per_ip.map(Arc::new),
+            per_email: per_email.map(Arc::new),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit { inner, limits: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limits: RateLimitLayer,
+}
+
+#[derive(serde::Deserialize)]
+struct TargetEmail {
+    email: Option<String>,
+}
+
+impl<S> Service<Request<Body>> for RateLimit<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // The clone might not be ready, keep the service that was polled.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limits = self.limits.clone();
+
+        Box::pin(async move {
+            if let Some(limiter) = &limits.per_ip {
+                let client_address = request
+                    .extensions()
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(address)| address.ip());
+                if let Some(ip) = client_address {
+                    if let Err(retry_after) = limiter.check(&ip.to_string()) {
+                        tracing::warn!(%ip, "\nRate limited by client IP address.");
+                        return Ok(too_many_requests(retry_after));
+                    }
+                }
+            }
+
+            let request = match &limits.per_email {
+                Some(limiter) => {
+                    // The body has to be read to find the target address,
+                    // the handler gets it back untouched.
+                    let (parts, body) = request.into_parts();
+                    let bytes = match read_body(body, MAX_BODY_SIZE).await {
+                        Ok(bytes) => bytes,
+                        Err(response) => return Ok(response),
+                    };
+                    // Any body parses as a form, JSON ones just lack an `email` field.
+                    let email = serde_urlencoded::from_bytes::<TargetEmail>(&bytes)
+                        .ok()
+                        .and_then(|form| form.email)
+                        .or_else(|| {
+                            serde_json::from_slice::<TargetEmail>(&bytes)
+                                .ok()
+                                .and_then(|json| json.email)
+                        });
+                    if let Some(email) = email {
+                        let key = email.trim().to_lowercase();
+                        if let Err(retry_after) = limiter.check(&key) {
+                            tracing::warn!(email = %key, "\nRate limited by target email.");
+                            return Ok(too_many_requests(retry_after));
+                        }
+                    }
+                    Request::from_parts(parts, Body::from(bytes))
+                },
+                None => request,
+            };
+
+            inner.call(request).await
+        })
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    // Round up, so that clients do not retry a second too early.
+    let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let body = Json(serde_json::json!({
+        "error": "Too many requests, try again later."
+    }));
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, retry_after.to_string())],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use super::{RateLimiter, EVICTED_KEYS, MAX_TRACKED_KEYS};
+
+    #[test]
+    fn a_burst_is_allowed_then_requests_are_blocked() {
+        let limiter = RateLimiter::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at("key", now).is_ok());
+        }
+        assert_eq!(limiter.check_at("key", now), Err(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn tokens_are_refilled_over_time() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(limiter.check_at("key", now).is_ok());
+
+        let retry_after = limiter.check_at("key", now + Duration::from_secs(45)).unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(15));
+        assert!(limiter.check_at("key", now + Duration::from_secs(60)).is_ok());
+    }
+
+    #[test]
+    fn the_bucket_never_holds_more_than_the_burst() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(1));
+        let now = Instant::now();
+        assert!(limiter.check_at("key", now).is_ok());
+        let later = now + Duration::from_secs(3600);
+        assert!(limiter.check_at("key", later).is_ok());
+        assert!(limiter.check_at("key", later).is_ok());
+        assert!(limiter.check_at("key", later).is_err());
+    }
+
+    #[test]
+    fn the_least_recently_used_keys_are_forgotten_past_the_cap() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(3600));
+        let now = Instant::now();
+        assert!(limiter.check_at("oldest", now).is_ok());
+        for i in 1..MAX_TRACKED_KEYS {
+            assert!(limiter.check_at(&i.to_string(), now + Duration::from_secs(1)).is_ok());
+        }
+
+        let later = now + Duration::from_secs(2);
+        assert!(limiter.check_at("newest", later).is_ok());
+
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_TRACKED_KEYS - EVICTED_KEYS + 1);
+        // Forgotten, so it starts over with a full bucket...
+        assert!(limiter.check_at("oldest", later).is_ok());
+        // ...while recent keys are still held to their limit.
+        assert!(limiter.check_at("newest", later).is_err());
+    }
+
+    #[test]
+    fn keys_have_their_own_bucket() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(limiter.check_at("a", now).is_ok());
+        assert!(limiter.check_at("a", now).is_err());
+        assert!(limiter.check_at("b", now).is_ok());
+    }
+}
@@ -4,9 +4,13 @@ mod reviews;
 mod subscriptions;
 mod health_check;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
 mod newsletters;
 mod login;
+mod admin;
+mod privacy;
 
+pub use admin::*;
 pub use login::*;
 pub use newsletters::*;
 pub use health_check::*;
@@ -14,4 +18,6 @@ pub use blog::*;
 pub use home::*;
 pub use reviews::*;
 pub use subscriptions::*;
-pub use subscriptions_confirm::*;
\ No newline at end of file
+pub use subscriptions_confirm::*;
+pub use subscriptions_unsubscribe::*;
+pub use privacy::*;
\ No newline at end of file
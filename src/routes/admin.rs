@@ -0,0 +1,15 @@
+mod dashboard;
+mod lockouts;
+mod logout;
+mod password;
+mod reviews;
+mod subscribers;
+mod users;
+
+pub use dashboard::admin_dashboard;
+pub use lockouts::*;
+pub use logout::log_out;
+pub use password::*;
+pub use reviews::*;
+pub use subscribers::*;
+pub use users::*;
@@ -0,0 +1,47 @@
+use anyhow::Context;
+use axum::response::Html;
+use axum::Extension;
+use hyper::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::authentication::{CurrentUser, ManageSubscribers, ManageUsers, ModerateReviews, Permission};
+use crate::csrf::CsrfToken;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::templates::Templates;
+use crate::utils::e500;
+
+pub async fn admin_dashboard(
+    Extension(templates): Extension<Templates>,
+    user: CurrentUser,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let mut context = tera::Context::new();
+    context.insert("username", &user.username);
+    context.insert("role", user.role.as_str());
+    context.insert("can_moderate_reviews", &ModerateReviews::is_granted_to(user.role));
+    context.insert("can_manage_subscribers", &ManageSubscribers::is_granted_to(user.role));
+    context.insert("can_manage_users", &ManageUsers::is_granted_to(user.role));
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    templates.render("admin/dashboard.html", &context).map(Html).map_err(e500)
+}
+
+#[tracing::instrument(name = "Get username", skip(pool))]
+pub async fn get_username(
+    user_id: Uuid,
+    pool: &PgPool
+) -> Result<String, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+            SELECT username FROM users
+                WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to perform a query to retrieve a username.")?;
+
+    Ok(row.username)
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::response::Html;
+use axum::Extension;
+use hyper::StatusCode;
+use sqlx::PgPool;
+use crate::authentication::{get_active_lockouts, Authorized, ManageUsers};
+use crate::csrf::CsrfToken;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::templates::Templates;
+use crate::utils::e500;
+
+/// Accounts locked after too many failed logins.
+#[tracing::instrument(name = "Show the locked accounts", skip(pool, templates, flash_messages))]
+pub async fn lockouts(
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    _user: Authorized<ManageUsers>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let lockouts = get_active_lockouts(&pool).await.map_err(e500)?;
+    let lockouts: Vec<_> = lockouts
+        .iter()
+        .map(|lockout| serde_json::json!({
+            "username": lockout.username,
+            "locked_at": lockout.locked_at.format("%Y-%m-%d %H:%M").to_string(),
+            "locked_until": lockout.locked_until.format("%Y-%m-%d %H:%M").to_string(),
+        }))
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("lockouts", &lockouts);
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    templates.render("admin/lockouts.html", &context).map(Html).map_err(e500)
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Form;
+use hyper::{header::LOCATION, StatusCode};
+use sqlx::PgPool;
+use crate::authentication::{unlock_account, Authorized, ManageUsers};
+use crate::flash_messages::FlashMessage;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    username: String,
+}
+
+#[tracing::instrument(
+    name = "Unlock an account",
+    skip(form, pool, user),
+    fields(user_id = %user.user_id, username = %form.username)
+)]
+pub async fn unlock(
+    State(pool): State<Arc<PgPool>>,
+    user: Authorized<ManageUsers>,
+    Form(form): Form<FormData>,
+) -> Response {
+    let message = match unlock_account(&pool, &form.username, user.user_id).await {
+        Ok(true) => FlashMessage::info(format!("{} has been unlocked.", form.username)),
+        Ok(false) => FlashMessage::warning(format!("{} is not locked.", form.username)),
+        Err(e) => {
+            tracing::error!("\nServer error: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                .into_response();
+        }
+    };
+    message.send();
+    (StatusCode::SEE_OTHER, [(LOCATION, "/admin/lockouts")]).into_response()
+}
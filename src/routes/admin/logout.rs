@@ -0,0 +1,15 @@
+use axum::response::IntoResponse;
+use hyper::{header::LOCATION, StatusCode};
+use crate::authentication::UserId;
+use crate::session::TypedSession;
+use crate::flash_messages::FlashMessage;
+
+#[tracing::instrument(name = "Log out", skip(session))]
+pub async fn log_out(
+    user_id: UserId,
+    session: TypedSession,
+) -> impl IntoResponse {
+    session.log_out();
+    FlashMessage::info("You have successfully logged out.").send();
+    (StatusCode::SEE_OTHER, [(LOCATION, "/login")])
+}
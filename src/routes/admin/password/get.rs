@@ -0,0 +1,19 @@
+use axum::{response::Html, Extension};
+use hyper::StatusCode;
+use crate::authentication::UserId;
+use crate::csrf::CsrfToken;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::templates::Templates;
+use crate::utils::e500;
+
+pub async fn change_password_form(
+    _user_id: UserId,
+    Extension(templates): Extension<Templates>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let mut context = tera::Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    templates.render("admin/password.html", &context).map(Html).map_err(e500)
+}
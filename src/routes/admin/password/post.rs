@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Form;
+use hyper::{header::LOCATION, StatusCode};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use crate::authentication::{self, validate_credentials, AuthError, Credentials, UserId};
+use crate::domain::NewPassword;
+use crate::flash_messages::FlashMessage;
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::e500;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    current_password: Secret<String>,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Change password",
+    skip(form, pool),
+    fields(user_id = %user_id)
+)]
+pub async fn change_password(
+    State(pool): State<Arc<PgPool>>,
+    user_id: UserId,
+    Form(form): Form<FormData>,
+) -> Response {
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        return password_redirect(FlashMessage::error(
+            "You entered two different new passwords - the field values must match.",
+        ));
+    }
+    if form.new_password.expose_secret() == form.current_password.expose_secret() {
+        return password_redirect(FlashMessage::error(
+            "The new password must be different from the current one.",
+        ));
+    }
+    let new_password = match NewPassword::parse(form.new_password) {
+        Ok(new_password) => new_password,
+        Err(e) => return password_redirect(FlashMessage::error(e)),
+    };
+
+    let username = match get_username(*user_id, &pool).await {
+        Ok(username) => username,
+        Err(e) => return e500(e).into_response(),
+    };
+    let credentials = Credentials {
+        username,
+        password: form.current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => password_redirect(
+                FlashMessage::error("The current password is incorrect."),
+            ),
+            AuthError::UnexpectedError(_) => e500(e).into_response(),
+        };
+    }
+
+    if let Err(e) = authentication::change_password(*user_id, new_password, &pool).await {
+        return e500(e).into_response();
+    }
+    password_redirect(FlashMessage::info("Your password has been changed."))
+}
+
+fn password_redirect(message: FlashMessage) -> Response {
+    message.send();
+    (StatusCode::SEE_OTHER, [(LOCATION, "/admin/password")]).into_response()
+}
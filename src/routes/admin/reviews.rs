@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::moderation_queue;
+pub use post::moderate_review;
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::State;
+use axum::response::Html;
+use axum::Extension;
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::authentication::{Authorized, ModerateReviews};
+use crate::csrf::CsrfToken;
+use crate::domain::ReviewRating;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::templates::Templates;
+use crate::utils::e500;
+
+/// Reviews waiting for an admin's approval, oldest first.
+#[tracing::instrument(name = "Show the review moderation queue", skip(pool, templates, flash_messages))]
+pub async fn moderation_queue(
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    _user: Authorized<ModerateReviews>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let reviews = get_pending_reviews(&pool).await.map_err(e500)?;
+    let reviews: Vec<_> = reviews
+        .iter()
+        .map(|review| serde_json::json!({
+            "review_id": review.review_id,
+            "name": review.name,
+            "email": review.email,
+            "rating": review.rating,
+            "content": review.content,
+            "submitted_at": review.submitted_at.format("%Y-%m-%d %H:%M").to_string(),
+        }))
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("reviews", &reviews);
+    context.insert("max_rating", &ReviewRating::MAX);
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    templates.render("admin/reviews.html", &context).map(Html).map_err(e500)
+}
+
+struct PendingReview {
+    review_id: Uuid,
+    name: String,
+    email: String,
+    rating: i16,
+    content: String,
+    submitted_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_pending_reviews(pool: &PgPool) -> Result<Vec<PendingReview>, anyhow::Error> {
+    let reviews = sqlx::query_as!(
+        PendingReview,
+        r#"
+            SELECT reviews.review_id, subscriptions.name, subscriptions.email,
+                    reviews.rating, reviews.content, reviews.submitted_at
+                FROM reviews
+                JOIN subscriptions ON subscriptions.id = reviews.subscriber_id
+                WHERE reviews.status = 'pending'
+                ORDER BY reviews.submitted_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the pending reviews.")?;
+
+    Ok(reviews)
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use hyper::{header::LOCATION, StatusCode};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::authentication::{Authorized, ModerateReviews};
+use crate::flash_messages::FlashMessage;
+
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Approve,
+    Reject,
+}
+
+impl Decision {
+    fn status(&self) -> &'static str {
+        match self {
+            Decision::Approve => "approved",
+            Decision::Reject => "rejected",
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Moderate a review",
+    skip(pool, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn moderate_review(
+    State(pool): State<Arc<PgPool>>,
+    user: Authorized<ModerateReviews>,
+    Path((review_id, decision)): Path<(Uuid, Decision)>,
+) -> Response {
+    let moderated = match set_review_status(&pool, review_id, decision, user.user_id).await {
+        Ok(moderated) => moderated,
+        Err(e) => {
+            tracing::error!("\nServer error: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                .into_response();
+        }
+    };
+
+    let message = if !moderated {
+        FlashMessage::warning("This review has already been moderated.")
+    } else {
+        match decision {
+            Decision::Approve => FlashMessage::info("The review has been approved."),
+            Decision::Reject => FlashMessage::info("The review has been rejected."),
+        }
+    };
+    message.send();
+    (StatusCode::SEE_OTHER, [(LOCATION, "/admin/reviews")]).into_response()
+}
+
+#[tracing::instrument(skip(pool))]
+async fn set_review_status(
+    pool: &PgPool,
+    review_id: Uuid,
+    decision: Decision,
+    moderator: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let n_updated = sqlx::query!(
+        r#"
+            UPDATE reviews
+                SET status = $2, moderated_at = now(), moderated_by = $3
+                WHERE review_id = $1 AND status = 'pending'
+        "#,
+        review_id,
+        decision.status(),
+        moderator,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the status of a review.")?
+    .rows_affected();
+
+    Ok(n_updated > 0)
+}
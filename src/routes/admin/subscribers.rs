@@ -0,0 +1,10 @@
+mod export;
+mod get;
+mod import;
+mod post;
+mod query;
+
+pub use export::export_subscribers;
+pub use get::{subscriber_details, subscribers};
+pub use import::{import_subscribers, MAX_IMPORT_SIZE};
+pub use post::{confirm_subscriber_manually, delete_subscriber, unsubscribe_subscriber_manually};
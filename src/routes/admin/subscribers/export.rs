@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use hyper::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use sqlx::PgPool;
+use crate::audit::{record_audit_event, AuditEvent};
+use crate::authentication::{Authorized, ManageSubscribers};
+use crate::subscribers::get_subscribers;
+use super::query::{SubscriberQuery, SubscribersError};
+
+/// Every subscriber matching the filters of the query string, as CSV.
+#[tracing::instrument(name = "Export the subscribers", skip(pool, user), fields(user_id = %user.user_id))]
+pub async fn export_subscribers(
+    State(pool): State<Arc<PgPool>>,
+    user: Authorized<ManageSubscribers>,
+    Query(query): Query<SubscriberQuery>,
+) -> Result<Response, SubscribersError> {
+    let filter = query.filter()?;
+    let subscribers = get_subscribers(&pool, &filter, None, 0).await?;
+
+    let mut csv = String::from("id,email,name,status,subscribed_at\r\n");
+    for subscriber in &subscribers {
+        let record = [
+            subscriber.id.to_string(),
+            csv_field(&subscriber.email),
+            csv_field(&subscriber.name),
+            csv_field(&subscriber.status),
+            subscriber.subscribed_at.to_rfc3339(),
+        ];
+        csv.push_str(&record.join(","));
+        csv.push_str("\r\n");
+    }
+
+    // The file holds personal data: keep track of who took it.
+    record_audit_event(&*pool, AuditEvent {
+        actor_user_id: Some(user.user_id),
+        details: Some(format!("{} subscribers, filters: {}", subscribers.len(), query.to_query_string())),
+        ..AuditEvent::new("subscribers_exported")
+    })
+    .await
+    .context("Failed to record the export.")?;
+
+    let disposition = format!(
+        "attachment; filename=\"subscribers-{}.csv\"",
+        Utc::now().format("%Y-%m-%d")
+    );
+    Ok((
+        [(CONTENT_TYPE, "text/csv; charset=utf-8".to_owned()), (CONTENT_DISPOSITION, disposition)],
+        csv,
+    )
+        .into_response())
+}
+
+/// A field as written by RFC 4180. Values a spreadsheet would evaluate as
+/// a formula are prefixed with a quote, so that opening the file runs nothing.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_owned()
+    };
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_field;
+
+    #[test]
+    fn plain_fields_are_left_alone() {
+        assert_eq!(csv_field("ursula_le_guin@gmail.com"), "ursula_le_guin@gmail.com");
+    }
+
+    #[test]
+    fn fields_with_separators_are_quoted() {
+        assert_eq!(csv_field("le guin, ursula"), "\"le guin, ursula\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn quotes_are_doubled() {
+        assert_eq!(csv_field("ursula \"le\" guin"), "\"ursula \"\"le\"\" guin\"");
+    }
+
+    #[test]
+    fn formulas_are_neutralised() {
+        assert_eq!(csv_field("=HYPERLINK(1)"), "'=HYPERLINK(1)");
+        assert_eq!(csv_field("@sum"), "'@sum");
+        assert_eq!(csv_field("-1,2"), "\"'-1,2\"");
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::{Path, Query, State};
+use axum::response::Html;
+use axum::Extension;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::authentication::{Authorized, DeleteSubscribers, ManageSubscribers, Permission};
+use crate::csrf::CsrfToken;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::subscribers::{get_confirmation_tokens, get_subscriber, get_subscribers, STATUSES};
+use crate::templates::Templates;
+use super::query::{SubscriberQuery, SubscribersError};
+
+const SUBSCRIBERS_PER_PAGE: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct Pagination {
+    /// Starts at 1.
+    page: Option<u32>,
+}
+
+/// The subscribers matching the filters of the query string, newest first.
+#[tracing::instrument(
+    name = "Show the subscribers",
+    skip(pool, templates, user, pagination, flash_messages),
+    fields(user_id = %user.user_id)
+)]
+pub async fn subscribers(
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    user: Authorized<ManageSubscribers>,
+    Query(query): Query<SubscriberQuery>,
+    Query(pagination): Query<Pagination>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, SubscribersError> {
+    let filter = query.filter()?;
+    let page_number = pagination.page.unwrap_or(1);
+    if page_number == 0 {
+        return Err(SubscribersError::NotFound);
+    }
+    // One extra row tells us whether there is a next page.
+    let mut subscribers = get_subscribers(
+        &pool,
+        &filter,
+        Some(SUBSCRIBERS_PER_PAGE + 1),
+        (page_number as i64 - 1) * SUBSCRIBERS_PER_PAGE,
+    )
+    .await?;
+    let has_next = subscribers.len() as i64 > SUBSCRIBERS_PER_PAGE;
+    subscribers.truncate(SUBSCRIBERS_PER_PAGE as usize);
+    if subscribers.is_empty() && page_number > 1 {
+        return Err(SubscribersError::NotFound);
+    }
+
+    let subscribers: Vec<_> = subscribers
+        .iter()
+        .map(|subscriber| serde_json::json!({
+            "id": subscriber.id,
+            "email": &subscriber.email,
+            "name": &subscriber.name,
+            "status": subscriber.status,
+            "subscribed_at": subscriber.subscribed_at.format("%Y-%m-%d %H:%M").to_string(),
+        }))
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("subscribers", &subscribers);
+    context.insert("statuses", &STATUSES);
+    // Fills the filter form back in.
+    context.insert("query", &serde_json::json!({
+        "status": query.status,
+        "subscribed_from": query.subscribed_from,
+        "subscribed_until": query.subscribed_until,
+        "search": query.search,
+    }));
+    context.insert("query_string", &query.to_query_string());
+    if page_number > 1 {
+        context.insert("previous_page", &(page_number - 1));
+    }
+    if has_next {
+        context.insert("next_page", &(page_number + 1));
+    }
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    let html = templates
+        .render("admin/subscribers.html", &context)
+        .context("Failed to render the subscribers.")?;
+
+    Ok(Html(html))
+}
+
+/// A subscriber, the confirmation links they were sent, and what can be done to them.
+#[tracing::instrument(
+    name = "Show a subscriber",
+    skip(pool, templates, user, flash_messages),
+    fields(user_id = %user.user_id)
+)]
+pub async fn subscriber_details(
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    user: Authorized<ManageSubscribers>,
+    Path(subscriber_id): Path<Uuid>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, SubscribersError> {
+    let subscriber = get_subscriber(&pool, subscriber_id)
+        .await?
+        .ok_or(SubscribersError::NotFound)?;
+    let tokens = get_confirmation_tokens(&pool, subscriber_id).await?;
+    let tokens: Vec<_> = tokens
+        .iter()
+        .map(|token| serde_json::json!({
+            "created_at": token.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            "expires_at": token.expires_at.format("%Y-%m-%d %H:%M").to_string(),
+            "used_at": token.used_at.map(|d| d.format("%Y-%m-%d %H:%M").to_string()),
+        }))
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("subscriber", &serde_json::json!({
+        "id": subscriber.id,
+        "email": &subscriber.email,
+        "name": &subscriber.name,
+        "status": subscriber.status,
+        "subscribed_at": subscriber.subscribed_at.format("%Y-%m-%d %H:%M").to_string(),
+    }));
+    context.insert("tokens", &tokens);
+    context.insert("can_delete", &DeleteSubscribers::is_granted_to(user.role));
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    let html = templates
+        .render("admin/subscriber.html", &context)
+        .context("Failed to render a subscriber.")?;
+
+    Ok(Html(html))
+}
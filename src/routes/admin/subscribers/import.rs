@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::{Multipart, State};
+use axum::extract::multipart::{Field, MultipartError};
+use axum::response::Html;
+use axum::Extension;
+use sqlx::PgPool;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use crate::authentication::{Authorized, ManageSubscribers};
+use crate::csrf::{UploadCsrfCheck, CSRF_FIELD_NAME};
+use crate::subscribers::{ImportError, ImportStatus, SubscriberImport};
+use crate::telemetry::spawn_blocking_with_tracing;
+use crate::templates::Templates;
+use super::query::SubscribersError;
+
+/// Larger files are better imported from the command line.
+pub const MAX_IMPORT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Import the subscribers of the CSV sent in the `file` field, once it is
+/// fully uploaded. The `status` field (`pending` by default) must come before it,
+/// and the `csrf_token` field before both.
+#[tracing::instrument(
+    name = "Import subscribers",
+    skip(pool, templates, user, csrf_check, multipart),
+    fields(user_id = %user.user_id)
+)]
+pub async fn import_subscribers(
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    user: Authorized<ManageSubscribers>,
+    csrf_check: UploadCsrfCheck,
+    mut multipart: Multipart,
+) -> Result<Html<String>, SubscribersError> {
+    let mut status = ImportStatus::Pending;
+    let mut has_valid_token = csrf_check.accepts(None);
+    while let Some(mut field) = multipart.next_field().await.map_err(invalid_upload)? {
+        let name = field.name().map(ToOwned::to_owned);
+        match name.as_deref() {
+            Some(CSRF_FIELD_NAME) => {
+                let value = field.text().await.map_err(invalid_upload)?;
+                has_valid_token = csrf_check.accepts(Some(&value));
+            },
+            _ if !has_valid_token => {
+                tracing::warn!("\nRejected an upload without a valid CSRF token.");
+                return Err(SubscribersError::InvalidCsrfToken);
+            },
+            Some("status") => {
+                let value = field.text().await.map_err(invalid_upload)?;
+                status = ImportStatus::parse(&value).map_err(SubscribersError::InvalidUpload)?;
+            },
+            Some("file") => {
+                // The import transaction must not stay open for as long as
+                // a slow client takes to upload the file.
+                let mut upload = buffer_upload(&mut field).await?;
+                let mut import = SubscriberImport::begin(&pool, status).await?;
+                let mut buffer = vec![0; 64 * 1024];
+                loop {
+                    let n_read = upload
+                        .read(&mut buffer)
+                        .await
+                        .context("Failed to read the buffered upload.")?;
+                    if n_read == 0 {
+                        break;
+                    }
+                    import.feed(&buffer[..n_read]).await?;
+                }
+                let report = import.finish(Some(user.user_id)).await?;
+
+                let mut context = tera::Context::new();
+                context.insert("report", &report);
+                context.insert("status", status.as_str());
+                let html = templates
+                    .render("admin/import_report.html", &context)
+                    .context("Failed to render the import report.")?;
+                return Ok(Html(html));
+            },
+            _ => {},
+        }
+    }
+
+    match has_valid_token {
+        true => Err(SubscribersError::InvalidUpload("No file was uploaded.".into())),
+        false => Err(SubscribersError::InvalidCsrfToken),
+    }
+}
+
+/// Copy the uploaded file to an anonymous temporary file, rewound.
+async fn buffer_upload(field: &mut Field<'_>) -> Result<tokio::fs::File, SubscribersError> {
+    let file = spawn_blocking_with_tracing(tempfile::tempfile)
+        .await
+        .context("Failed to spawn a blocking task.")?
+        .context("Failed to create a temporary file.")?;
+    let mut file = tokio::fs::File::from_std(file);
+    while let Some(chunk) = field.chunk().await.map_err(invalid_upload)? {
+        file.write_all(&chunk).await.context("Failed to buffer the upload.")?;
+    }
+    file.flush().await.context("Failed to buffer the upload.")?;
+    file.rewind().await.context("Failed to rewind the buffered upload.")?;
+    Ok(file)
+}
+
+fn invalid_upload(e: MultipartError) -> SubscribersError {
+    SubscribersError::InvalidUpload(e.to_string())
+}
+
+impl From<ImportError> for SubscribersError {
+    fn from(e: ImportError) -> Self {
+        match e {
+            ImportError::UnexpectedError(e) => SubscribersError::UnexpectedError(e),
+            e => SubscribersError::InvalidUpload(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use hyper::{header::LOCATION, StatusCode};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::authentication::{Authorized, DeleteSubscribers, ManageSubscribers};
+use crate::flash_messages::FlashMessage;
+use crate::subscribers::{self, set_subscriber_status};
+use crate::utils::e500;
+
+#[tracing::instrument(name = "Confirm a subscriber", skip(pool, user), fields(user_id = %user.user_id))]
+pub async fn confirm_subscriber_manually(
+    State(pool): State<Arc<PgPool>>,
+    user: Authorized<ManageSubscribers>,
+    Path(subscriber_id): Path<Uuid>,
+) -> Response {
+    match set_subscriber_status(&pool, subscriber_id, "confirmed", Some(user.user_id)).await {
+        Ok(true) => subscriber_redirect(subscriber_id, FlashMessage::info("The subscription is confirmed.")),
+        Ok(false) => subscribers_redirect(FlashMessage::warning("This subscriber does not exist.")),
+        Err(e) => e500(e).into_response(),
+    }
+}
+
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(pool, user), fields(user_id = %user.user_id))]
+pub async fn unsubscribe_subscriber_manually(
+    State(pool): State<Arc<PgPool>>,
+    user: Authorized<ManageSubscribers>,
+    Path(subscriber_id): Path<Uuid>,
+) -> Response {
+    match set_subscriber_status(&pool, subscriber_id, "unsubscribed", Some(user.user_id)).await {
+        Ok(true) => subscriber_redirect(subscriber_id, FlashMessage::info("The subscriber has been unsubscribed.")),
+        Ok(false) => subscribers_redirect(FlashMessage::warning("This subscriber does not exist.")),
+        Err(e) => e500(e).into_response(),
+    }
+}
+
+#[tracing::instrument(name = "Delete a subscriber", skip(pool, admin), fields(user_id = %admin.user_id))]
+pub async fn delete_subscriber(
+    State(pool): State<Arc<PgPool>>,
+    admin: Authorized<DeleteSubscribers>,
+    Path(subscriber_id): Path<Uuid>,
+) -> Response {
+    match subscribers::delete_subscriber(&pool, subscriber_id, Some(admin.user_id)).await {
+        Ok(true) => subscribers_redirect(FlashMessage::info("The subscriber has been deleted.")),
+        Ok(false) => subscribers_redirect(FlashMessage::warning("This subscriber does not exist.")),
+        Err(e) => e500(e).into_response(),
+    }
+}
+
+fn subscriber_redirect(subscriber_id: Uuid, message: FlashMessage) -> Response {
+    message.send();
+    let location = format!("/admin/subscribers/{}", subscriber_id);
+    (StatusCode::SEE_OTHER, [(LOCATION, location)]).into_response()
+}
+
+fn subscribers_redirect(message: FlashMessage) -> Response {
+    message.send();
+    (StatusCode::SEE_OTHER, [(LOCATION, "/admin/subscribers")]).into_response()
+}
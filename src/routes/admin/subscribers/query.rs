@@ -0,0 +1,182 @@
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use hyper::StatusCode;
+use crate::csrf::reject_invalid_token;
+use crate::routes::error_chain_fmt;
+use crate::subscribers::{SubscriberFilter, STATUSES};
+use crate::templates::ErrorPage;
+use crate::utils::e500;
+
+/// The filters of the subscriber list, as submitted by its form:
+/// empty fields match everything.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
+#[serde(default)]
+pub struct SubscriberQuery {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub status: String,
+    /// `YYYY-MM-DD`, inclusive.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub subscribed_from: String,
+    /// `YYYY-MM-DD`, inclusive.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub subscribed_until: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub search: String,
+}
+
+impl SubscriberQuery {
+    pub fn filter(&self) -> Result<SubscriberFilter, SubscribersError> {
+        let status = non_empty(&self.status)
+            .map(|status| match STATUSES.contains(&status) {
+                true => Ok(status.to_owned()),
+                false => Err(SubscribersError::InvalidFilter(format!("Unknown status: {}.", status))),
+            })
+            .transpose()?;
+        let subscribed_from = non_empty(&self.subscribed_from).map(parse_date).transpose()?;
+        let subscribed_until = non_empty(&self.subscribed_until).map(parse_date).transpose()?;
+        if let (Some(from), Some(until)) = (subscribed_from, subscribed_until) {
+            if from > until {
+                return Err(SubscribersError::InvalidFilter(
+                    "The first date must not come after the last one.".into(),
+                ));
+            }
+        }
+
+        Ok(SubscriberFilter {
+            status,
+            subscribed_from: subscribed_from.map(start_of_day),
+            subscribed_before: subscribed_until
+                .map(|until| until.succ_opt().unwrap_or(NaiveDate::MAX))
+                .map(start_of_day),
+            search: non_empty(&self.search).map(str::to_owned),
+        })
+    }
+
+    /// The query string selecting the same subscribers, empty fields left out.
+    pub fn to_query_string(&self) -> String {
+        serde_urlencoded::to_string(self).unwrap_or_default()
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|value| !value.is_empty())
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, SubscribersError> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        SubscribersError::InvalidFilter(format!("{} is not a date like 2023-07-31.", date))
+    })
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("Midnight is a valid time."))
+}
+
+#[derive(thiserror::Error)]
+pub enum SubscribersError {
+    #[error("{0}")]
+    InvalidFilter(String),
+    #[error("{0}")]
+    InvalidUpload(String),
+    #[error("This page does not exist.")]
+    NotFound,
+    #[error("Invalid or missing CSRF token.")]
+    InvalidCsrfToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SubscribersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for SubscribersError {
+    fn into_response(self) -> Response {
+        let back = |page: ErrorPage| page.back_to("/admin/subscribers", "Back to the subscribers");
+        match self {
+            Self::InvalidFilter(_) => {
+                (StatusCode::BAD_REQUEST, back(ErrorPage::new("Invalid filter", self.to_string())))
+                    .into_response()
+            },
+            Self::InvalidUpload(_) => {
+                (StatusCode::BAD_REQUEST, back(ErrorPage::new("Invalid file", self.to_string())))
+                    .into_response()
+            },
+            Self::NotFound => {
+                (StatusCode::NOT_FOUND, back(ErrorPage::new("Not found", self.to_string())))
+                    .into_response()
+            },
+            Self::InvalidCsrfToken => reject_invalid_token(),
+            Self::UnexpectedError(e) => e500(e).into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_ok};
+    use super::SubscriberQuery;
+
+    #[test]
+    fn empty_fields_match_everything() {
+        let query = SubscriberQuery { search: "  ".into(), ..Default::default() };
+
+        let filter = assert_ok!(query.filter());
+        assert!(filter.status.is_none());
+        assert!(filter.subscribed_from.is_none());
+        assert!(filter.subscribed_before.is_none());
+        assert!(filter.search.is_none());
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        let query = SubscriberQuery { status: "bounced".into(), ..Default::default() };
+
+        assert_err!(query.filter());
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected() {
+        let query = SubscriberQuery { subscribed_from: "31/07/2023".into(), ..Default::default() };
+
+        assert_err!(query.filter());
+    }
+
+    #[test]
+    fn reversed_date_ranges_are_rejected() {
+        let query = SubscriberQuery {
+            subscribed_from: "2023-08-01".into(),
+            subscribed_until: "2023-07-31".into(),
+            ..Default::default()
+        };
+
+        assert_err!(query.filter());
+    }
+
+    #[test]
+    fn the_last_day_is_included() {
+        let query = SubscriberQuery {
+            subscribed_from: "2023-07-31".into(),
+            subscribed_until: "2023-07-31".into(),
+            ..Default::default()
+        };
+
+        let filter = assert_ok!(query.filter());
+        assert_eq!(filter.subscribed_from, Some(Utc.with_ymd_and_hms(2023, 7, 31, 0, 0, 0).unwrap()));
+        assert_eq!(filter.subscribed_before, Some(Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn query_strings_leave_empty_fields_out() {
+        let query = SubscriberQuery {
+            status: "confirmed".into(),
+            search: "le guin".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(query.to_query_string(), "status=confirmed&search=le+guin");
+    }
+}
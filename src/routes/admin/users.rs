@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::users;
+pub use post::{add_user, disable_user, enable_user, update_user_role};
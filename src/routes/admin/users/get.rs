@@ -0,0 +1,43 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::response::Html;
+use axum::Extension;
+use hyper::StatusCode;
+use sqlx::PgPool;
+use crate::authentication::{get_users, Authorized, ManageUsers};
+use crate::csrf::CsrfToken;
+use crate::domain::Role;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::templates::Templates;
+use crate::utils::e500;
+
+/// Every user, with forms to add new ones and to change existing ones.
+#[tracing::instrument(name = "Show the users", skip(pool, templates, flash_messages))]
+pub async fn users(
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    admin: Authorized<ManageUsers>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let users = get_users(&pool).await.map_err(e500)?;
+    let users: Vec<_> = users
+        .iter()
+        .map(|user| serde_json::json!({
+            "user_id": user.user_id,
+            "username": user.username,
+            "role": user.role.as_str(),
+            "disabled_on": user.disabled_at.map(|d| d.format("%Y-%m-%d").to_string()),
+            // Admins cannot lock themselves out.
+            "is_current_user": user.user_id == admin.user_id,
+        }))
+        .collect();
+    let roles: Vec<_> = Role::ALL.iter().map(Role::as_str).collect();
+
+    let mut context = tera::Context::new();
+    context.insert("users", &users);
+    context.insert("roles", &roles);
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    templates.render("admin/users.html", &context).map(Html).map_err(e500)
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use axum::Form;
+use hyper::{header::LOCATION, StatusCode};
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::authentication::{
+    change_role, create_user, set_user_disabled, Authorized, CreateUserError, ManageUsers,
+};
+use crate::domain::{NewPassword, Role};
+use crate::flash_messages::FlashMessage;
+use crate::utils::e500;
+
+const MAX_USERNAME_LENGTH: usize = 256;
+
+#[derive(serde::Deserialize)]
+pub struct NewUserForm {
+    username: String,
+    password: Secret<String>,
+    role: String,
+}
+
+#[tracing::instrument(
+    name = "Add a user",
+    skip(form, pool, admin),
+    fields(user_id = %admin.user_id, username = %form.username, role = %form.role)
+)]
+pub async fn add_user(
+    State(pool): State<Arc<PgPool>>,
+    admin: Authorized<ManageUsers>,
+    Form(form): Form<NewUserForm>,
+) -> Response {
+    let username = form.username.trim();
+    if username.is_empty() || username.chars().count() > MAX_USERNAME_LENGTH {
+        return users_redirect(FlashMessage::error(format!(
+            "The username must be between 1 and {} characters long.",
+            MAX_USERNAME_LENGTH
+        )));
+    }
+    let role = match Role::parse(&form.role) {
+        Ok(role) => role,
+        Err(e) => return users_redirect(FlashMessage::error(e)),
+    };
+    let password = match NewPassword::parse(form.password) {
+        Ok(password) => password,
+        Err(e) => return users_redirect(FlashMessage::error(e)),
+    };
+
+    match create_user(&pool, username, password, role, Some(admin.user_id)).await {
+        Ok(_) => users_redirect(FlashMessage::info(format!("{} has been added.", username))),
+        Err(CreateUserError::UsernameTaken) => {
+            users_redirect(FlashMessage::error(format!("{} is already taken.", username)))
+        },
+        Err(CreateUserError::UnexpectedError(e)) => e500(e).into_response(),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleForm {
+    role: String,
+}
+
+#[tracing::instrument(
+    name = "Change the role of a user",
+    skip(form, pool, admin),
+    fields(user_id = %admin.user_id, role = %form.role)
+)]
+pub async fn update_user_role(
+    State(pool): State<Arc<PgPool>>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<RoleForm>,
+) -> Response {
+    if user_id == admin.user_id {
+        return users_redirect(FlashMessage::error("You cannot change your own role."));
+    }
+    let role = match Role::parse(&form.role) {
+        Ok(role) => role,
+        Err(e) => return users_redirect(FlashMessage::error(e)),
+    };
+
+    match change_role(&pool, user_id, role, Some(admin.user_id)).await {
+        Ok(true) => users_redirect(FlashMessage::info(format!("The role has been changed to {}.", role))),
+        Ok(false) => users_redirect(FlashMessage::warning("This user does not exist.")),
+        Err(e) => e500(e).into_response(),
+    }
+}
+
+#[tracing::instrument(name = "Disable a user", skip(pool, admin), fields(user_id = %admin.user_id))]
+pub async fn disable_user(
+    State(pool): State<Arc<PgPool>>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<Uuid>,
+) -> Response {
+    if user_id == admin.user_id {
+        return users_redirect(FlashMessage::error("You cannot disable your own account."));
+    }
+    set_disabled(&pool, user_id, true, &admin).await
+}
+
+#[tracing::instrument(name = "Enable a user", skip(pool, admin), fields(user_id = %admin.user_id))]
+pub async fn enable_user(
+    State(pool): State<Arc<PgPool>>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<Uuid>,
+) -> Response {
+    set_disabled(&pool, user_id, false, &admin).await
+}
+
+async fn set_disabled(
+    pool: &PgPool,
+    user_id: Uuid,
+    disabled: bool,
+    admin: &Authorized<ManageUsers>,
+) -> Response {
+    match set_user_disabled(pool, user_id, disabled, Some(admin.user_id)).await {
+        Ok(true) if disabled => users_redirect(FlashMessage::info("The account has been disabled.")),
+        Ok(true) => users_redirect(FlashMessage::info("The account has been enabled.")),
+        Ok(false) => users_redirect(FlashMessage::warning("This user does not exist.")),
+        Err(e) => e500(e).into_response(),
+    }
+}
+
+fn users_redirect(message: FlashMessage) -> Response {
+    message.send();
+    (StatusCode::SEE_OTHER, [(LOCATION, "/admin/users")]).into_response()
+}
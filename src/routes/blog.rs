@@ -1 +1,203 @@
-pub async fn blog() {}
+use std::sync::Arc;
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use sqlx::PgPool;
+use utoipa::IntoParams;
+use uuid::Uuid;
+use crate::routes::error_chain_fmt;
+use crate::templates::{ErrorPage, Templates};
+
+const ISSUES_PER_PAGE: i64 = 10;
+
+#[derive(thiserror::Error)]
+pub enum BlogError {
+    #[error("This page does not exist.")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl std::fmt::Debug for BlogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for BlogError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::NotFound => {
+                (StatusCode::NOT_FOUND, ErrorPage::new("Not found", self.to_string())).into_response()
+            },
+            Self::UnexpectedError(e) => {
+                tracing::error!("\nServer error: {:?}", e);
+                let body = Json(serde_json::json!({
+                    "error": "Unexpected internal server error."
+                }));
+                (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+            }
+        }
+    }
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    /// Starts at 1.
+    #[param(minimum = 1)]
+    page: Option<u32>,
+}
+
+/// The public archive of published issues, newest first.
+#[utoipa::path(
+    get,
+    path = "/blog",
+    tag = "pages",
+    params(Pagination),
+    responses(
+        (status = 200, description = "A page of the archive.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Show the newsletter archive", skip(pagination, pool, templates))]
+pub async fn blog(
+    Query(pagination): Query<Pagination>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+) -> Result<Html<String>, BlogError> {
+    let page_number = pagination.page.unwrap_or(1);
+    if page_number == 0 {
+        return Err(BlogError::NotFound);
+    }
+    // One extra row tells us whether there is an older page.
+    let mut issues = get_issue_summaries(
+        &pool,
+        ISSUES_PER_PAGE + 1,
+        (page_number as i64 - 1) * ISSUES_PER_PAGE,
+    )
+    .await
+    .context("Failed to retrieve the published issues.")?;
+    let has_older = issues.len() as i64 > ISSUES_PER_PAGE;
+    issues.truncate(ISSUES_PER_PAGE as usize);
+    if issues.is_empty() && page_number > 1 {
+        return Err(BlogError::NotFound);
+    }
+
+    let issues: Vec<_> = issues
+        .iter()
+        .map(|issue| serde_json::json!({
+            "newsletter_issue_id": issue.newsletter_issue_id,
+            "title": issue.title,
+            "published_at": issue.published_at.format("%Y-%m-%d").to_string(),
+        }))
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("issues", &issues);
+    if page_number > 1 {
+        context.insert("newer_page", &(page_number - 1));
+    }
+    if has_older {
+        context.insert("older_page", &(page_number + 1));
+    }
+    let html = templates
+        .render("blog/archive.html", &context)
+        .context("Failed to render the newsletter archive.")?;
+
+    Ok(Html(html))
+}
+
+/// A single published issue.
+#[utoipa::path(
+    get,
+    path = "/blog/{issue_id}",
+    tag = "pages",
+    params(("issue_id" = Uuid, Path, description = "As in the links of the archive.")),
+    responses(
+        (status = 200, description = "The issue.", body = String, content_type = "text/html"),
+        (status = 404, description = "There is no such published issue.", body = String,
+            content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Show a newsletter issue", skip(pool, templates))]
+pub async fn blog_issue(
+    Path(issue_id): Path<Uuid>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+) -> Result<Html<String>, BlogError> {
+    let issue = get_published_issue(&pool, issue_id)
+        .await
+        .context("Failed to retrieve a newsletter issue.")?
+        .ok_or(BlogError::NotFound)?;
+
+    let mut context = tera::Context::new();
+    context.insert("issue", &serde_json::json!({
+        "title": issue.title,
+        "html_content": issue.html_content,
+        "published_at": issue.published_at.format("%Y-%m-%d").to_string(),
+        "author": issue.author,
+    }));
+    let html = templates
+        .render("blog/issue.html", &context)
+        .context("Failed to render a newsletter issue.")?;
+
+    Ok(Html(html))
+}
+
+struct IssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_issue_summaries(
+    pool: &PgPool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<IssueSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        IssueSummary,
+        r#"
+            SELECT newsletter_issue_id, title, published_at
+                FROM newsletter_issues
+                ORDER BY published_at DESC, newsletter_issue_id
+                LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+struct PublishedIssue {
+    title: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+    author: Option<String>,
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_published_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<PublishedIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        PublishedIssue,
+        r#"
+            SELECT title, html_content, published_at, users.username AS "author?"
+                FROM newsletter_issues
+                LEFT JOIN users ON users.user_id = newsletter_issues.author_user_id
+                WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+}
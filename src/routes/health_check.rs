@@ -1 +1,196 @@
-pub async fn health_check() {}
\ No newline at end of file
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hyper::StatusCode;
+use serde_json::{json, Map, Value};
+use sqlx::migrate::Migrator;
+use sqlx::PgPool;
+use crate::email_client::EmailClient;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+// A single check never holds the probe for longer than this.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+// Above this share of busy connections the pool is reported as degraded.
+const POOL_SATURATION_WARNING: f64 = 0.9;
+
+/// Which optional checks `/health_check/ready` runs.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadinessChecks {
+    pub probe_email_backend: bool,
+}
+
+/// The server is up.
+#[utoipa::path(
+    get,
+    path = "/health_check",
+    tag = "operations",
+    responses((status = 200, description = "Empty body.")),
+)]
+pub async fn health_check() {}
+
+/// The process is up and serving requests.
+#[utoipa::path(
+    get,
+    path = "/health_check/live",
+    tag = "operations",
+    responses((status = 200, description = "Always `ok`.", body = Liveness)),
+)]
+pub async fn liveness() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Ok,
+    Degraded,
+    Down,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Degraded => "degraded",
+            Status::Down => "down",
+        }
+    }
+}
+
+/// Whether the application can do its job.
+///
+/// Answers 503 as soon as one of its dependencies is down,
+/// with the details of every check.
+#[utoipa::path(
+    get,
+    path = "/health_check/ready",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Ready, maybe degraded.", body = Readiness),
+        (status = 503, description = "A dependency is down.", body = Readiness),
+    ),
+)]
+#[tracing::instrument(name = "Readiness check", skip_all)]
+pub async fn readiness(
+    State(pool): State<Arc<PgPool>>,
+    Extension(email_client): Extension<Arc<EmailClient>>,
+    Extension(checks): Extension<ReadinessChecks>,
+) -> Response {
+    let mut results = vec![
+        ("database", check_database(&pool).await),
+        ("pool", check_pool(&pool)),
+        ("migrations", check_migrations(&pool).await),
+    ];
+    if checks.probe_email_backend {
+        results.push(("email", check_email_backend(&email_client).await));
+    }
+
+    let overall = results
+        .iter()
+        .map(|(_, (status, _))| *status)
+        .max()
+        .unwrap_or(Status::Ok);
+    let details: Map<String, Value> = results
+        .into_iter()
+        .map(|(name, (status, mut detail))| {
+            detail.insert("status".into(), status.as_str().into());
+            (name.to_owned(), Value::Object(detail))
+        })
+        .collect();
+    let status_code = if overall == Status::Down {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+
+    (
+        status_code,
+        Json(json!({ "status": overall.as_str(), "checks": details })),
+    )
+        .into_response()
+}
+
+type CheckResult = (Status, Map<String, Value>);
+
+/// The error is logged, never returned: the probe is public, and database
+/// or SMTP errors tell more about the infrastructure than it should.
+fn down(check: &str, error: impl std::fmt::Display, detail: Map<String, Value>) -> CheckResult {
+    tracing::error!(check, error = %error, "A readiness check failed.");
+    (Status::Down, detail)
+}
+
+fn latency(start: Instant) -> Map<String, Value> {
+    let mut detail = Map::new();
+    detail.insert(
+        "latency_ms".into(),
+        (start.elapsed().as_secs_f64() * 1000.0).into(),
+    );
+    detail
+}
+
+async fn check_database(pool: &PgPool) -> CheckResult {
+    let start = Instant::now();
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(pool)).await;
+    match outcome {
+        Ok(Ok(_)) => (Status::Ok, latency(start)),
+        Ok(Err(e)) => down("database", e, latency(start)),
+        Err(_) => down("database", "Timed out.", latency(start)),
+    }
+}
+
+fn check_pool(pool: &PgPool) -> CheckResult {
+    let max_connections = pool.options().get_max_connections();
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+    let saturation = size.saturating_sub(idle) as f64 / max_connections as f64;
+
+    let mut detail = Map::new();
+    detail.insert("size".into(), size.into());
+    detail.insert("idle".into(), idle.into());
+    detail.insert("max_connections".into(), max_connections.into());
+    detail.insert("saturation".into(), saturation.into());
+    let status = if saturation >= POOL_SATURATION_WARNING {
+        Status::Degraded
+    } else {
+        Status::Ok
+    };
+    (status, detail)
+}
+
+async fn check_migrations(pool: &PgPool) -> CheckResult {
+    let start = Instant::now();
+    // `_sqlx_migrations` is managed by sqlx, it is not known at compile time.
+    let applied = tokio::time::timeout(
+        CHECK_TIMEOUT,
+        sqlx::query_scalar::<_, i64>("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool),
+    )
+    .await;
+    let applied = match applied {
+        Ok(Ok(applied)) => applied,
+        Ok(Err(e)) => return down("migrations", e, latency(start)),
+        Err(_) => return down("migrations", "Timed out.", latency(start)),
+    };
+
+    let pending: Vec<Value> = MIGRATOR
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .map(|m| format!("{}_{}", m.version, m.description).into())
+        .collect();
+    let mut detail = latency(start);
+    detail.insert("applied".into(), applied.len().into());
+    let status = if pending.is_empty() { Status::Ok } else { Status::Down };
+    detail.insert("pending".into(), pending.into());
+    (status, detail)
+}
+
+async fn check_email_backend(email_client: &EmailClient) -> CheckResult {
+    let start = Instant::now();
+    match tokio::time::timeout(CHECK_TIMEOUT, email_client.probe()).await {
+        Ok(Ok(())) => (Status::Ok, latency(start)),
+        Ok(Err(e)) => down("email", e, latency(start)),
+        Err(_) => down("email", "Timed out.", latency(start)),
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod home;
 
 pub use home::*;
\ No newline at end of file
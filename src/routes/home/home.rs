@@ -1,10 +1,26 @@
-use axum::response::{IntoResponse, Response};
+use axum::{response::Html, Extension};
 use hyper::StatusCode;
+use crate::csrf::CsrfToken;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::templates::Templates;
+use crate::utils::e500;
 
-pub async fn home() -> impl IntoResponse {
-    Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "text/html")
-        .body(include_str!("home.html").to_owned())
-        .unwrap()
-}
\ No newline at end of file
+/// Home page, with the subscription form.
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "pages",
+    responses(
+        (status = 200, description = "The home page.", body = String, content_type = "text/html"),
+    )
+)]
+pub async fn home(
+    Extension(templates): Extension<Templates>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let mut context = tera::Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    templates.render("home.html", &context).map(Html).map_err(e500)
+}
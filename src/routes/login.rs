@@ -1,5 +1,5 @@
 mod get;
 mod post;
 
-pub use post::login;
-pub use get::login_form;
+pub use post::{login, __path_login};
+pub use get::{login_form, __path_login_form};
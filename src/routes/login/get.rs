@@ -1,86 +1,26 @@
-use axum::response::{IntoResponse, Response};
-use axum_extra::extract::cookie::{CookieJar, Cookie};
+use axum::{response::Html, Extension};
 use hyper::StatusCode;
+use crate::csrf::CsrfToken;
+use crate::flash_messages::IncomingFlashMessages;
+use crate::templates::Templates;
+use crate::utils::e500;
 
-
+/// The login form of the admin area.
+#[utoipa::path(
+    get,
+    path = "/login",
+    tag = "pages",
+    responses(
+        (status = 200, description = "The form.", body = String, content_type = "text/html"),
+    )
+)]
 pub async fn login_form(
-    jar: CookieJar,
-) -> impl IntoResponse {
-    let error_html = match jar.get("_flash") {
-        None => "".into(),
-        Some(cookie) => {
-            format!("<p><i>{}</i></p>", cookie.value())
-        }
-    };
-
-//     Response::builder()
-//         .status(200)
-//         .header("Content-Type", "text/html")
-//         // Make it with tera later
-//         .body(format!(
-//             r#"<!DOCTYPE html>
-// <html lang="en">
-// <head>
-//     <meta http-equiv="content-type" content="text/html; charset=utf-8">
-//     <title>Login</title>
-// </head>
-// <body>
-//     {error_html}
-//     <form action="/login" method="post">
-//         <label>Username
-//             <input
-//                 type="text"
-//                 placeholder="Enter Username"
-//                 name="username"
-//             >
-//         </label>
-//         <label>Password
-//             <input
-//                 type="password"
-//                 placeholder="Enter Password"
-//                 name="password"
-//             >
-//         </label>
-//         <button type="submit">Login</button>
-//     </form>
-// </body>
-// </html>"#,
-//         ))
-        // .unwrap();
-    let body = format!(
-            r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Login</title>
-</head>
-<body>
-    {error_html}
-    <form action="/login" method="post">
-        <label>Username
-            <input
-                type="text"
-                placeholder="Enter Username"
-                name="username"
-            >
-        </label>
-        <label>Password
-            <input
-                type="password"
-                placeholder="Enter Password"
-                name="password"
-            >
-        </label>
-        <button type="submit">Login</button>
-    </form>
-</body>
-</html>"#
-);
-        (
-            StatusCode::OK,
-            [("Content-Type", "text/html")],
-            jar.remove(Cookie::named("_flash")),
-            body
-        )
-    
-}
\ No newline at end of file
+    Extension(templates): Extension<Templates>,
+    csrf_token: CsrfToken,
+    flash_messages: IncomingFlashMessages,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let mut context = tera::Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("flash_messages", &flash_messages);
+    templates.render("login.html", &context).map(Html).map_err(e500)
+}
@@ -1,23 +1,32 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
-use axum::extract::State;
+use axum::extract::{ConnectInfo, State};
 use axum::response::{IntoResponse, Response};
-use axum::{Form, Extension};
-use hyper::{StatusCode, header::{LOCATION, SET_COOKIE}};
-use secrecy::{Secret, ExposeSecret};
-use crate::authentication::{validate_credentials, Credentials, AuthError};
+use axum::{Extension, Form};
+use hyper::{StatusCode, header::LOCATION};
+use secrecy::Secret;
+use utoipa::ToSchema;
+use crate::metrics::LOGIN_FAILURES;
+use crate::authentication::{
+    validate_credentials_throttled, AuthError, Credentials, LoginThrottle, ThrottledAuthError,
+};
 use crate::routes::error_chain_fmt;
+use crate::session::TypedSession;
+use crate::flash_messages::FlashMessage;
 use sqlx::PgPool;
-use axum_extra::extract::cookie::{Cookie, CookieJar};
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct FormData {
     username: String,
+    #[schema(value_type = String, format = Password)]
     password: Secret<String>,
 }
 #[derive(thiserror::Error)]
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, try again later.")]
+    AccountLocked(#[source] anyhow::Error),
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -44,12 +53,25 @@ impl std::fmt::Debug for LoginError {
 //          }
 //     }
 // }
+/// Log in to the admin area.
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "pages",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "To `/admin/dashboard` on success, back to `/login` otherwise."),
+    )
+)]
 #[tracing::instrument(
-    skip(form, pool),
+    skip(form, pool, session, throttle),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
     State(pool): State<Arc<PgPool>>,
+    Extension(throttle): Extension<LoginThrottle>,
+    ConnectInfo(client_address): ConnectInfo<SocketAddr>,
+    session: TypedSession,
     Form(form): Form<FormData>,
 ) -> impl IntoResponse {
     let credentials = Credentials {
@@ -57,32 +79,47 @@ pub async fn login(
         password: form.password,
     };
     tracing::Span::current()
-        .record("username", &tracing::field::display(&credentials.username));
-    match validate_credentials(credentials, &pool).await {
+        .record("username", tracing::field::display(&credentials.username));
+    match validate_credentials_throttled(credentials, client_address.ip(), &throttle, &pool).await {
         Ok(user_id) => {
             tracing::Span::current()
-                .record("user_id", &tracing::field::display(&user_id));
+                .record("user_id", tracing::field::display(&user_id));
+            // Rotate the session key on login to prevent session fixation
+            session.renew();
+            if let Err(e) = session.insert_user_id(user_id) {
+                return login_redirect(LoginError::UnexpectedError(e.into()));
+            }
             (
                 StatusCode::SEE_OTHER,
-                [(LOCATION, "/")]
+                [(LOCATION, "/admin/dashboard")]
             ).into_response()
         },
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
-                AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
+                ThrottledAuthError::AuthError(AuthError::InvalidCredentials(_)) => {
+                    LOGIN_FAILURES.inc();
+                    LoginError::AuthError(e.into())
+                },
+                ThrottledAuthError::AccountLocked { .. } => {
+                    LOGIN_FAILURES.inc();
+                    LoginError::AccountLocked(e.into())
+                },
+                ThrottledAuthError::AuthError(AuthError::UnexpectedError(_)) => {
+                    LoginError::UnexpectedError(e.into())
+                },
             };
 
-            tracing::error!("\nServer error: {e:?}");
-
-            (
-                StatusCode::SEE_OTHER,
-                [(LOCATION, "/login"),],
-                CookieJar::new()
-                    .add(Cookie::new("_flash", e.to_string())),
-                
-            ).into_response()
+            login_redirect(e)
         }
     }
 }
 
+fn login_redirect(e: LoginError) -> Response {
+    tracing::error!("\nServer error: {e:?}");
+
+    FlashMessage::error(e.to_string()).send();
+    (
+        StatusCode::SEE_OTHER,
+        [(LOCATION, "/login")],
+    ).into_response()
+}
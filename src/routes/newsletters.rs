@@ -1,25 +1,39 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use base64::Engine;
 use secrecy::Secret;
 use anyhow::Context;
 use axum::{
-    Extension,
     Json,
     headers::{HeaderMap},
-    response::{IntoResponse},
-    extract::State,
+    response::{IntoResponse, Response},
+    extract::{ConnectInfo, State},
+    Extension,
     http::{StatusCode, HeaderValue, header}
 };
-use sqlx::PgPool;
-use crate::{email_client::EmailClient, domain::SubscriberEmail};
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
 use super::error_chain_fmt;
-use crate::authentication::{AuthError, validate_credentials, Credentials};
+use crate::metrics::NEWSLETTERS_PUBLISHED;
+use crate::authentication::{
+    get_enabled_user, validate_credentials_throttled, AuthError, Credentials, LoginThrottle,
+    Permission, PublishNewsletters, ThrottledAuthError,
+};
+use crate::idempotency::{IdempotencyKey, NextAction, try_processing, save_response};
 
 
 #[derive(thiserror::Error)]
 pub enum PublishError {
     #[error("Authentication failed.")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, try again later.")]
+    AccountLocked { retry_after: Duration },
+    #[error("You are not allowed to publish newsletters.")]
+    Forbidden,
+    #[error("{0}")]
+    ValidationError(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error)
 }
@@ -37,6 +51,10 @@ impl IntoResponse for PublishError {
                 tracing::error!("\nServer error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.").into_response()
             },
+            Self::ValidationError(e) => {
+                tracing::error!("\nValidation error: {}", e);
+                (StatusCode::BAD_REQUEST, e).into_response()
+            },
             Self::AuthError(e) => {
                 tracing::error!("\nAuthorization error: {:?}", e);
                 
@@ -47,95 +65,147 @@ impl IntoResponse for PublishError {
                     "Authorization failed."
                 )
                 .into_response()
+            },
+            Self::Forbidden => {
+                tracing::error!("\nAuthorization error: {}", self);
+                (StatusCode::FORBIDDEN, self.to_string()).into_response()
+            },
+            Self::AccountLocked { retry_after } => {
+                tracing::error!("\nAuthorization error: {}", self);
+                // Round up, so that clients do not retry a second too early.
+                let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    self.to_string(),
+                )
+                .into_response()
             }
         }
     }    
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct BodyData {
     title: String,
     content: Content
 }
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct Content {
     html: String,
     text: String
 }
 
+/// Publish an issue to every confirmed subscriber.
+///
+/// Needs the credentials of an editor or an admin.
+#[utoipa::path(
+    post,
+    path = "/newsletters",
+    tag = "newsletters",
+    params(
+        ("Idempotency-Key" = String, Header,
+            description = "Retries with the same key get the first response back."),
+    ),
+    request_body = BodyData,
+    responses(
+        (status = 202, description = "The issue is queued for delivery."),
+        (status = 400, description = "The idempotency key is missing or invalid.", body = String),
+        (status = 401, description = "Missing or wrong credentials.", body = String),
+        (status = 403, description = "The user is not allowed to publish.", body = String),
+        (status = 429, description = "The account is locked after too many failures.",
+            body = String, headers(("Retry-After" = u64, description = "Seconds to wait."))),
+    ),
+    security(("basic" = []))
+)]
 #[tracing::instrument(
     name = "Publish a newsletter issue",
-    skip(body, pool, email_client, headers),
+    skip(body, pool, headers, throttle),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn publish_newsletter(
     State(pool): State<Arc<PgPool>>,
-    Extension(email_client): Extension<Arc<EmailClient>>,
+    Extension(throttle): Extension<LoginThrottle>,
+    ConnectInfo(client_address): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     Json(body): Json<BodyData>,
-) -> Result<impl IntoResponse, PublishError> {
-    let credentials = basic_authentication(headers)
+) -> Result<Response, PublishError> {
+    let credentials = basic_authentication(&headers)
         .map_err(PublishError::AuthError)?;
     tracing::Span::current().record(
         "username",
-        &tracing::field::display(&credentials.username)
+        tracing::field::display(&credentials.username)
     );
-    let user_id = validate_credentials(credentials, &pool)
+    let user_id = validate_credentials_throttled(credentials, client_address.ip(), &throttle, &pool)
         .await
         // We match on `AuthError`'s variants, but we pass the **whole** error
         // into the constructors for `PublishError` variants. This ensures that
         // the context of the top-level wrapper is preserved when the error is
         // logged by our middleware  
         .map_err(|e| match e {
-            AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
-            AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into())
+            ThrottledAuthError::AuthError(AuthError::InvalidCredentials(_)) => {
+                PublishError::AuthError(e.into())
+            },
+            ThrottledAuthError::AuthError(AuthError::UnexpectedError(_)) => {
+                PublishError::UnexpectedError(e.into())
+            },
+            ThrottledAuthError::AccountLocked { retry_after } => {
+                PublishError::AccountLocked { retry_after }
+            },
         })?;
     tracing::Span::current().record(
         "user_id",
-        &tracing::field::display(&user_id)
+        tracing::field::display(&user_id)
     );
+    // The user may have been disabled since their credentials were checked.
+    let user = get_enabled_user(&pool, user_id)
+        .await?
+        .ok_or_else(|| PublishError::AuthError(anyhow::anyhow!("The user is disabled.")))?;
+    if !PublishNewsletters::is_granted_to(user.role) {
+        return Err(PublishError::Forbidden);
+    }
 
-    let subscribers = get_confirmed_subscribers(&pool)
-        .await
-        .context("Failed to get confirmed subscribers from the database")?;
-    
-    for subscriber in subscribers {
-        match subscriber {
-            Ok(subscriber) => {
-                email_client
-                    .send_email(
-                        &subscriber.email,
-                        &body.title,
-                        &body.content.html,
-                        &body.content.text
-                    )
-                    .await
-                    .with_context(|| {
-                        format!(
-                            "Failed to send newsletter issue to {}",
-                            subscriber.email
-                        )
-                    })?;
-            },
-            Err(error) => {
-                tracing::warn!(
-                    // We record the error chain as a structual field
-                    // on the log record
-                    error.cause_chain = ?error,
-                    // Using '\' to split a long string literal over
-                    // two lines, without creating a '\n' character.
-                    "Skipping a confirmed subscriber. \
-                    Their stored contact details are invalid.",
-                );
-            } 
+    let idempotency_key: IdempotencyKey = headers
+        .get("Idempotency-Key")
+        .ok_or_else(|| PublishError::ValidationError(
+            "The 'Idempotency-Key' header was missing.".into()
+        ))?
+        .to_str()
+        .map_err(|_| PublishError::ValidationError(
+            "The 'Idempotency-Key' header was not a valid UTF-8 string.".into()
+        ))?
+        .to_owned()
+        .try_into()
+        .map_err(PublishError::ValidationError)?;
+
+    let mut transaction = match try_processing(&pool, &idempotency_key, user_id).await? {
+        NextAction::StartProcessing(t) => t,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            return Ok(saved_response);
         }
-    }
-    Ok(StatusCode::OK)
-}
+    };
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &body.title,
+        &body.content.text,
+        &body.content.html,
+        user_id,
+    )
+    .await
+    .context("Failed to store newsletter issue details.")?;
+    enqueue_delivery_tasks(&mut transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks.")?;
+    let response = StatusCode::ACCEPTED.into_response();
+    let response = save_response(transaction, &idempotency_key, user_id, response)
+        .await?;
+    NEWSLETTERS_PUBLISHED.inc();
 
+    Ok(response)
+}
 
 fn basic_authentication(
-    headers: HeaderMap,
+    headers: &HeaderMap,
 ) -> Result<Credentials, anyhow::Error> {
     let header_value = headers
         .get("Authorization")
@@ -166,36 +236,58 @@ fn basic_authentication(
         username,
         password: Secret::new(password) })
 }
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
+#[tracing::instrument(skip_all)]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+    author_user_id: Uuid,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+            INSERT INTO newsletter_issues (
+                newsletter_issue_id,
+                title,
+                text_content,
+                html_content,
+                author_user_id,
+                published_at
+            )
+            VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content,
+        author_user_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(newsletter_issue_id)
 }
 
-#[tracing::instrument(
-    name = "Get confirmed subscribers",
-    skip(pool)
-)]
-async fn get_confirmed_subscribers(
-    pool: &PgPool,
-    // We are returning a `Vec` of `Result`s in the happy case.
-    // This allows the caller to bubble up errors due to network issues or other
-    // transient failures using the `?` operator, while the compiler 
-    // forces them to handle the subtler mapping error.
-    // See http://sled.rs/errors.html for a deep-dive about this technique.
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {  
-    let confirmed_subscribers = sqlx::query!(
+#[tracing::instrument(skip_all)]
+async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
         r#"
-            SELECT email FROM subscriptions
+            INSERT INTO issue_delivery_queue (
+                newsletter_issue_id,
+                subscriber_email
+            )
+            SELECT $1, email
+                FROM subscriptions
                 WHERE status = 'confirmed'
         "#,
+        newsletter_issue_id,
     )
-    .fetch_all(pool)
-    .await?
-    .into_iter()
-    .map(|r| match SubscriberEmail::parse(r.email) {
-        Ok(email) => Ok(ConfirmedSubscriber { email }),
-        Err(error) => Err(anyhow::anyhow!(error)),
-    })
-    .collect();
+    .execute(&mut **transaction)
+    .await?;
 
-    Ok(confirmed_subscribers)
+    Ok(())
 }
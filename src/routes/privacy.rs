@@ -0,0 +1,8 @@
+mod manage;
+mod request;
+
+pub use manage::{
+    erase_personal_data, export_personal_data, privacy_dashboard, __path_erase_personal_data,
+    __path_export_personal_data, __path_privacy_dashboard,
+};
+pub use request::{privacy_page, request_privacy_link, __path_privacy_page, __path_request_privacy_link};
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::{Query, State};
+use axum::response::{Html, IntoResponse, Response};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use hyper::header::CONTENT_DISPOSITION;
+use hyper::StatusCode;
+use sqlx::PgPool;
+use utoipa::IntoParams;
+use uuid::Uuid;
+use crate::audit::{record_audit_event, AuditEvent};
+use crate::csrf::CsrfToken;
+use crate::routes::error_chain_fmt;
+use crate::subscribers::{erase_subscriber, get_personal_data};
+use crate::templates::{ErrorPage, Templates};
+
+#[derive(thiserror::Error)]
+pub enum PrivacyError {
+    #[error("This link is not valid.")]
+    UnknownToken,
+    #[error("This link has expired. Please ask for a new one.")]
+    ExpiredToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl std::fmt::Debug for PrivacyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for PrivacyError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::UnknownToken => StatusCode::UNAUTHORIZED,
+            Self::ExpiredToken => StatusCode::GONE,
+            Self::UnexpectedError(e) => {
+                tracing::error!("\nServer error: {:?}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                    .into_response();
+            }
+        };
+        let page = ErrorPage::new("Your data", self.to_string())
+            .back_to("/privacy", "Ask for a new link");
+        (status, page).into_response()
+    }
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    /// From the emailed privacy link.
+    token: String,
+}
+
+/// Where the emailed link leads: a download link and an erasure form.
+#[utoipa::path(
+    get,
+    path = "/privacy/manage",
+    tag = "privacy",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The page.", body = String, content_type = "text/html"),
+        (status = 401, description = "Unknown link.", body = String, content_type = "text/html"),
+        (status = 410, description = "The link has expired.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Show the privacy dashboard", skip(parameters, pool, templates, csrf_token))]
+pub async fn privacy_dashboard(
+    Query(parameters): Query<Parameters>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    csrf_token: CsrfToken,
+) -> Result<Html<String>, PrivacyError> {
+    check_privacy_token(&pool, &parameters.token).await?;
+
+    let token = urlencoding::encode(&parameters.token);
+    let mut context = tera::Context::new();
+    context.insert("export_link", &format!("/privacy/export?token={}", token));
+    context.insert("erase_action", &format!("/privacy/erase?token={}", token));
+    context.insert("csrf_token", csrf_token.as_str());
+    let html = templates
+        .render("privacy/manage.html", &context)
+        .context("Failed to render the privacy dashboard.")?;
+
+    Ok(Html(html))
+}
+
+/// Everything we keep about the subscriber, as a JSON download.
+/// The link keeps working until it expires.
+#[utoipa::path(
+    get,
+    path = "/privacy/export",
+    tag = "privacy",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The data, as an attachment.", body = PersonalDataExport),
+        (status = 401, description = "Unknown link.", body = String, content_type = "text/html"),
+        (status = 410, description = "The link has expired.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Export personal data", skip(parameters, pool))]
+pub async fn export_personal_data(
+    Query(parameters): Query<Parameters>,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Response, PrivacyError> {
+    let subscriber_id = check_privacy_token(&pool, &parameters.token).await?;
+    let data = get_personal_data(&pool, subscriber_id)
+        .await?
+        .ok_or(PrivacyError::UnknownToken)?;
+
+    record_audit_event(&*pool, AuditEvent {
+        details: Some(format!("subscriber: {}", subscriber_id)),
+        ..AuditEvent::new("subscriber_data_exported")
+    })
+    .await
+    .context("Failed to record the export.")?;
+
+    let disposition = format!(
+        "attachment; filename=\"my-data-{}.json\"",
+        data.exported_at.format("%Y-%m-%d")
+    );
+    Ok(([(CONTENT_DISPOSITION, disposition)], Json(data)).into_response())
+}
+
+/// Remove the subscriber and everything attached to them for good.
+/// Only an audit entry with their former id is kept.
+#[utoipa::path(
+    post,
+    path = "/privacy/erase",
+    tag = "privacy",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The data has been erased.", body = String, content_type = "text/html"),
+        (status = 401, description = "Unknown link.", body = String, content_type = "text/html"),
+        (status = 410, description = "The link has expired.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Erase personal data", skip(parameters, pool, templates))]
+pub async fn erase_personal_data(
+    Query(parameters): Query<Parameters>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+) -> Result<Html<String>, PrivacyError> {
+    let subscriber_id = check_privacy_token(&pool, &parameters.token).await?;
+    // The token goes with the rest: a second submission finds nothing.
+    if !erase_subscriber(&pool, subscriber_id).await? {
+        return Err(PrivacyError::UnknownToken);
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("title", "Your data");
+    context.insert("message", "Your data has been erased. You will not hear from us again.");
+    let html = templates
+        .render("message.html", &context)
+        .context("Failed to render the erasure confirmation.")?;
+
+    Ok(Html(html))
+}
+
+/// The subscriber the token was issued to, if it can still be used.
+async fn check_privacy_token(pool: &PgPool, privacy_token: &str) -> Result<Uuid, PrivacyError> {
+    let token = get_privacy_token(pool, privacy_token)
+        .await
+        .context("Failed to retrieve the privacy token.")?
+        .ok_or(PrivacyError::UnknownToken)?;
+
+    if token.expires_at < Utc::now() {
+        return Err(PrivacyError::ExpiredToken);
+    }
+
+    Ok(token.subscriber_id)
+}
+
+struct PrivacyToken {
+    subscriber_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_privacy_token(
+    pool: &PgPool,
+    privacy_token: &str,
+) -> Result<Option<PrivacyToken>, sqlx::Error> {
+    sqlx::query_as!(
+        PrivacyToken,
+        r#"
+            SELECT subscriber_id, expires_at FROM privacy_tokens
+                WHERE privacy_token = $1
+        "#,
+        privacy_token,
+    )
+    .fetch_optional(pool)
+    .await
+}
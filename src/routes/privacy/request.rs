@@ -0,0 +1,168 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use axum::extract::State;
+use axum::response::{Html, IntoResponse, Response};
+use axum::{Extension, Form};
+use chrono::Utc;
+use hyper::StatusCode;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::csrf::CsrfToken;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::generate_subscription_token;
+use crate::templates::{ErrorPage, Templates};
+use crate::utils::{e500, spawn_and_log_error};
+
+/// How long a privacy link stays valid.
+const PRIVACY_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    #[schema(format = "email")]
+    email: String,
+}
+
+/// A form to ask for a link to export or erase your data.
+#[utoipa::path(
+    get,
+    path = "/privacy",
+    tag = "privacy",
+    responses(
+        (status = 200, description = "The form.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Show the privacy page", skip(templates, csrf_token))]
+pub async fn privacy_page(
+    Extension(templates): Extension<Templates>,
+    csrf_token: CsrfToken,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let mut context = tera::Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    templates.render("privacy/request.html", &context).map(Html).map_err(e500)
+}
+
+/// Email a subscriber, whatever their status, a link to their data.
+/// The email goes out in the background and the response is the same,
+/// and as fast, whether or not the address is known, so that the form
+/// cannot be used to find out who is.
+#[utoipa::path(
+    post,
+    path = "/privacy/request",
+    tag = "privacy",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The link has been sent, if the address is known.",
+            body = String, content_type = "text/html"),
+        (status = 400, description = "The email address is invalid.", body = String,
+            content_type = "text/html"),
+        (status = 429, description = "Too many requests from this client or for this address.",
+            body = ErrorBody, headers(("Retry-After" = u64, description = "Seconds to wait."))),
+    )
+)]
+#[tracing::instrument(
+    name = "Request a privacy link",
+    skip(form, pool, email_client, templates, base_url)
+)]
+pub async fn request_privacy_link(
+    State(pool): State<Arc<PgPool>>,
+    Extension(email_client): Extension<Arc<EmailClient>>,
+    Extension(templates): Extension<Templates>,
+    Extension(base_url): Extension<String>,
+    Form(form): Form<FormData>,
+) -> Response {
+    let email = match SubscriberEmail::parse(form.email) {
+        Ok(email) => email,
+        Err(e) => return (StatusCode::BAD_REQUEST, ErrorPage::new("Your data", e.to_string())).into_response(),
+    };
+
+    let send_templates = templates.clone();
+    spawn_and_log_error(
+        async move { send_privacy_link(&pool, &email_client, &send_templates, &base_url, &email).await },
+        "Failed to send a privacy link.",
+    );
+
+    let mut context = tera::Context::new();
+    context.insert("title", "Your data");
+    context.insert(
+        "message",
+        "If we know this address, we have sent you a link \
+        to download or erase your data.",
+    );
+    templates
+        .render("message.html", &context)
+        .map(Html)
+        .map_err(e500)
+        .into_response()
+}
+
+async fn send_privacy_link(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Templates,
+    base_url: &str,
+    email: &SubscriberEmail,
+) -> Result<(), anyhow::Error> {
+    let Some(subscriber_id) = get_subscriber_id(pool, email).await? else {
+        return Ok(());
+    };
+    let privacy_token = generate_subscription_token();
+    store_privacy_token(pool, subscriber_id, &privacy_token).await?;
+
+    let privacy_link = format!("{}/privacy/manage?token={}", base_url, privacy_token);
+    let mut context = tera::Context::new();
+    context.insert("link", &privacy_link);
+    let html_body = templates
+        .render("emails/privacy_link.html", &context)
+        .context("Failed to render the HTML body of the privacy link email.")?;
+    let text_body = templates
+        .render("emails/privacy_link.txt", &context)
+        .context("Failed to render the text body of the privacy link email.")?;
+    email_client
+        .send_email(email, "Your data", &html_body, &text_body)
+        .await
+        .context("Failed to send a privacy link.")?;
+
+    Ok(())
+}
+
+/// Whatever their status: unsubscribed and pending subscribers have data too.
+#[tracing::instrument(name = "Get subscriber by email", skip(pool, email))]
+async fn get_subscriber_id(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE email = $1"#,
+        email.as_ref()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the subscriber.")?;
+
+    Ok(row.map(|r| r.id))
+}
+
+#[tracing::instrument(name = "Store privacy token", skip(pool, privacy_token))]
+async fn store_privacy_token(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    privacy_token: &str,
+) -> Result<(), anyhow::Error> {
+    let expires_at = Utc::now() + chrono::Duration::from_std(PRIVACY_TOKEN_TTL)
+        .expect("The token TTL is out of range.");
+    sqlx::query!(
+        r#"INSERT INTO privacy_tokens (privacy_token, subscriber_id, expires_at)
+            VALUES ($1, $2, $3)"#,
+        privacy_token,
+        subscriber_id,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a privacy token.")?;
+
+    Ok(())
+}
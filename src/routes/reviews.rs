@@ -1 +1,7 @@
-pub async fn reviews() {}
\ No newline at end of file
+mod get;
+mod request;
+mod submit;
+
+pub use get::{reviews, __path_reviews};
+pub use request::{request_review_link, __path_request_review_link};
+pub use submit::{review_form, submit_review, __path_review_form, __path_submit_review};
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::State;
+use axum::response::Html;
+use axum::Extension;
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use sqlx::PgPool;
+use crate::csrf::CsrfToken;
+use crate::domain::ReviewRating;
+use crate::templates::Templates;
+use crate::utils::e500;
+
+/// Approved reviews, newest first, and a form to ask for a review link.
+#[utoipa::path(
+    get,
+    path = "/reviews",
+    tag = "reviews",
+    responses(
+        (status = 200, description = "The reviews.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Show approved reviews", skip(pool, templates, csrf_token))]
+pub async fn reviews(
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    csrf_token: CsrfToken,
+) -> Result<Html<String>, (StatusCode, &'static str)> {
+    let reviews = get_approved_reviews(&pool).await.map_err(e500)?;
+
+    let mut context = tera::Context::new();
+    if !reviews.is_empty() {
+        let average = reviews.iter().map(|r| r.rating as f64).sum::<f64>() / reviews.len() as f64;
+        context.insert("average_rating", &format!("{:.1}", average));
+    }
+    let reviews: Vec<_> = reviews
+        .iter()
+        .map(|review| serde_json::json!({
+            "stars": stars(review.rating),
+            "name": review.name,
+            "content": review.content,
+            "submitted_at": review.submitted_at.format("%Y-%m-%d").to_string(),
+        }))
+        .collect();
+    context.insert("reviews", &reviews);
+    context.insert("max_rating", &ReviewRating::MAX);
+    context.insert("csrf_token", csrf_token.as_str());
+    templates.render("reviews/list.html", &context).map(Html).map_err(e500)
+}
+
+fn stars(rating: i16) -> String {
+    (1..=ReviewRating::MAX)
+        .map(|i| if i <= rating { '★' } else { '☆' })
+        .collect()
+}
+
+struct ApprovedReview {
+    name: String,
+    rating: i16,
+    content: String,
+    submitted_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_approved_reviews(pool: &PgPool) -> Result<Vec<ApprovedReview>, anyhow::Error> {
+    let reviews = sqlx::query_as!(
+        ApprovedReview,
+        r#"
+            SELECT subscriptions.name, reviews.rating, reviews.content, reviews.submitted_at
+                FROM reviews
+                JOIN subscriptions ON subscriptions.id = reviews.subscriber_id
+                WHERE reviews.status = 'approved'
+                ORDER BY reviews.submitted_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the approved reviews.")?;
+
+    Ok(reviews)
+}
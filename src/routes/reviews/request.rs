@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use axum::extract::State;
+use axum::response::{Html, IntoResponse, Response};
+use axum::{Extension, Form};
+use chrono::Utc;
+use hyper::StatusCode;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::generate_subscription_token;
+use crate::templates::{ErrorPage, Templates};
+use crate::utils::{e500, spawn_and_log_error};
+
+/// How long a review link stays valid.
+const REVIEW_TOKEN_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    #[schema(format = "email")]
+    email: String,
+}
+
+/// Email a link to the review form to a confirmed subscriber.
+/// The email goes out in the background and the response is the same,
+/// and as fast, whether or not the address is subscribed, so that the
+/// form cannot be used to find out who is.
+#[utoipa::path(
+    post,
+    path = "/reviews/request",
+    tag = "reviews",
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The link has been sent, if the address is subscribed.",
+            body = String, content_type = "text/html"),
+        (status = 400, description = "The email address is invalid.", body = String,
+            content_type = "text/html"),
+        (status = 429, description = "Too many requests from this client or for this address.",
+            body = ErrorBody, headers(("Retry-After" = u64, description = "Seconds to wait."))),
+    )
+)]
+#[tracing::instrument(
+    name = "Request a review link",
+    skip(form, pool, email_client, templates, base_url)
+)]
+pub async fn request_review_link(
+    State(pool): State<Arc<PgPool>>,
+    Extension(email_client): Extension<Arc<EmailClient>>,
+    Extension(templates): Extension<Templates>,
+    Extension(base_url): Extension<String>,
+    Form(form): Form<FormData>,
+) -> Response {
+    let email = match SubscriberEmail::parse(form.email) {
+        Ok(email) => email,
+        Err(e) => return (StatusCode::BAD_REQUEST, ErrorPage::new("Reviews", e.to_string())).into_response(),
+    };
+
+    let send_templates = templates.clone();
+    spawn_and_log_error(
+        async move { send_review_link(&pool, &email_client, &send_templates, &base_url, &email).await },
+        "Failed to send a review link.",
+    );
+
+    let mut context = tera::Context::new();
+    context.insert("title", "Reviews");
+    context.insert(
+        "message",
+        "If this address is subscribed to our newsletter, \
+        we have sent you a link to write your review.",
+    );
+    templates
+        .render("message.html", &context)
+        .map(Html)
+        .map_err(e500)
+        .into_response()
+}
+
+async fn send_review_link(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Templates,
+    base_url: &str,
+    email: &SubscriberEmail,
+) -> Result<(), anyhow::Error> {
+    let Some(subscriber_id) = get_confirmed_subscriber_id(pool, email).await? else {
+        return Ok(());
+    };
+    let review_token = generate_subscription_token();
+    store_review_token(pool, subscriber_id, &review_token).await?;
+
+    let review_link = format!("{}/reviews/new?token={}", base_url, review_token);
+    let mut context = tera::Context::new();
+    context.insert("link", &review_link);
+    let html_body = templates
+        .render("emails/review_link.html", &context)
+        .context("Failed to render the HTML body of the review link email.")?;
+    let text_body = templates
+        .render("emails/review_link.txt", &context)
+        .context("Failed to render the text body of the review link email.")?;
+    email_client
+        .send_email(email, "Tell us what you think", &html_body, &text_body)
+        .await
+        .context("Failed to send a review link.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get confirmed subscriber by email", skip(pool, email))]
+async fn get_confirmed_subscriber_id(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+            SELECT id FROM subscriptions
+                WHERE email = $1 AND status = 'confirmed'
+        "#,
+        email.as_ref()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the subscriber.")?;
+
+    Ok(row.map(|r| r.id))
+}
+
+#[tracing::instrument(name = "Store review token", skip(pool, review_token))]
+async fn store_review_token(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    review_token: &str,
+) -> Result<(), anyhow::Error> {
+    let expires_at = Utc::now() + chrono::Duration::from_std(REVIEW_TOKEN_TTL)
+        .expect("The token TTL is out of range.");
+    sqlx::query!(
+        r#"INSERT INTO review_tokens (review_token, subscriber_id, expires_at)
+            VALUES ($1, $2, $3)"#,
+        review_token,
+        subscriber_id,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a review token.")?;
+
+    Ok(())
+}
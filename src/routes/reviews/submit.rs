@@ -0,0 +1,259 @@
+use std::sync::Arc;
+use anyhow::Context;
+use axum::extract::{Query, State};
+use axum::response::{Html, IntoResponse, Response};
+use axum::{Extension, Form};
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::csrf::CsrfToken;
+use crate::domain::{NewReview, ReviewContent, ReviewRating};
+use crate::routes::error_chain_fmt;
+use crate::templates::{ErrorPage, Templates};
+
+#[derive(thiserror::Error)]
+pub enum ReviewError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("This review link is not valid.")]
+    UnknownToken,
+    #[error("This review link has already been used.")]
+    UsedToken,
+    #[error("This review link has expired. Please ask for a new one.")]
+    ExpiredToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl std::fmt::Debug for ReviewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for ReviewError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::ValidationError(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownToken => StatusCode::UNAUTHORIZED,
+            Self::UsedToken => StatusCode::BAD_REQUEST,
+            Self::ExpiredToken => StatusCode::GONE,
+            Self::UnexpectedError(e) => {
+                tracing::error!("\nServer error: {:?}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                    .into_response();
+            }
+        };
+        let page = ErrorPage::new("Reviews", self.to_string())
+            .back_to("/reviews", "Back to the reviews");
+        (status, page).into_response()
+    }
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    /// From the emailed review link.
+    token: String,
+}
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct FormData {
+    #[schema(minimum = 1, maximum = 5)]
+    rating: i16,
+    content: String,
+}
+
+impl TryFrom<FormData> for NewReview {
+    type Error = String;
+
+    fn try_from(value: FormData) -> Result<Self, Self::Error> {
+        let rating = ReviewRating::parse(value.rating)?;
+        let content = ReviewContent::parse(value.content)?;
+
+        Ok(Self { rating, content })
+    }
+}
+
+/// The form to write a review, from the emailed link.
+#[utoipa::path(
+    get,
+    path = "/reviews/new",
+    tag = "reviews",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The form.", body = String, content_type = "text/html"),
+        (status = 400, description = "The link has already been used.", body = String,
+            content_type = "text/html"),
+        (status = 401, description = "Unknown link.", body = String, content_type = "text/html"),
+        (status = 410, description = "The link has expired.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Show the review form", skip(parameters, pool, templates, csrf_token))]
+pub async fn review_form(
+    Query(parameters): Query<Parameters>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    csrf_token: CsrfToken,
+) -> Result<Html<String>, ReviewError> {
+    check_review_token(&pool, &parameters.token).await?;
+
+    let action = format!("/reviews/new?token={}", urlencoding::encode(&parameters.token));
+    let ratings: Vec<_> = (1..=ReviewRating::MAX).rev().collect();
+    let mut context = tera::Context::new();
+    context.insert("action", &action);
+    context.insert("ratings", &ratings);
+    context.insert("csrf_token", csrf_token.as_str());
+    let html = templates
+        .render("reviews/form.html", &context)
+        .context("Failed to render the review form.")?;
+
+    Ok(Html(html))
+}
+
+/// Reviews are held for moderation before they are shown publicly.
+#[utoipa::path(
+    post,
+    path = "/reviews/new",
+    tag = "reviews",
+    params(Parameters),
+    request_body(content = inline(FormData), content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The review has been received.", body = String,
+            content_type = "text/html"),
+        (status = 400, description = "The review is invalid, or the link has already been used.",
+            body = String, content_type = "text/html"),
+        (status = 401, description = "Unknown link.", body = String, content_type = "text/html"),
+        (status = 410, description = "The link has expired.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(name = "Submit a review", skip(parameters, pool, templates, form))]
+pub async fn submit_review(
+    Query(parameters): Query<Parameters>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+    Form(form): Form<FormData>,
+) -> Result<Html<String>, ReviewError> {
+    let subscriber_id = check_review_token(&pool, &parameters.token).await?;
+    let new_review: NewReview = form.try_into().map_err(ReviewError::ValidationError)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    // Two concurrent submissions with the same link: only one of them gets to use it.
+    if !mark_review_token_as_used(&mut transaction, &parameters.token)
+        .await
+        .context("Failed to invalidate the review token.")?
+    {
+        return Err(ReviewError::UsedToken);
+    }
+    insert_review(&mut transaction, subscriber_id, &new_review)
+        .await
+        .context("Failed to store the review.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a review.")?;
+
+    let mut context = tera::Context::new();
+    context.insert("title", "Reviews");
+    context.insert("message", "Thank you! Your review will be published once it has been approved.");
+    let html = templates
+        .render("message.html", &context)
+        .context("Failed to render the review confirmation.")?;
+
+    Ok(Html(html))
+}
+
+/// The subscriber the token was issued to, if it can still be used.
+async fn check_review_token(pool: &PgPool, review_token: &str) -> Result<Uuid, ReviewError> {
+    let token = get_review_token(pool, review_token)
+        .await
+        .context("Failed to retrieve the review token.")?
+        .ok_or(ReviewError::UnknownToken)?;
+
+    if token.used_at.is_some() {
+        return Err(ReviewError::UsedToken);
+    }
+    if token.expires_at < Utc::now() {
+        return Err(ReviewError::ExpiredToken);
+    }
+    // They might have unsubscribed since they asked for the link.
+    if token.status != "confirmed" {
+        return Err(ReviewError::UnknownToken);
+    }
+
+    Ok(token.subscriber_id)
+}
+
+struct ReviewToken {
+    subscriber_id: Uuid,
+    status: String,
+    expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_review_token(
+    pool: &PgPool,
+    review_token: &str,
+) -> Result<Option<ReviewToken>, sqlx::Error> {
+    sqlx::query_as!(
+        ReviewToken,
+        r#"
+            SELECT review_tokens.subscriber_id, subscriptions.status,
+                    review_tokens.expires_at, review_tokens.used_at
+                FROM review_tokens
+                JOIN subscriptions ON subscriptions.id = review_tokens.subscriber_id
+                WHERE review_token = $1
+        "#,
+        review_token,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(skip_all)]
+async fn mark_review_token_as_used(
+    transaction: &mut Transaction<'_, Postgres>,
+    review_token: &str,
+) -> Result<bool, sqlx::Error> {
+    let n_updated = sqlx::query!(
+        r#"
+            UPDATE review_tokens SET used_at = now()
+                WHERE review_token = $1 AND used_at IS NULL
+        "#,
+        review_token,
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+
+    Ok(n_updated > 0)
+}
+
+#[tracing::instrument(skip(transaction, new_review))]
+async fn insert_review(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_review: &NewReview,
+) -> Result<Uuid, sqlx::Error> {
+    let review_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+            INSERT INTO reviews (review_id, subscriber_id, rating, content, status, submitted_at)
+                VALUES ($1, $2, $3, $4, 'pending', now())
+        "#,
+        review_id,
+        subscriber_id,
+        new_review.rating.value(),
+        new_review.content.as_ref(),
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(review_id)
+}
@@ -1,7 +1,8 @@
 use anyhow::Context;
 use axum::Extension;
 use axum_macros::debug_handler;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use axum::{
     http::StatusCode,
     Form,
@@ -14,18 +15,60 @@ use sqlx::{PgPool, Postgres, Transaction};
 use std::sync::Arc;
 use crate::domain::{NewSubscriber, SubscriberName, SubscriberEmail};
 use crate::email_client::EmailClient;
+use crate::metrics::SUBSCRIPTIONS_CREATED;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-// Return on better PC
-// use tera::{Tera, Context as TeraContext};
-// use lazy_static::lazy_static;
+use std::time::Duration;
+use crate::templates::Templates;
+use crate::utils::JsonOrForm;
 
-#[derive(Deserialize)]
+/// How long a confirmation link stays valid.
+pub const SUBSCRIPTION_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+// How long expired or used tokens are kept before being deleted.
+const STALE_TOKEN_GRACE_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = SubscriptionRequest)]
 pub struct FormData {
+    #[schema(format = "email", example = "ursula_le_guin@gmail.com")]
     email: String,
+    /// At most 256 characters, none of them / ( ) " < > \ { }.
+    #[schema(example = "Ursula Le Guin")]
     name: String,
 }
 
+/// A field that failed validation, as reported by the API.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FieldError {
+    /// `name` or `email`.
+    #[schema(value_type = String, example = "email")]
+    pub field: &'static str,
+    /// Stable, unlike the message: `name_empty`, `name_too_long`,
+    /// `name_forbidden_characters` or `email_invalid`.
+    #[schema(value_type = String, example = "email_invalid")]
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FormData {
+    /// Check every field, instead of stopping at the first invalid one.
+    pub fn validate(self) -> Result<NewSubscriber, Vec<FieldError>> {
+        let name = SubscriberName::parse(self.name);
+        let email = SubscriberEmail::parse(self.email);
+        let mut errors = Vec::new();
+        if let Err(e) = &name {
+            errors.push(FieldError { field: "name", code: e.code(), message: e.to_string() });
+        }
+        if let Err(e) = &email {
+            errors.push(FieldError { field: "email", code: e.code(), message: e.to_string() });
+        }
+        match (name, email) {
+            (Ok(name), Ok(email)) => Ok(NewSubscriber { email, name }),
+            _ => Err(errors),
+        }
+    }
+}
+
 impl TryFrom<FormData> for NewSubscriber {
     type Error = String;
     
@@ -42,6 +85,8 @@ impl TryFrom<FormData> for NewSubscriber {
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("Some fields are invalid.")]
+    InvalidFields(Vec<FieldError>),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error)
 }
@@ -54,31 +99,49 @@ impl std::fmt::Debug for SubscribeError {
 
 impl IntoResponse for SubscribeError {
     fn into_response(self) -> axum::response::Response {
-        let (status, error_message) = match self {
+        let (status, body) = match self {
             // TODO: make tracing in the middleware (how?)
             Self::ValidationError(e) => {
                 tracing::error!("\nParsing error: {}", e);
-                (StatusCode::BAD_REQUEST, e)
+                (StatusCode::BAD_REQUEST, serde_json::json!({ "error": e }))
+            },
+            Self::InvalidFields(fields) => {
+                tracing::error!("\nParsing error: {:?}", fields);
+                (
+                    StatusCode::BAD_REQUEST,
+                    serde_json::json!({ "error": "Some fields are invalid.", "fields": fields }),
+                )
             },
             Self::UnexpectedError(e) => {
                 tracing::error!("\nServer error: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.".to_owned())
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    serde_json::json!({ "error": "Unexpected internal server error." }),
+                )
             } 
         };
-            
-
-        let body = Json(serde_json::json!({
-            "error": error_message
-        }));
 
-        (status, body).into_response()
+        (status, Json(body)).into_response()
     }
 }
 
-
+/// Subscribe from the form of the home page.
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(content = SubscriptionRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A confirmation email has been sent."),
+        (status = 400, description = "A field is invalid.", body = ErrorBody),
+        (status = 422, description = "A field is missing."),
+        (status = 429, description = "Too many requests from this client or for this address.",
+            body = ErrorBody, headers(("Retry-After" = u64, description = "Seconds to wait."))),
+    )
+)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(form, pool, email_client, templates, base_url),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -88,11 +151,65 @@ impl IntoResponse for SubscribeError {
 pub async fn subscribe(
     State(pool): State<Arc<PgPool>>,
     Extension(email_client): Extension<Arc<EmailClient>>,
+    Extension(templates): Extension<Templates>,
     Extension(base_url): Extension<String>,
     Form(form): Form<FormData>,
 ) -> Result<impl IntoResponse, SubscribeError> {
     let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    register_subscriber(&pool, &email_client, &templates, &base_url, new_subscriber).await?;
+
+    Ok(StatusCode::OK)
+}
 
+/// Subscribe, with a JSON or form payload.
+///
+/// Every invalid field is reported with a stable error code.
+/// Shares its rate limits with `/subscriptions`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscriptions",
+    tag = "subscriptions",
+    request_body(content = SubscriptionRequest, content_type = "application/json"),
+    responses(
+        (status = 200, description = "A confirmation email has been sent.", body = SubscriptionCreated),
+        (status = 400, description = "Some fields are invalid, or the body is malformed.",
+            body = ValidationErrors),
+        (status = 415, description = "The body is neither JSON nor a form.", body = ErrorBody),
+        (status = 422, description = "A field is missing.", body = ErrorBody),
+        (status = 429, description = "Too many requests from this client or for this address.",
+            body = ErrorBody, headers(("Retry-After" = u64, description = "Seconds to wait."))),
+    )
+)]
+#[tracing::instrument(
+    name = "Adding a new subscriber through the API",
+    skip(payload, pool, email_client, templates, base_url),
+    fields(
+        subscriber_email = %payload.email,
+        subscriber_name = %payload.name
+    )
+)]
+pub async fn subscribe_api(
+    State(pool): State<Arc<PgPool>>,
+    Extension(email_client): Extension<Arc<EmailClient>>,
+    Extension(templates): Extension<Templates>,
+    Extension(base_url): Extension<String>,
+    JsonOrForm(payload): JsonOrForm<FormData>,
+) -> Result<impl IntoResponse, SubscribeError> {
+    let new_subscriber = payload.validate().map_err(SubscribeError::InvalidFields)?;
+    register_subscriber(&pool, &email_client, &templates, &base_url, new_subscriber).await?;
+
+    Ok(Json(serde_json::json!({ "status": "pending_confirmation" })))
+}
+
+/// Store the subscriber (or give somebody who unsubscribed a fresh start)
+/// and send them a new confirmation link.
+pub async fn register_subscriber(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Templates,
+    base_url: &str,
+    new_subscriber: NewSubscriber,
+) -> Result<(), anyhow::Error> {
     let mut transaction = pool
         .begin()
         .await
@@ -103,43 +220,51 @@ pub async fn subscribe(
         .context("Failed to check if subscriber is already in the database.")?;
 
     let subscriber_id = match check_subscriber {
-        Some(id) => id,
+        Some(id) => {
+            // Somebody who unsubscribed has to confirm their address again.
+            reset_unsubscribed_subscriber(&mut transaction, id)
+                .await
+                .context("Failed to reset the status of an unsubscribed subscriber.")?;
+            id
+        },
         None => insert_subscriber(&mut transaction, &new_subscriber)
             .await
             .context("Failed to insert new subscriber in the database.")?,
         };
 
-    let subscription_token = match check_subscriber {
-        Some(id) => retrieve_token_from_database(id, &mut transaction)
+    // Every (re-)submission gets a brand new token: links sent out earlier
+    // stop working as soon as a newer one is issued.
+    if check_subscriber.is_some() {
+        delete_subscriber_tokens(&mut transaction, subscriber_id)
             .await
-            .context("Failed to retrieve subscriber token from the database.")?,
-        None => {
-                let subscription_token = generate_subscription_token();
-                store_token(&mut transaction, subscriber_id, &subscription_token)
-                .await
-                    .context("Failed to store the confirmation token for a new subscriber.")?;
-                subscription_token
-        }
-    };
+            .context("Failed to invalidate the previous confirmation tokens.")?;
+    }
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token for a subscriber.")?;
 
     transaction.commit()
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
+    if check_subscriber.is_none() {
+        SUBSCRIPTIONS_CREATED.inc();
+    }
 
     send_confirmation_email(
-        &email_client,
+        email_client,
+        templates,
         new_subscriber,
-        &base_url,
+        base_url,
         &subscription_token,
     )
     .await
     .context("Failed to send a confirmation email.")?;
 
-
-    Ok(StatusCode::OK)
+    Ok(())
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(||  rng.sample(Alphanumeric))
         .map(char::from)
@@ -148,24 +273,47 @@ fn generate_subscription_token() -> String {
 }
 
 #[tracing::instrument(
-    name = "Retrieve existing subscriber's token",
+    name = "Invalidate subscriber's previous tokens",
     skip(transaction, id)
 )]
-pub async fn retrieve_token_from_database(
-    id: Uuid,
+pub async fn delete_subscriber_tokens(
     transaction: &mut Transaction<'_, Postgres>,
-) -> Result<String, sqlx::Error> {
-    let token = sqlx::query!(
+    id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
         r#"
-            SELECT subscription_token FROM subscription_tokens
+            DELETE FROM subscription_tokens
                 WHERE subscriber_id = $1
         "#,
         id
     )
-    .fetch_one(&mut **transaction)
+    .execute(&mut **transaction)
     .await?;
 
-    Ok(token.subscription_token)
+    Ok(())
+}
+
+/// Remove tokens that nobody can use anymore.
+/// Expired and used tokens are kept around for a while, so that
+/// `confirm` can still tell their owners what went wrong.
+#[tracing::instrument(
+    name = "Delete stale subscription tokens",
+    skip(pool)
+)]
+pub async fn delete_stale_subscription_tokens(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let n_deleted = sqlx::query!(
+        r#"
+            DELETE FROM subscription_tokens
+                WHERE expires_at < now() - $1 * interval '1 second'
+                    OR used_at < now() - $1 * interval '1 second'
+        "#,
+        STALE_TOKEN_GRACE_PERIOD.as_secs() as f64
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(n_deleted)
 }
 
 #[tracing::instrument(
@@ -189,33 +337,51 @@ pub async fn subscriber_exists(
     Ok(existing.map(|r| r.id))
 }
 
+#[tracing::instrument(
+    name = "Reset the status of an unsubscribed subscriber",
+    skip(transaction, id)
+)]
+pub async fn reset_unsubscribed_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            UPDATE subscriptions SET status = 'pending_confirmation'
+                WHERE id = $1 AND status = 'unsubscribed'
+        "#,
+        id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber)
+    skip(email_client, templates, new_subscriber)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
+    templates: &Templates,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url,
         subscription_token
     );
-    let plain_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
-    let html_body = format!(
-        "Welcome to our newsletter!<br />\
-        Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
-    // Return it after getting a better PC
-    // let html_body = make_template(&confirmation_link)
-    //     .expect("Failed to render template");
+    let mut context = tera::Context::new();
+    context.insert("link", &confirmation_link);
+    let html_body = templates
+        .render("emails/confirmation.html", &context)
+        .context("Failed to render the HTML body of the confirmation email.")?;
+    let plain_body = templates
+        .render("emails/confirmation.txt", &context)
+        .context("Failed to render the text body of the confirmation email.")?;
 
     email_client
         .send_email(
@@ -224,7 +390,9 @@ pub async fn send_confirmation_email(
             &html_body, 
             &plain_body, 
         )
-        .await
+        .await?;
+
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -239,13 +407,14 @@ pub async fn insert_subscriber(
     
     sqlx::query!(
         r#"
-            INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-                VALUES ($1, $2, $3, $4, 'pending_confirmation')
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+                VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        generate_subscription_token()
     )
     .execute(&mut **transaction)
     .await?;
@@ -262,11 +431,14 @@ pub async fn store_token(
     subscriber_id: Uuid,
     subscription_token: &str
 ) -> Result<(), StoreTokenError> {
+    let expires_at = Utc::now() + chrono::Duration::from_std(SUBSCRIPTION_TOKEN_TTL)
+        .expect("The token TTL is out of range.");
     sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-            VALUES ($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, expires_at)
+            VALUES ($1, $2, $3)"#,
         subscription_token,
-        subscriber_id
+        subscriber_id,
+        expires_at
     )
     .execute(&mut **transaction)
     .await
@@ -304,33 +476,12 @@ impl IntoResponse for StoreTokenError {
     fn into_response(self) -> axum::response::Response {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!(
-                "A database error was encountered while \
-                trying to store a subscription token"
-            )
+            "A database error was encountered while \
+            trying to store a subscription token"
         ).into_response()
     }
 }
 
-// Return it after getting a better PC
-// lazy_static!{
-//     pub static ref TEMPLATES: Tera = {
-//         let tera = Tera::new("templates/**/*")
-//             .expect("Failed to parse templates");
-//         tera
-//     };
-// }
-
-// fn make_template(confirmation_link: &str) -> Result<String, tera::Error> {
-//     let tera = &TEMPLATES;
-//     let mut context = TeraContext::new();
-//     context.insert("title", "Welcome to our newsletter!");
-//     context.insert("link", confirmation_link);
-
-//     let rendered = tera.render("confirmation_email.html", &context)?;
-//     Ok(rendered)
-// }
-
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
     f: &mut std::fmt::Formatter<'_>,
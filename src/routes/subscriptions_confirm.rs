@@ -1,13 +1,22 @@
 use std::sync::Arc;
 use axum::{extract::{Query, State}, Json, response::IntoResponse};
 use hyper::StatusCode;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::IntoParams;
 use uuid::Uuid;
 use anyhow::Context;
+use crate::metrics::SUBSCRIPTIONS_CONFIRMED;
 use crate::routes::error_chain_fmt;
 
 #[derive(thiserror::Error)]
 pub enum ConfirmError {
+    #[error("This confirmation link is not valid.")]
+    UnknownToken,
+    #[error("This confirmation link has already been used.")]
+    UsedToken,
+    #[error("This confirmation link has expired. Please subscribe again to receive a new one.")]
+    ExpiredToken,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error)
 }
@@ -21,6 +30,9 @@ impl std::fmt::Debug for ConfirmError {
 impl IntoResponse for ConfirmError {
     fn into_response(self) -> axum::response::Response {
         let (status, error_message) = match self {
+            Self::UnknownToken => (StatusCode::UNAUTHORIZED, self.to_string()),
+            Self::UsedToken => (StatusCode::BAD_REQUEST, self.to_string()),
+            Self::ExpiredToken => (StatusCode::GONE, self.to_string()),
             Self::UnexpectedError(e) => {
                 tracing::error!("\nServer error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.".to_owned())
@@ -36,11 +48,26 @@ impl IntoResponse for ConfirmError {
     }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Parameters {
+    /// From the confirmation email.
     subscription_token: String,
 }
 
+/// Confirm a subscription, from the link in the confirmation email.
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The subscription is confirmed."),
+        (status = 400, description = "The link has already been used.", body = ErrorBody),
+        (status = 401, description = "Unknown link.", body = ErrorBody),
+        (status = 410, description = "The link has expired.", body = ErrorBody),
+    )
+)]
 #[tracing::instrument(
     name = "Confirm a pending subscriber",
     skip(parameters, pool)
@@ -50,28 +77,46 @@ pub async fn confirm(
     State(pool): State<Arc<PgPool>>,
 ) -> Result<impl IntoResponse, ConfirmError> {
     let subscription_token = parse_subscription_token(&parameters.subscription_token);
-    let id = get_subscriber_id_from_token(&pool, &subscription_token)
+    let token = get_token(&pool, &subscription_token)
+        .await
+        .context("Failed to get subscription token from database.")?
+        .ok_or(ConfirmError::UnknownToken)?;
+
+    if token.used_at.is_some() {
+        return Err(ConfirmError::UsedToken);
+    }
+    if token.expires_at < Utc::now() {
+        return Err(ConfirmError::ExpiredToken);
+    }
+
+    let pending = subscriber_is_pending(&pool, token.subscriber_id)
         .await
-        .context("Failed to get subscriber id from database.")?;
-
-    match id {
-        // Non-existing token!
-        None => Ok(StatusCode::UNAUTHORIZED),
-        Some(subscriber_id) => {
-            let pending = subscriber_is_pending(&pool, subscriber_id)
-                .await
-                .context("Failed to check subscriber's status.")?;
-
-            if pending {
-                confirm_subscriber(&pool, subscriber_id)
-                    .await
-                    .context("Failed to change subscriber's status.")?;
-                Ok(StatusCode::OK)
-            } else {
-                Ok(StatusCode::BAD_REQUEST)
-            }
-        }
+        .context("Failed to check subscriber's status.")?;
+    if !pending {
+        return Err(ConfirmError::UsedToken);
     }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    // Two concurrent clicks on the same link: only one of them gets to use it.
+    let marked = mark_token_as_used(&mut transaction, &subscription_token)
+        .await
+        .context("Failed to invalidate the subscription token.")?;
+    if !marked {
+        return Err(ConfirmError::UsedToken);
+    }
+    confirm_subscriber(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to change subscriber's status.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+    SUBSCRIPTIONS_CONFIRMED.inc();
+
+    Ok(StatusCode::OK)
 }
 
 #[tracing::instrument(
@@ -101,10 +146,10 @@ pub async fn subscriber_is_pending(
 
 #[tracing::instrument(
     name = "Mark subscriber as confirmed",
-    skip(subscriber_id, pool)
+    skip(subscriber_id, transaction)
 )]
 pub async fn confirm_subscriber(
-    pool: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid
 ) -> Result<(), sqlx::Error> {    
     sqlx::query!(
@@ -114,29 +159,56 @@ pub async fn confirm_subscriber(
         "#,
         subscriber_id,
     )
-    .execute(pool)
+    .execute(&mut **transaction)
     .await?;
 
     Ok(())
 }
 
+pub struct SubscriptionToken {
+    pub subscriber_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
 #[tracing::instrument(
-    name = "Get subscriber_id from token",
+    name = "Get subscription token details",
     skip(pool, subscription_token)
 )]
-pub async fn get_subscriber_id_from_token(
+pub async fn get_token(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens
+) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriptionToken,
+        r#"SELECT subscriber_id, expires_at, used_at FROM subscription_tokens
             WHERE subscription_token = $1"#,
         subscription_token,
     )
     .fetch_optional(pool)
-    .await?;
+    .await
+}
+
+#[tracing::instrument(
+    name = "Mark subscription token as used",
+    skip(transaction, subscription_token)
+)]
+pub async fn mark_token_as_used(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+) -> Result<bool, sqlx::Error> {
+    let n_updated = sqlx::query!(
+        r#"
+        UPDATE subscription_tokens SET used_at = now()
+            WHERE subscription_token = $1 AND used_at IS NULL
+        "#,
+        subscription_token,
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
 
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(n_updated > 0)
 }
 
 fn parse_subscription_token(subscription_token: &str) -> String {
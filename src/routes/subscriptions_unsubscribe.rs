@@ -0,0 +1,176 @@
+use std::sync::Arc;
+use axum::{extract::{Query, State}, Extension, Json, response::{Html, IntoResponse, Response}};
+use hyper::StatusCode;
+use sqlx::PgPool;
+use anyhow::Context;
+use utoipa::IntoParams;
+use crate::routes::error_chain_fmt;
+use crate::templates::{ErrorPage, Templates};
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("This unsubscribe link is not valid.")]
+    UnknownToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for UnsubscribeError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::UnknownToken => {
+                (StatusCode::UNAUTHORIZED, ErrorPage::new("Unsubscribe", self.to_string())).into_response()
+            },
+            Self::UnexpectedError(e) => {
+                tracing::error!("\nServer error: {:?}", e);
+                let body = Json(serde_json::json!({
+                    "error": "Unexpected internal server error."
+                }));
+                (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+            }
+        }
+    }
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UnsubscribeParameters {
+    /// From the newsletter footer.
+    token: String,
+}
+
+/// Landing page of the link in the newsletter footer.
+///
+/// Following a link must not change anything (mail scanners follow them all),
+/// so we only ask for a confirmation here.
+#[utoipa::path(
+    get,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    params(UnsubscribeParameters),
+    responses(
+        (status = 200, description = "The confirmation form.", body = String, content_type = "text/html"),
+        (status = 401, description = "Unknown link.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(
+    name = "Show the unsubscribe confirmation page",
+    skip(parameters, pool, templates)
+)]
+pub async fn unsubscribe_form(
+    Query(parameters): Query<UnsubscribeParameters>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+) -> Result<Html<String>, UnsubscribeError> {
+    let status = get_subscriber_status_from_unsubscribe_token(&pool, &parameters.token)
+        .await
+        .context("Failed to retrieve the subscriber associated with the provided token.")?
+        .ok_or(UnsubscribeError::UnknownToken)?;
+
+    if status == "unsubscribed" {
+        return unsubscribe_page(&templates, "You are already unsubscribed.");
+    }
+
+    let action = format!(
+        "/subscriptions/unsubscribe?token={}",
+        urlencoding::encode(&parameters.token)
+    );
+    let mut context = tera::Context::new();
+    context.insert("action", &action);
+    let html = templates
+        .render("unsubscribe.html", &context)
+        .context("Failed to render the unsubscribe form.")?;
+
+    Ok(Html(html))
+}
+
+/// Unsubscribe, from the confirmation form or an email client.
+///
+/// Handles RFC 8058 one-click requests too (`List-Unsubscribe=One-Click`
+/// in the body, token in the query string). No CSRF token is needed.
+#[utoipa::path(
+    post,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    params(UnsubscribeParameters),
+    responses(
+        (status = 200, description = "Unsubscribed.", body = String, content_type = "text/html"),
+        (status = 401, description = "Unknown link.", body = String, content_type = "text/html"),
+    )
+)]
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(parameters, pool, templates)
+)]
+pub async fn unsubscribe(
+    Query(parameters): Query<UnsubscribeParameters>,
+    State(pool): State<Arc<PgPool>>,
+    Extension(templates): Extension<Templates>,
+) -> Result<Html<String>, UnsubscribeError> {
+    let unsubscribed = mark_subscriber_as_unsubscribed(&pool, &parameters.token)
+        .await
+        .context("Failed to change subscriber's status.")?;
+
+    if !unsubscribed {
+        return Err(UnsubscribeError::UnknownToken);
+    }
+    unsubscribe_page(&templates, "You have been unsubscribed. Sorry to see you go!")
+}
+
+#[tracing::instrument(
+    name = "Get subscriber status from unsubscribe token",
+    skip(pool, unsubscribe_token)
+)]
+pub async fn get_subscriber_status_from_unsubscribe_token(
+    pool: &PgPool,
+    unsubscribe_token: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT status FROM subscriptions
+            WHERE unsubscribe_token = $1"#,
+        unsubscribe_token,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|r| r.status))
+}
+
+#[tracing::instrument(
+    name = "Mark subscriber as unsubscribed",
+    skip(pool, unsubscribe_token)
+)]
+pub async fn mark_subscriber_as_unsubscribed(
+    pool: &PgPool,
+    unsubscribe_token: &str,
+) -> Result<bool, sqlx::Error> {
+    let n_updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+            WHERE unsubscribe_token = $1
+        "#,
+        unsubscribe_token,
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(n_updated > 0)
+}
+
+fn unsubscribe_page(templates: &Templates, message: &str) -> Result<Html<String>, UnsubscribeError> {
+    let mut context = tera::Context::new();
+    context.insert("title", "Unsubscribe");
+    context.insert("message", message);
+    let html = templates
+        .render("message.html", &context)
+        .context("Failed to render the unsubscribe page.")?;
+
+    Ok(Html(html))
+}
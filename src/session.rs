@@ -0,0 +1,7 @@
+mod middleware;
+mod store;
+mod typed_session;
+
+pub use middleware::{session_middleware, Session, SessionLayerState};
+pub use store::PgSessionStore;
+pub use typed_session::TypedSession;
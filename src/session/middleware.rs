@@ -0,0 +1,178 @@
+use std::sync::{Arc, Mutex};
+use axum::{
+    extract::State,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, Key, SameSite, SignedCookieJar};
+use hyper::{Request, StatusCode};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::{de::DeserializeOwned, Serialize};
+use super::store::{PgSessionStore, SessionState};
+
+const SESSION_COOKIE_NAME: &str = "id";
+
+#[derive(Clone)]
+pub struct SessionLayerState {
+    pub store: PgSessionStore,
+    pub key: Key,
+    pub secure_cookie: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SessionStatus {
+    Unchanged,
+    Changed,
+    Renewed,
+    Purged,
+}
+
+struct SessionInner {
+    state: SessionState,
+    status: SessionStatus,
+}
+
+/// Server-side session state attached to the current request.
+/// Changes are written back to the store once the handler has returned.
+#[derive(Clone)]
+pub struct Session(Arc<Mutex<SessionInner>>);
+
+impl Session {
+    fn new(state: SessionState) -> Self {
+        Self(Arc::new(Mutex::new(SessionInner {
+            state,
+            status: SessionStatus::Unchanged,
+        })))
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, serde_json::Error> {
+        let inner = self.0.lock().unwrap();
+        inner.state
+            .get(key)
+            .map(|value| serde_json::from_value(value.clone()))
+            .transpose()
+    }
+
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_value(value)?;
+        let mut inner = self.0.lock().unwrap();
+        inner.state.insert(key.to_owned(), value);
+        if inner.status == SessionStatus::Unchanged {
+            inner.status = SessionStatus::Changed;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut inner = self.0.lock().unwrap();
+        if inner.state.remove(key).is_some() && inner.status == SessionStatus::Unchanged {
+            inner.status = SessionStatus::Changed;
+        }
+    }
+
+    /// Keep the state, but move it under a new session key.
+    /// Call it whenever the privilege level changes, e.g. on login.
+    pub fn renew(&self) {
+        let mut inner = self.0.lock().unwrap();
+        if inner.status != SessionStatus::Purged {
+            inner.status = SessionStatus::Renewed;
+        }
+    }
+
+    /// Drop the state and remove the session from the store.
+    pub fn purge(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.state.clear();
+        inner.status = SessionStatus::Purged;
+    }
+}
+
+pub async fn session_middleware<B>(
+    State(config): State<SessionLayerState>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let jar = SignedCookieJar::from_headers(request.headers(), config.key.clone());
+    let session_key = jar
+        .get(SESSION_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+
+    let state = match &session_key {
+        Some(session_key) => match config.store.load(session_key).await {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::error!(error.cause_chain = ?e, "Failed to load the session state.");
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                    .into_response();
+            }
+        },
+        None => None,
+    };
+    // A cookie pointing to an expired (or deleted) session is treated
+    // as if there was no cookie at all.
+    let session_key = session_key.filter(|_| state.is_some());
+
+    let session = Session::new(state.unwrap_or_default());
+    request.extensions_mut().insert(session.clone());
+
+    let response = next.run(request).await;
+
+    let (state, status) = {
+        let inner = session.0.lock().unwrap();
+        (inner.state.clone(), inner.status)
+    };
+    let outcome = match (status, session_key) {
+        (SessionStatus::Unchanged, _) => Ok(jar),
+        (SessionStatus::Changed, Some(session_key)) => {
+            config.store.save(&session_key, &state).await.map(|_| jar)
+        },
+        (SessionStatus::Changed, None) | (SessionStatus::Renewed, None) => {
+            let session_key = generate_session_key();
+            config.store.save(&session_key, &state)
+                .await
+                .map(|_| jar.add(session_cookie(session_key, config.secure_cookie)))
+        },
+        (SessionStatus::Renewed, Some(old_session_key)) => {
+            let session_key = generate_session_key();
+            match config.store.delete(&old_session_key).await {
+                Ok(_) => config.store.save(&session_key, &state)
+                    .await
+                    .map(|_| jar.add(session_cookie(session_key, config.secure_cookie))),
+                Err(e) => Err(e),
+            }
+        },
+        (SessionStatus::Purged, Some(session_key)) => {
+            config.store.delete(&session_key)
+                .await
+                .map(|_| jar.remove(Cookie::named(SESSION_COOKIE_NAME)))
+        },
+        (SessionStatus::Purged, None) => Ok(jar),
+    };
+
+    match outcome {
+        Ok(jar) => (jar, response).into_response(),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to persist the session state.");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+                .into_response()
+        }
+    }
+}
+
+fn session_cookie(session_key: String, secure: bool) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE_NAME, session_key)
+        .path("/")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+fn generate_session_key() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(64)
+        .collect()
+}
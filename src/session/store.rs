@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use anyhow::Context;
+use sqlx::PgPool;
+
+pub type SessionState = HashMap<String, serde_json::Value>;
+
+// How long a session survives without being written to.
+const SESSION_TTL_SECONDS: f64 = 60.0 * 60.0 * 24.0;
+
+#[derive(Clone)]
+pub struct PgSessionStore {
+    pool: PgPool,
+}
+
+impl PgSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(name = "Load session state", skip_all)]
+    pub async fn load(&self, session_key: &str) -> Result<Option<SessionState>, anyhow::Error> {
+        let row = sqlx::query!(
+            r#"
+                SELECT state FROM sessions
+                    WHERE session_key = $1 AND expires_at > now()
+            "#,
+            session_key
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load session state.")?;
+
+        row.map(|r| serde_json::from_value(r.state))
+            .transpose()
+            .context("Failed to deserialize session state.")
+    }
+
+    #[tracing::instrument(name = "Save session state", skip_all)]
+    pub async fn save(&self, session_key: &str, state: &SessionState) -> Result<(), anyhow::Error> {
+        let state = serde_json::to_value(state)
+            .context("Failed to serialize session state.")?;
+        sqlx::query!(
+            r#"
+                INSERT INTO sessions (session_key, state, expires_at)
+                    VALUES ($1, $2, now() + $3 * interval '1 second')
+                ON CONFLICT (session_key) DO UPDATE
+                    SET state = EXCLUDED.state, expires_at = EXCLUDED.expires_at
+            "#,
+            session_key,
+            state,
+            SESSION_TTL_SECONDS,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to save session state.")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Delete session", skip_all)]
+    pub async fn delete(&self, session_key: &str) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            "DELETE FROM sessions WHERE session_key = $1",
+            session_key
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete session.")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Delete expired sessions", skip_all)]
+    pub async fn delete_expired(&self) -> Result<u64, anyhow::Error> {
+        let n_deleted = sqlx::query!("DELETE FROM sessions WHERE expires_at <= now()")
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete expired sessions.")?
+            .rows_affected();
+
+        Ok(n_deleted)
+    }
+}
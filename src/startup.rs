@@ -1,40 +1,56 @@
+use std::collections::HashMap;
 use std::{sync::Arc};
 
 use crate::{
-    routes::{home, blog, reviews, subscribe, health_check, confirm, publish_newsletter, login_form, login},
-    email_client::EmailClient
+    routes::{home, blog, blog_issue, reviews, subscribe, subscribe_api, health_check, liveness, readiness,
+        ReadinessChecks, confirm, publish_newsletter, login_form, login,
+        admin_dashboard, log_out, change_password_form, change_password, unsubscribe_form, unsubscribe,
+        delete_stale_subscription_tokens, request_review_link, review_form, submit_review,
+        moderation_queue, moderate_review, lockouts, unlock, users, add_user, update_user_role,
+        disable_user, enable_user, subscribers, subscriber_details, export_subscribers,
+        import_subscribers, MAX_IMPORT_SIZE, privacy_page, request_privacy_link, privacy_dashboard,
+        export_personal_data, erase_personal_data,
+        confirm_subscriber_manually, unsubscribe_subscriber_manually, delete_subscriber},
+    email_client::EmailClient,
+    authentication::{delete_stale_login_failures, CurrentUser, LoginThrottle},
+    session::{PgSessionStore, SessionLayerState, session_middleware},
+    flash_messages::flash_messages_middleware,
+    csrf::csrf_middleware,
+    metrics::{init_metrics, metrics, track_metrics},
+    templates::{render_error_pages, Templates},
+    api_docs::{openapi_json, swagger_ui_page},
 };
 use axum::{
-    routing::{get, post, IntoMakeService},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, DefaultBodyLimit},
+    routing::{get, post},
+    middleware::{from_extractor_with_state, from_fn, from_fn_with_state},
     Router, Extension,
 };
-use secrecy::Secret;
+use axum_extra::extract::cookie::Key;
+use secrecy::{Secret, ExposeSecret};
 use sqlx::PgPool;
-use std::net::TcpListener;
+use std::net::{SocketAddr, TcpListener};
 use crate::utils::handler_404;
+use crate::issue_delivery_worker::run_worker_until_stopped;
 use crate::telemetry::request_id;
 use tower_http::trace::TraceLayer;
 use tower_request_id::RequestIdLayer;
 use hyper::{Body, http::Request, server::conn::AddrIncoming};
-use crate::configuration::{Settings, DatabaseSettings};
+use crate::configuration::{Settings, DatabaseSettings, RouteRateLimits};
 use sqlx::postgres::PgPoolOptions;
 
+pub type Server = axum::Server<AddrIncoming, IntoMakeServiceWithConnectInfo<Router, SocketAddr>>;
 
-pub async fn build(configuration: Settings) -> axum::Server<AddrIncoming, IntoMakeService<Router>> {
+pub async fn build(configuration: Settings) -> Server {
     let connection_pool = get_connection_pool(&configuration.database);
     
-    let sender_email = configuration.email_client.sender()
-        .expect("Invalid sender email address.");
-    let base_url = reqwest::Url::parse(&configuration.email_client.base_url)
-        .expect("Failed to parse URL");
-    let timeout = configuration.email_client.timeout();
-
-    let email_client = EmailClient::new(
-        base_url,
-        sender_email,
-        configuration.email_client.authorization_token,
-        timeout
-    );
+    let readiness_checks = ReadinessChecks {
+        probe_email_backend: configuration.email_client.probe_on_readiness,
+    };
+    let email_client = configuration.email_client.client();
+    let login_throttle = configuration.login_throttle.throttle();
+    let templates = Templates::new(configuration.application.reload_templates)
+        .expect("Failed to load the templates.");
 
     let address = format!(
         "{}:{}",
@@ -44,12 +60,32 @@ pub async fn build(configuration: Settings) -> axum::Server<AddrIncoming, IntoMa
     let listener = TcpListener::bind(address)
         .expect("Failed to bind a port");
 
+    tokio::spawn(run_worker_until_stopped(
+        connection_pool.clone(),
+        Arc::new(email_client.clone()),
+        templates.clone(),
+        configuration.application.base_url.clone()
+    ));
+
+    let session_store = PgSessionStore::new(connection_pool.clone());
+    tokio::spawn(run_session_cleanup_until_stopped(session_store.clone()));
+    tokio::spawn(run_database_cleanup_until_stopped(
+        connection_pool.clone(),
+        login_throttle.failure_window,
+    ));
+
    run(
     connection_pool,
+        session_store,
         email_client,
+        templates,
         listener,
         configuration.application.base_url,
-        configuration.application.hmac_secret
+        configuration.application.hmac_secret,
+        readiness_checks,
+        login_throttle,
+        configuration.application.rate_limits,
+        configuration.application.swagger_ui,
     )
 }
 
@@ -62,26 +98,125 @@ pub fn get_connection_pool(
 }
 pub struct ApplicationBaseUrl(pub String);
 
+async fn run_session_cleanup_until_stopped(session_store: PgSessionStore) {
+    loop {
+        if let Err(e) = session_store.delete_expired().await {
+            tracing::error!(error.cause_chain = ?e, "Failed to delete expired sessions.");
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(60 * 60)).await;
+    }
+}
+
+async fn run_database_cleanup_until_stopped(pool: PgPool, failure_window: std::time::Duration) {
+    loop {
+        if let Err(e) = delete_stale_subscription_tokens(&pool).await {
+            tracing::error!(error.cause_chain = ?e, "Failed to delete stale subscription tokens.");
+        }
+        if let Err(e) = delete_stale_login_failures(&pool, failure_window).await {
+            tracing::error!(error.cause_chain = ?e, "Failed to delete stale failed login attempts.");
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(60 * 60)).await;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run(
     db_pool: PgPool,
+    session_store: PgSessionStore,
     email_client: EmailClient,
+    templates: Templates,
     listener: TcpListener,
     base_url: String,
     hmac_secret: Secret<String>,
-) -> axum::Server<AddrIncoming, IntoMakeService<Router>> {
+    readiness_checks: ReadinessChecks,
+    login_throttle: LoginThrottle,
+    rate_limits: HashMap<String, RouteRateLimits>,
+    swagger_ui: bool,
+) -> Server {
+    init_metrics();
     let db_pool = Arc::new(db_pool);
     let email_client = Arc::new(email_client);
+    let cookie_key = HmacSecret(hmac_secret).cookie_key();
+    let session_state = SessionLayerState {
+        store: session_store,
+        key: cookie_key.clone(),
+        secure_cookie: base_url.starts_with("https://"),
+    };
+
+    // Routes without configured limits are not limited.
+    let rate_limit = |route: &str| {
+        rate_limits.get(route).map(RouteRateLimits::layer).unwrap_or_default()
+    };
+
+    // Both subscription endpoints draw from the same buckets.
+    let subscriptions_rate_limit = rate_limit("/subscriptions");
+
+    let admin_routes = Router::new()
+            .route("/dashboard", get(admin_dashboard))
+            .route("/password", get(change_password_form).post(change_password))
+            .route("/logout", post(log_out))
+            .route("/reviews", get(moderation_queue))
+            .route("/reviews/:review_id/:decision", post(moderate_review))
+            .route("/lockouts", get(lockouts))
+            .route("/lockouts/unlock", post(unlock))
+            .route("/users", get(users).post(add_user))
+            .route("/users/:user_id/role", post(update_user_role))
+            .route("/users/:user_id/disable", post(disable_user))
+            .route("/users/:user_id/enable", post(enable_user))
+            .route("/subscribers", get(subscribers))
+            .route("/subscribers/export", get(export_subscribers))
+            .route(
+                "/subscribers/import",
+                post(import_subscribers).layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE)),
+            )
+            .route("/subscribers/:subscriber_id", get(subscriber_details))
+            .route("/subscribers/:subscriber_id/confirm", post(confirm_subscriber_manually))
+            .route("/subscribers/:subscriber_id/unsubscribe", post(unsubscribe_subscriber_manually))
+            .route("/subscribers/:subscriber_id/delete", post(delete_subscriber))
+            // Per-route permissions are checked by the `Authorized` extractor.
+            .route_layer(from_extractor_with_state::<CurrentUser, _>(Arc::clone(&db_pool)));
+
+    // Unsubscribe links carry their own secret token, and mail clients post
+    // to them (RFC 8058 one-click) without any of our cookies.
+    let csrf_exempt_routes = Router::new()
+            .route("/subscriptions/unsubscribe", get(unsubscribe_form).post(unsubscribe));
+
+    let mut api_docs_routes = Router::new()
+            .route("/api-docs/openapi.json", get(openapi_json));
+    if swagger_ui {
+        api_docs_routes = api_docs_routes.route("/api-docs", get(swagger_ui_page));
+    }
 
     let router = Router::new()
             .route("/", get(home))
             .route("/blog", get(blog))
+            .route("/blog/:issue_id", get(blog_issue))
             .route("/reviews", get(reviews))
+            .route("/reviews/request", post(request_review_link).layer(rate_limit("/reviews/request")))
+            .route("/reviews/new", get(review_form).post(submit_review))
+            .route("/privacy", get(privacy_page))
+            .route("/privacy/request", post(request_privacy_link).layer(rate_limit("/privacy/request")))
+            .route("/privacy/manage", get(privacy_dashboard))
+            .route("/privacy/export", get(export_personal_data))
+            .route("/privacy/erase", post(erase_personal_data))
             .route("/health_check", get(health_check))
-            .route("/subscriptions", post(subscribe))
+            .route("/health_check/live", get(liveness))
+            .route("/health_check/ready", get(readiness))
+            .route("/subscriptions", post(subscribe).layer(subscriptions_rate_limit.clone()))
+            .route("/api/v1/subscriptions", post(subscribe_api).layer(subscriptions_rate_limit))
             .route("/subscriptions/confirm", get(confirm))
             .route("/newsletters", post(publish_newsletter))
             .route("/login", get(login_form).post(login))
+            .route("/metrics", get(metrics))
+            .nest("/admin", admin_routes)
+            .layer(from_fn_with_state(cookie_key.clone(), csrf_middleware))
+            .merge(csrf_exempt_routes)
+            .merge(api_docs_routes)
             .fallback(handler_404)
+            .layer(from_fn_with_state(templates.clone(), render_error_pages))
+            .layer(from_fn(track_metrics))
+            .layer(from_fn_with_state(session_state, session_middleware))
+            .layer(from_fn_with_state(cookie_key, flash_messages_middleware))
             .layer(TraceLayer::new_for_http()
                 .make_span_with(|request: &Request<Body>| {
                     request_id(request)
@@ -89,14 +224,24 @@ fn run(
             )
             .layer(RequestIdLayer)
             .layer(Extension(Arc::clone(&email_client)))
+            .layer(Extension(templates))
             .layer(Extension(base_url.clone()))
-            .layer(Extension(HmacSecret(hmac_secret.clone())))
+            .layer(Extension(readiness_checks))
+            .layer(Extension(login_throttle))
             .with_state(Arc::clone(&db_pool));
 
     axum::Server::from_tcp(listener)
         .expect("Failed to bind a port.")
-        .serve(router.into_make_service()) 
+        // The client's address is needed to throttle failed logins.
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
 }
 
 #[derive(Clone)]
-pub struct HmacSecret(pub Secret<String>);
\ No newline at end of file
+pub struct HmacSecret(pub Secret<String>);
+
+impl HmacSecret {
+    /// The key used to sign (and verify) every cookie we hand out.
+    pub fn cookie_key(&self) -> Key {
+        Key::derive_from(self.0.expose_secret().as_bytes())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+mod csv_records;
+mod import;
+mod personal_data;
+mod store;
+
+pub use import::{ImportError, ImportReport, ImportStatus, RejectedRow, SubscriberImport};
+pub use personal_data::*;
+pub use store::*;
@@ -0,0 +1,162 @@
+use csv_core::{ReadRecordResult, Reader};
+
+/// Longer rows are not subscribers: most likely a quote was never closed.
+const MAX_RECORD_LENGTH: usize = 64 * 1024;
+
+/// Splits CSV fed in arbitrary chunks into records, keeping the partial
+/// record between chunks, so that the input never has to be held whole.
+pub struct CsvRecords {
+    reader: Reader,
+    output: Vec<u8>,
+    output_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+}
+
+pub type Record = Result<Vec<String>, CsvError>;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum CsvError {
+    #[error("A row is longer than {} bytes.", MAX_RECORD_LENGTH)]
+    RecordTooLong,
+    #[error("A row is not valid UTF-8.")]
+    InvalidUtf8,
+}
+
+impl CsvRecords {
+    pub fn new() -> Self {
+        Self {
+            reader: Reader::new(),
+            output: vec![0; 1024],
+            output_len: 0,
+            ends: vec![0; 16],
+            ends_len: 0,
+        }
+    }
+
+    /// The records completed by `chunk`. An empty chunk marks the end of
+    /// the input and flushes the last record.
+    /// Rows that are not valid UTF-8 are errors of their own; rows too long
+    /// to be buffered stop the parsing.
+    pub fn feed(&mut self, mut chunk: &[u8]) -> Result<Vec<Record>, CsvError> {
+        let mut records = Vec::new();
+        loop {
+            let (result, n_in, n_out, n_ends) = self.reader.read_record(
+                chunk,
+                &mut self.output[self.output_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            chunk = &chunk[n_in..];
+            // The reader keeps track of the partial record itself: field ends
+            // already count from the start of the record, not of our slice.
+            self.output_len += n_out;
+            self.ends_len += n_ends;
+
+            match result {
+                ReadRecordResult::InputEmpty | ReadRecordResult::End => return Ok(records),
+                ReadRecordResult::OutputFull => {
+                    if self.output.len() >= MAX_RECORD_LENGTH {
+                        return Err(CsvError::RecordTooLong);
+                    }
+                    self.output.resize(self.output.len() * 2, 0);
+                },
+                ReadRecordResult::OutputEndsFull => {
+                    self.ends.resize(self.ends.len() * 2, 0);
+                },
+                ReadRecordResult::Record => records.push(self.take_record()),
+            }
+        }
+    }
+
+    fn take_record(&mut self) -> Record {
+        let mut start = 0;
+        let fields = self.ends[..self.ends_len]
+            .iter()
+            .map(|&end| {
+                let field = std::str::from_utf8(&self.output[start..end]).map(str::to_owned);
+                start = end;
+                field.map_err(|_| CsvError::InvalidUtf8)
+            })
+            .collect();
+        self.output_len = 0;
+        self.ends_len = 0;
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use super::{CsvError, CsvRecords};
+
+    fn parse_in_chunks(input: &str, chunk_size: usize) -> Vec<Vec<String>> {
+        let mut records = CsvRecords::new();
+        let mut parsed = Vec::new();
+        for chunk in input.as_bytes().chunks(chunk_size) {
+            parsed.extend(assert_ok!(records.feed(chunk)));
+        }
+        parsed.extend(assert_ok!(records.feed(&[])));
+        parsed.into_iter().map(|record| assert_ok!(record)).collect()
+    }
+
+    #[test]
+    fn records_are_split_into_fields() {
+        let parsed = parse_in_chunks("email,name\r\nursula@example.com,ursula\r\n", 1024);
+
+        assert_eq!(parsed, vec![vec!["email", "name"], vec!["ursula@example.com", "ursula"]]);
+    }
+
+    #[test]
+    fn the_last_record_does_not_need_a_line_break() {
+        let parsed = parse_in_chunks("email,name\nursula@example.com,ursula", 1024);
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1], vec!["ursula@example.com", "ursula"]);
+    }
+
+    #[test]
+    fn quoted_fields_can_hold_separators() {
+        let parsed = parse_in_chunks("\"le guin, \"\"ursula\"\"\",\"two\nlines\"\n", 1024);
+
+        assert_eq!(parsed, vec![vec!["le guin, \"ursula\"", "two\nlines"]]);
+    }
+
+    #[test]
+    fn records_can_span_several_chunks() {
+        let input = "email,name\n\"ursula@example.com\",\"ursula, le guin\"\noctavia@example.com,octavia\n";
+
+        for chunk_size in 1..8 {
+            assert_eq!(parse_in_chunks(input, chunk_size), parse_in_chunks(input, 1024));
+        }
+    }
+
+    #[test]
+    fn long_records_grow_the_buffers() {
+        let name = "a".repeat(5000);
+        let fields = vec!["x"; 100].join(",");
+        let parsed = parse_in_chunks(&format!("{}\n{}\n", name, fields), 1000);
+
+        assert_eq!(parsed[0], vec![name]);
+        assert_eq!(parsed[1].len(), 100);
+    }
+
+    #[test]
+    fn unterminated_quotes_are_rejected() {
+        let mut records = CsvRecords::new();
+        let chunk = format!("\"{}", "a".repeat(1024));
+
+        let result = (0..100).try_for_each(|_| records.feed(chunk.as_bytes()).map(|_| ()));
+
+        assert_err!(result);
+    }
+
+    #[test]
+    fn invalid_utf8_only_rejects_its_row() {
+        let mut records = CsvRecords::new();
+
+        let parsed = assert_ok!(records.feed(b"\xff\xfe,name\nursula@example.com,ursula\n"));
+
+        assert_eq!(parsed[0], Err(CsvError::InvalidUtf8));
+        assert_eq!(parsed[1], Ok(vec!["ursula@example.com".to_owned(), "ursula".to_owned()]));
+    }
+}
@@ -0,0 +1,308 @@
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use crate::audit::{record_audit_event, AuditEvent};
+use crate::confirmation_queue::enqueue_confirmation_email;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::metrics::SUBSCRIPTIONS_CREATED;
+use crate::routes::generate_subscription_token;
+use super::csv_records::{CsvError, CsvRecords, Record};
+
+/// What the imported subscribers start as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportStatus {
+    /// They are sent a confirmation email, through the delivery worker.
+    Pending,
+    /// They already confirmed their address with the previous tool.
+    Confirmed,
+}
+
+impl ImportStatus {
+    pub fn parse(status: &str) -> Result<ImportStatus, String> {
+        match status {
+            "pending" => Ok(ImportStatus::Pending),
+            "confirmed" => Ok(ImportStatus::Confirmed),
+            other => Err(format!(
+                "{} is not a valid import status. Use either `pending` or `confirmed`.",
+                other
+            )),
+        }
+    }
+
+    /// The matching `subscriptions.status`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportStatus::Pending => "pending_confirmation",
+            ImportStatus::Confirmed => "confirmed",
+        }
+    }
+}
+
+/// A row that was left out, and why.
+#[derive(Debug, Serialize)]
+pub struct RejectedRow {
+    /// Starts at 1, with the header.
+    pub row: u64,
+    pub email: Option<String>,
+    pub reason: String,
+}
+
+/// How many rejected rows an `ImportReport` lists, at most.
+pub const MAX_LISTED_REJECTIONS: usize = 1000;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub n_imported: u64,
+    /// Addresses that were already subscribed, or that came up earlier in the file.
+    pub n_duplicates: u64,
+    pub n_rejected: u64,
+    /// The first `MAX_LISTED_REJECTIONS` rejected rows.
+    pub rejected: Vec<RejectedRow>,
+}
+
+impl ImportReport {
+    fn reject(&mut self, rejected: RejectedRow) {
+        self.n_rejected += 1;
+        if self.rejected.len() < MAX_LISTED_REJECTIONS {
+            self.rejected.push(rejected);
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum ImportError {
+    #[error("The first row must name an `email` and a `name` column.")]
+    MissingColumns,
+    #[error(transparent)]
+    InvalidCsv(#[from] CsvError),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        crate::routes::error_chain_fmt(self, f)
+    }
+}
+
+#[derive(Debug)]
+struct Columns {
+    email: usize,
+    name: usize,
+}
+
+/// Subscribers read from a CSV file with a header row naming (at least)
+/// an `email` and a `name` column, fed chunk by chunk.
+/// Everything is imported in a single transaction: nothing is stored
+/// unless `finish` is reached.
+pub struct SubscriberImport {
+    transaction: Transaction<'static, Postgres>,
+    status: ImportStatus,
+    records: CsvRecords,
+    columns: Option<Columns>,
+    n_rows: u64,
+    report: ImportReport,
+}
+
+impl SubscriberImport {
+    pub async fn begin(pool: &PgPool, status: ImportStatus) -> Result<Self, ImportError> {
+        let transaction = pool.begin().await.context("Failed to start a transaction.")?;
+        Ok(Self {
+            transaction,
+            status,
+            records: CsvRecords::new(),
+            columns: None,
+            n_rows: 0,
+            report: ImportReport::default(),
+        })
+    }
+
+    pub async fn feed(&mut self, chunk: &[u8]) -> Result<(), ImportError> {
+        if chunk.is_empty() {
+            // An empty chunk would end the input early.
+            return Ok(());
+        }
+        for record in self.records.feed(chunk)? {
+            self.import_record(record).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Finish a subscriber import", skip(self), fields(status = ?self.status))]
+    pub async fn finish(mut self, actor_user_id: Option<Uuid>) -> Result<ImportReport, ImportError> {
+        for record in self.records.feed(&[])? {
+            self.import_record(record).await?;
+        }
+        if self.columns.is_none() {
+            return Err(ImportError::MissingColumns);
+        }
+
+        let report = self.report;
+        record_audit_event(&mut *self.transaction, AuditEvent {
+            actor_user_id,
+            details: Some(format!(
+                "{} imported as {}, {} duplicates, {} rejected",
+                report.n_imported,
+                self.status.as_str(),
+                report.n_duplicates,
+                report.n_rejected,
+            )),
+            ..AuditEvent::new("subscribers_imported")
+        })
+        .await
+        .context("Failed to record the import.")?;
+        self.transaction.commit().await.context("Failed to commit the import.")?;
+        SUBSCRIPTIONS_CREATED.inc_by(report.n_imported);
+
+        Ok(report)
+    }
+
+    async fn import_record(&mut self, record: Record) -> Result<(), ImportError> {
+        self.n_rows += 1;
+        let row = self.n_rows;
+        let Some(columns) = &self.columns else {
+            self.columns = Some(find_columns(&record.map_err(|_| ImportError::MissingColumns)?)?);
+            return Ok(());
+        };
+        let fields = match record {
+            Ok(fields) => fields,
+            Err(e) => {
+                self.report.reject(RejectedRow { row, email: None, reason: e.to_string() });
+                return Ok(());
+            }
+        };
+        // Spreadsheets like to end their exports with a few empty rows.
+        if fields.iter().all(|field| field.trim().is_empty()) {
+            return Ok(());
+        }
+
+        let email = fields.get(columns.email).cloned().unwrap_or_default();
+        let name = fields.get(columns.name).cloned().unwrap_or_default();
+        let new_subscriber = match parse_subscriber(email.clone(), name) {
+            Ok(new_subscriber) => new_subscriber,
+            Err(reason) => {
+                self.report.reject(RejectedRow { row, email: Some(email), reason });
+                return Ok(());
+            }
+        };
+
+        match insert_imported_subscriber(&mut self.transaction, &new_subscriber, self.status)
+            .await
+            .context("Failed to insert an imported subscriber.")?
+        {
+            Some(subscriber_id) => {
+                if self.status == ImportStatus::Pending {
+                    enqueue_confirmation_email(&mut self.transaction, subscriber_id)
+                        .await
+                        .context("Failed to queue the confirmation email of an imported subscriber.")?;
+                }
+                self.report.n_imported += 1;
+            },
+            None => self.report.n_duplicates += 1,
+        }
+        Ok(())
+    }
+}
+
+fn find_columns(header: &[String]) -> Result<Columns, ImportError> {
+    let position = |column: &str| {
+        header.iter().position(|field| field.trim().eq_ignore_ascii_case(column))
+    };
+    match (position("email"), position("name")) {
+        (Some(email), Some(name)) => Ok(Columns { email, name }),
+        _ => Err(ImportError::MissingColumns),
+    }
+}
+
+/// Every validation error of the row, not only the first one.
+fn parse_subscriber(email: String, name: String) -> Result<NewSubscriber, String> {
+    match (SubscriberEmail::parse(email), SubscriberName::parse(name)) {
+        (Ok(email), Ok(name)) => Ok(NewSubscriber { email, name }),
+        (Err(e), Ok(_)) => Err(e.to_string()),
+        (Ok(_), Err(e)) => Err(e.to_string()),
+        (Err(email_error), Err(name_error)) => Err(format!("{} {}", email_error, name_error)),
+    }
+}
+
+/// `None` if the address is already taken: imports never touch existing
+/// subscribers, least of all those who unsubscribed.
+#[tracing::instrument(name = "Insert an imported subscriber", skip(transaction, new_subscriber))]
+async fn insert_imported_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_subscriber: &NewSubscriber,
+    status: ImportStatus,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let inserted = sqlx::query!(
+        r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+                VALUES ($1, $2, $3, now(), $4, $5)
+                ON CONFLICT (email) DO NOTHING
+                RETURNING id
+        "#,
+        Uuid::new_v4(),
+        new_subscriber.email.as_ref(),
+        new_subscriber.name.as_ref(),
+        status.as_str(),
+        generate_subscription_token(),
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    Ok(inserted.map(|row| row.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+    use super::{
+        find_columns, parse_subscriber, ImportReport, ImportStatus, RejectedRow,
+        MAX_LISTED_REJECTIONS,
+    };
+
+    fn header(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|&field| field.to_owned()).collect()
+    }
+
+    #[test]
+    fn columns_are_found_in_any_order_and_case() {
+        let columns = assert_ok!(find_columns(&header(&["Name", "joined", " EMAIL "])));
+
+        assert_eq!(columns.email, 2);
+        assert_eq!(columns.name, 0);
+    }
+
+    #[test]
+    fn both_columns_are_required() {
+        assert_err!(find_columns(&header(&["email", "full name"])));
+        assert_err!(find_columns(&header(&[])));
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let reason = parse_subscriber("not-an-email".into(), "".into()).unwrap_err();
+
+        assert!(reason.contains("not-an-email"), "{}", reason);
+        assert!(reason.contains("empty"), "{}", reason);
+    }
+
+    #[test]
+    fn only_the_first_rejected_rows_are_listed() {
+        let mut report = ImportReport::default();
+
+        for row in 0..MAX_LISTED_REJECTIONS as u64 + 10 {
+            report.reject(RejectedRow { row, email: None, reason: "Invalid.".into() });
+        }
+
+        assert_eq!(report.n_rejected, MAX_LISTED_REJECTIONS as u64 + 10);
+        assert_eq!(report.rejected.len(), MAX_LISTED_REJECTIONS);
+        assert_eq!(report.rejected.last().unwrap().row, MAX_LISTED_REJECTIONS as u64 - 1);
+    }
+
+    #[test]
+    fn import_statuses_are_parsed_from_their_name() {
+        assert_ok_eq!(ImportStatus::parse("pending"), ImportStatus::Pending);
+        assert_ok_eq!(ImportStatus::parse("confirmed"), ImportStatus::Confirmed);
+        assert_err!(ImportStatus::parse("pending_confirmation"));
+    }
+}
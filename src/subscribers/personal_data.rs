@@ -0,0 +1,163 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Everything we keep about a subscriber, as stored: addresses that an
+/// older validation let through are exported as they are.
+#[derive(Serialize)]
+pub struct PersonalData {
+    pub exported_at: DateTime<Utc>,
+    pub subscriber: SubscriberRecord,
+    pub confirmation_tokens: Vec<TokenRecord>,
+    pub review_tokens: Vec<TokenRecord>,
+    pub reviews: Vec<ReviewRecord>,
+    pub deliveries: Vec<DeliveryRecord>,
+    pub pending_deliveries: Vec<PendingDeliveryRecord>,
+}
+
+#[derive(Serialize)]
+pub struct SubscriberRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+/// The tokens themselves are left out: they are credentials, not data.
+#[derive(Serialize)]
+pub struct TokenRecord {
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ReviewRecord {
+    pub rating: i16,
+    pub content: String,
+    pub status: String,
+    pub submitted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct DeliveryRecord {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    /// `delivered` or `failed`.
+    pub outcome: String,
+    pub attempted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct PendingDeliveryRecord {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+}
+
+/// `None` if the subscriber does not exist.
+#[tracing::instrument(name = "Get the personal data of a subscriber", skip(pool))]
+pub async fn get_personal_data(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<PersonalData>, anyhow::Error> {
+    // One snapshot, so that the sections agree with each other.
+    let mut transaction = pool.begin().await.context("Failed to start a transaction.")?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to make the export transaction read-only.")?;
+
+    let subscriber = sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+            SELECT id, email, name, status, subscribed_at FROM subscriptions
+                WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve a subscriber.")?;
+    let Some(subscriber) = subscriber else {
+        return Ok(None);
+    };
+
+    let confirmation_tokens = sqlx::query_as!(
+        TokenRecord,
+        r#"
+            SELECT created_at, expires_at, used_at FROM subscription_tokens
+                WHERE subscriber_id = $1
+                ORDER BY created_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the confirmation tokens.")?;
+    let review_tokens = sqlx::query_as!(
+        TokenRecord,
+        r#"
+            SELECT created_at, expires_at, used_at FROM review_tokens
+                WHERE subscriber_id = $1
+                ORDER BY created_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the review tokens.")?;
+    let reviews = sqlx::query_as!(
+        ReviewRecord,
+        r#"
+            SELECT rating, content, status, submitted_at FROM reviews
+                WHERE subscriber_id = $1
+                ORDER BY submitted_at
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the reviews.")?;
+    let deliveries = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+            SELECT d.newsletter_issue_id, i.title, d.outcome, d.attempted_at
+                FROM issue_deliveries d
+                JOIN newsletter_issues i USING (newsletter_issue_id)
+                WHERE d.subscriber_email = $1
+                ORDER BY d.attempted_at
+        "#,
+        subscriber.email,
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the delivery history.")?;
+    let pending_deliveries = sqlx::query_as!(
+        PendingDeliveryRecord,
+        r#"
+            SELECT q.newsletter_issue_id, i.title
+                FROM issue_delivery_queue q
+                JOIN newsletter_issues i USING (newsletter_issue_id)
+                WHERE q.subscriber_email = $1
+                ORDER BY i.published_at
+        "#,
+        subscriber.email,
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the pending deliveries.")?;
+    transaction.commit().await.context("Failed to end the export transaction.")?;
+
+    Ok(Some(PersonalData {
+        exported_at: Utc::now(),
+        subscriber,
+        confirmation_tokens,
+        review_tokens,
+        reviews,
+        deliveries,
+        pending_deliveries,
+    }))
+}
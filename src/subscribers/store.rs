@@ -0,0 +1,274 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::audit::{record_audit_event, AuditEvent};
+
+/// Every value of `subscriptions.status`.
+pub const STATUSES: [&str; 3] = ["pending_confirmation", "confirmed", "unsubscribed"];
+
+/// A subscriber as stored: addresses and names that got past older, laxer
+/// validations are listed as they are, so that they can still be managed.
+/// Parse them into the domain types before sending them anything.
+pub struct Subscriber {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+/// Which subscribers to list; every field left to `None` matches them all.
+#[derive(Debug, Default, Clone)]
+pub struct SubscriberFilter {
+    pub status: Option<String>,
+    /// Inclusive.
+    pub subscribed_from: Option<DateTime<Utc>>,
+    /// Exclusive.
+    pub subscribed_before: Option<DateTime<Utc>>,
+    /// Part of the email address or of the name, case insensitive.
+    pub search: Option<String>,
+}
+
+/// The subscribers matching `filter`, newest first.
+/// `limit` and `offset` page through them; `None` returns them all.
+#[tracing::instrument(name = "Get subscribers", skip(pool))]
+pub async fn get_subscribers(
+    pool: &PgPool,
+    filter: &SubscriberFilter,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<Vec<Subscriber>, anyhow::Error> {
+    let pattern = filter.search.as_deref().map(|search| format!("%{}%", escape_like(search)));
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+            SELECT id, email, name, status, subscribed_at FROM subscriptions
+                WHERE ($1::text IS NULL OR status = $1)
+                    AND ($2::timestamptz IS NULL OR subscribed_at >= $2)
+                    AND ($3::timestamptz IS NULL OR subscribed_at < $3)
+                    AND ($4::text IS NULL OR email ILIKE $4 OR name ILIKE $4)
+                ORDER BY subscribed_at DESC, email
+                LIMIT $5 OFFSET $6
+        "#,
+        filter.status,
+        filter.subscribed_from,
+        filter.subscribed_before,
+        pattern,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscribers.")?;
+
+    Ok(subscribers)
+}
+
+#[tracing::instrument(name = "Get a subscriber", skip(pool))]
+pub async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+            SELECT id, email, name, status, subscribed_at FROM subscriptions
+                WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a subscriber.")?;
+
+    Ok(subscriber)
+}
+
+pub struct ConfirmationToken {
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// The confirmation links sent to a subscriber, newest first.
+#[tracing::instrument(name = "Get confirmation tokens", skip(pool))]
+pub async fn get_confirmation_tokens(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ConfirmationToken>, anyhow::Error> {
+    let tokens = sqlx::query_as!(
+        ConfirmationToken,
+        r#"
+            SELECT created_at, expires_at, used_at FROM subscription_tokens
+                WHERE subscriber_id = $1
+                ORDER BY created_at DESC
+        "#,
+        subscriber_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the confirmation tokens.")?;
+
+    Ok(tokens)
+}
+
+/// Change the status of a subscriber, bypassing the links we emailed them.
+/// `false` if the subscriber does not exist.
+#[tracing::instrument(name = "Set subscriber status", skip(pool))]
+pub async fn set_subscriber_status(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    status: &str,
+    actor_user_id: Option<Uuid>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool.begin().await.context("Failed to start a transaction.")?;
+    let previous = sqlx::query!(
+        r#"SELECT status FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the status of a subscriber.")?;
+    let Some(previous) = previous else {
+        return Ok(false);
+    };
+    if previous.status == status {
+        return Ok(true);
+    }
+
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = $1 WHERE id = $2"#,
+        status,
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to change the status of a subscriber.")?;
+    record_audit_event(&mut *transaction, AuditEvent {
+        actor_user_id,
+        details: Some(format!("subscriber: {}, status: {} -> {}", subscriber_id, previous.status, status)),
+        ..AuditEvent::new("subscriber_status_changed")
+    })
+    .await
+    .context("Failed to record the status change.")?;
+    transaction.commit().await.context("Failed to commit the status change.")?;
+
+    Ok(true)
+}
+
+/// Remove a subscriber and everything that refers to them: confirmation
+/// tokens, reviews, delivery history and the emails still in the queues.
+/// `false` if the subscriber does not exist.
+#[tracing::instrument(name = "Delete a subscriber", skip(pool))]
+pub async fn delete_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    actor_user_id: Option<Uuid>,
+) -> Result<bool, anyhow::Error> {
+    remove_subscriber(pool, subscriber_id, AuditEvent {
+        actor_user_id,
+        ..AuditEvent::new("subscriber_deleted")
+    })
+    .await
+}
+
+/// Same as `delete_subscriber`, on the subscriber's own request.
+#[tracing::instrument(name = "Erase a subscriber", skip(pool))]
+pub async fn erase_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+    remove_subscriber(pool, subscriber_id, AuditEvent::new("subscriber_erased")).await
+}
+
+async fn remove_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    event: AuditEvent<'_>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool.begin().await.context("Failed to start a transaction.")?;
+    let subscriber = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve a subscriber.")?;
+    let Some(subscriber) = subscriber else {
+        return Ok(false);
+    };
+
+    sqlx::query!(r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the confirmation tokens of a subscriber.")?;
+    sqlx::query!(r#"DELETE FROM confirmation_email_queue WHERE subscriber_id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the queued confirmation email of a subscriber.")?;
+    sqlx::query!(r#"DELETE FROM privacy_tokens WHERE subscriber_id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the privacy tokens of a subscriber.")?;
+    sqlx::query!(r#"DELETE FROM review_tokens WHERE subscriber_id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the review tokens of a subscriber.")?;
+    sqlx::query!(r#"DELETE FROM reviews WHERE subscriber_id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the reviews of a subscriber.")?;
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = $1"#,
+        subscriber.email,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the pending deliveries of a subscriber.")?;
+    sqlx::query!(
+        r#"DELETE FROM issue_deliveries WHERE subscriber_email = $1"#,
+        subscriber.email,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the delivery history of a subscriber.")?;
+    sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete a subscriber.")?;
+    // Only the id: the point of deleting them is not to keep their address.
+    record_audit_event(&mut *transaction, AuditEvent {
+        details: Some(format!("subscriber: {}", subscriber_id)),
+        ..event
+    })
+    .await
+    .context("Failed to record the deletion.")?;
+    transaction.commit().await.context("Failed to commit the deletion.")?;
+
+    Ok(true)
+}
+
+/// `search` as a literal inside an `ILIKE` pattern.
+fn escape_like(search: &str) -> String {
+    let mut escaped = String::with_capacity(search.len());
+    for c in search.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_like;
+
+    #[test]
+    fn plain_searches_are_left_alone() {
+        assert_eq!(escape_like("le guin"), "le guin");
+    }
+
+    #[test]
+    fn wildcards_are_searched_literally() {
+        assert_eq!(escape_like("100%_off\\"), "100\\%\\_off\\\\");
+    }
+}
@@ -1,17 +1,29 @@
+use opentelemetry::global;
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self as sdktrace, TracerProvider};
+use opentelemetry::sdk::{runtime, Resource};
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::{SpanExporterBuilder, WithExportConfig};
 use tower_request_id::RequestId;
 use tracing::{Subscriber, Span};
 use tracing::subscriber::set_global_default;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
 use tracing_subscriber::fmt::MakeWriter;
-use hyper::{Body, http::Request};
+use hyper::{Body, HeaderMap, http::Request};
 use tokio::task::JoinHandle;
 
+/// `tracer`, when there is one, receives every span on top of the
+/// Bunyan logs, to be exported with their W3C trace context.
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
+    tracer: Option<sdktrace::Tracer>,
 ) -> impl Subscriber + Send + Sync
 where
 
@@ -21,30 +33,77 @@ Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(env_filter));
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    let otel_layer = tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
 
     Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
         .with(formatting_layer)
-        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to set logger.");
+    global::set_text_map_propagator(TraceContextPropagator::new());
     set_global_default(subscriber).expect("Failed to set subscriber.");
 }
 
+/// The provider of the spans' trace context. Without an `otlp_endpoint`
+/// nothing is exported, but spans still get trace ids, so that an incoming
+/// `traceparent` is carried over to outgoing requests all the same.
+/// With one, spans are batched in the background and shipped to that
+/// OTLP/HTTP collector (a full URL, e.g. `http://localhost:4318/v1/traces`).
+/// Must be called from within a Tokio runtime.
+pub fn tracer_provider(
+    otlp_endpoint: Option<&str>,
+    service_name: String,
+) -> Result<TracerProvider, TraceError> {
+    let mut builder = TracerProvider::builder()
+        .with_config(sdktrace::config().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", service_name),
+        ])));
+    if let Some(endpoint) = otlp_endpoint {
+        let exporter = SpanExporterBuilder::from(
+            opentelemetry_otlp::new_exporter().http().with_endpoint(endpoint),
+        )
+        .build_span_exporter()?;
+        builder = builder.with_batch_exporter(exporter, runtime::Tokio);
+    }
+
+    Ok(builder.build())
+}
+
+/// Span ids are generated whether or not spans end up being exported,
+/// so the trace context can still be propagated.
+pub fn tracer(provider: &TracerProvider) -> sdktrace::Tracer {
+    provider.tracer("myweb")
+}
+
 pub fn request_id(request: &Request<Body>) -> Span {
     let request_id = request.extensions()
         .get::<RequestId>()
         .map(ToString::to_string)
         .unwrap_or_else(|| "unknown".into());
-    tracing::error_span!(
+    let span = tracing::error_span!(
         "request",
         id = %request_id,
         method = %request.method(),
         uri = %request.uri(),
-    )
+    );
+    // Continue the caller's trace when it sent a `traceparent` header.
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+    span
+}
+
+/// Add the current span's trace context (`traceparent`) to outgoing headers.
+pub fn inject_trace_context(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(headers))
+    });
 }
 
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
@@ -54,4 +113,4 @@ where
 {
     let current_spawn = tracing::Span::current();
     tokio::task::spawn_blocking(move || { current_spawn.in_scope(f) })
-}
\ No newline at end of file
+}
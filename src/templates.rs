@@ -0,0 +1,174 @@
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use axum::{
+    extract::State,
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use hyper::{header::CONTENT_LENGTH, Request, StatusCode};
+use tera::{Context, Tera};
+
+/// Every HTML page and email body is rendered from `templates/`.
+/// Files ending in `.html` are auto-escaped, `.txt` files are not.
+#[derive(Clone)]
+pub struct Templates {
+    tera: Arc<RwLock<Tera>>,
+    // Re-read the templates from disk before every render.
+    reload: bool,
+}
+
+impl Templates {
+    /// The templates in `templates/`, relative to the working directory
+    /// (like `configuration/`).
+    pub fn new(reload: bool) -> Result<Self, tera::Error> {
+        let base_path = std::env::current_dir()
+            .map_err(|e| tera::Error::chain("Failed to determine the current directory", e))?;
+        Self::from_dir(&base_path.join("templates"), reload)
+    }
+
+    pub fn from_dir(dir: &Path, reload: bool) -> Result<Self, tera::Error> {
+        let mut tera = Tera::new(&format!("{}/**/*", dir.display()))?;
+        // Same escaping as the rest of the code base: quotes are escaped,
+        // so values are safe inside quoted attributes too.
+        tera.set_escape_fn(htmlescape::encode_minimal);
+        Ok(Self {
+            tera: Arc::new(RwLock::new(tera)),
+            reload,
+        })
+    }
+
+    pub fn render(&self, name: &str, context: &Context) -> Result<String, tera::Error> {
+        if self.reload {
+            self.tera.write().unwrap().full_reload()?;
+        }
+        self.tera.read().unwrap().render(name, context)
+    }
+}
+
+/// A short message shown with the site layout, for error responses.
+/// `IntoResponse` implementations cannot reach the templates, so the page
+/// is rendered on the way out by `render_error_pages`.
+#[derive(Clone, Debug)]
+pub struct ErrorPage {
+    title: String,
+    message: String,
+    back_link: Option<(&'static str, &'static str)>,
+}
+
+impl ErrorPage {
+    pub fn new(title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { title: title.into(), message: message.into(), back_link: None }
+    }
+
+    /// Add a link below the message.
+    pub fn back_to(mut self, href: &'static str, label: &'static str) -> Self {
+        self.back_link = Some((href, label));
+        self
+    }
+
+    fn context(&self) -> Context {
+        let mut context = Context::new();
+        context.insert("title", &self.title);
+        context.insert("message", &self.message);
+        if let Some((href, label)) = self.back_link {
+            context.insert("back_link", &serde_json::json!({ "href": href, "label": label }));
+        }
+        context
+    }
+}
+
+impl IntoResponse for ErrorPage {
+    fn into_response(self) -> Response {
+        let mut response = StatusCode::OK.into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+pub async fn render_error_pages<B>(
+    State(templates): State<Templates>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut response = next.run(request).await;
+    let Some(page) = response.extensions_mut().remove::<ErrorPage>() else {
+        return response;
+    };
+
+    match templates.render("message.html", &page.context()) {
+        Ok(body) => {
+            let (mut parts, _) = response.into_parts();
+            // It was computed for the empty placeholder body.
+            parts.headers.remove(CONTENT_LENGTH);
+            (parts, Html(body)).into_response()
+        },
+        Err(e) => {
+            tracing::error!("\nFailed to render an error page: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use tera::Context;
+    use uuid::Uuid;
+    use crate::flash_messages::FlashMessage;
+    use super::{ErrorPage, Templates};
+
+    fn templates() -> Templates {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("templates");
+        Templates::from_dir(&dir, false).expect("Failed to load the templates")
+    }
+
+    #[test]
+    fn html_templates_are_escaped() {
+        let page = ErrorPage::new("Oops", "<script>alert('pwned')</script>");
+
+        let html = templates().render("message.html", &page.context()).unwrap();
+
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(&#x27;pwned&#x27;)&lt;/script&gt;"));
+    }
+
+    #[test]
+    fn flash_messages_are_escaped() {
+        let mut context = page_context();
+        context.insert("flash_messages", &[FlashMessage::error("<script>alert('pwned')</script>")]);
+
+        let html = templates().render("message.html", &context).unwrap();
+
+        assert!(!html.contains("<script>"));
+        assert!(html.contains(r#"<p class="flash-error"><i>&lt;script&gt;"#));
+    }
+
+    #[test]
+    fn text_templates_are_not_escaped() {
+        let mut context = Context::new();
+        context.insert("link", "https://example.com/confirm?a=1&b=2");
+
+        let text = templates().render("emails/confirmation.txt", &context).unwrap();
+
+        assert!(text.contains("https://example.com/confirm?a=1&b=2"));
+    }
+
+    #[test]
+    fn templates_are_reloaded_when_asked_to() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("page.html"), "before").unwrap();
+        let cached = Templates::from_dir(&dir, false).unwrap();
+        let reloaded = Templates::from_dir(&dir, true).unwrap();
+
+        std::fs::write(dir.join("page.html"), "after").unwrap();
+
+        assert_eq!(cached.render("page.html", &Context::new()).unwrap(), "before");
+        assert_eq!(reloaded.render("page.html", &Context::new()).unwrap(), "after");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn page_context() -> Context {
+        ErrorPage::new("Title", "Message").context()
+    }
+}
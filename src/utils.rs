@@ -1,13 +1,96 @@
 use axum::{
-    response::IntoResponse,
-    http::StatusCode,
+    async_trait,
+    extract::FromRequest,
+    response::{IntoResponse, Response},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    Form, Json,
 };
+use hyper::{body::Bytes, Body};
+use serde::de::DeserializeOwned;
+use std::future::Future;
 use tokio::signal;
+use tracing::Instrument;
 
 pub async fn handler_404() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "You've ventured beyond the horison.")
 }
 
+/// Log an unexpected error and answer with a generic 500.
+pub fn e500<E: std::fmt::Debug>(e: E) -> (StatusCode, &'static str) {
+    tracing::error!("\nServer error: {:?}", e);
+    (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected internal server error.")
+}
+
+/// Run `task` in the background, in the current span, and log its error.
+/// For handlers whose response must not depend on the outcome, e.g. so
+/// that a slow or failed email does not tell whether an address is known.
+pub fn spawn_and_log_error<F>(task: F, message: &'static str)
+where
+    F: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+{
+    tokio::spawn(
+        async move {
+            if let Err(e) = task.await {
+                tracing::error!(error.cause_chain = ?e, error.message = %e, "{}", message);
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+/// What axum's `DefaultBodyLimit` lets handlers read. Middleware that
+/// looks into a body before the handler does holds itself to the same.
+pub const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Buffer a request body for middleware, answering 413 past `limit` bytes.
+pub async fn read_body(body: Body, limit: usize) -> Result<Bytes, Response> {
+    hyper::body::to_bytes(http_body::Limited::new(body, limit))
+        .await
+        .map_err(|e| {
+            if e.downcast_ref::<http_body::LengthLimitError>().is_some() {
+                StatusCode::PAYLOAD_TOO_LARGE.into_response()
+            } else {
+                tracing::error!("\nFailed to read the request body: {:?}", e);
+                StatusCode::BAD_REQUEST.into_response()
+            }
+        })
+}
+
+/// A JSON body or a form, depending on the `Content-Type` of the request.
+/// Rejections are answered in JSON.
+pub struct JsonOrForm<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S, Body> for JsonOrForm<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        let is_json = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.to_ascii_lowercase().starts_with("application/json"));
+
+        let payload = if is_json {
+            Json::<T>::from_request(request, state)
+                .await
+                .map(|Json(payload)| payload)
+                .map_err(|rejection| (rejection.status(), rejection.body_text()))
+        } else {
+            Form::<T>::from_request(request, state)
+                .await
+                .map(|Form(payload)| payload)
+                .map_err(|rejection| (rejection.status(), rejection.body_text()))
+        };
+        payload.map(Self).map_err(|(status, message)| {
+            (status, Json(serde_json::json!({ "error": message }))).into_response()
+        })
+    }
+}
 
 pub async fn shutdown_signal() {
     let ctrl_c = async {
@@ -0,0 +1,66 @@
+use crate::helpers::{spawn_app, assert_is_redirected_to};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirected_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_tampered_session_cookie_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    // Act - Forge a session cookie that was not signed by the server
+    let response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("{}/admin/dashboard", &app.address))
+        .header("Cookie", "id=not-a-signed-session-key")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirected_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn logout_clears_session_state() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - Login
+    app.login_as_test_user().await;
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+
+    // Act - Part 3 - Logout
+    let response = app.post_logout().await;
+    assert_is_redirected_to(&response, "/login");
+
+    // Act - Part 4 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(r#"<p class="flash-info"><i>You have successfully logged out.</i></p>"#));
+
+    // Act - Part 5 - Attempt to load admin panel
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirected_to(&response, "/login");
+
+    // Assert - The session was removed from the store
+    let sessions = sqlx::query!("SELECT session_key FROM sessions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(sessions.is_empty());
+}
@@ -0,0 +1,497 @@
+use chrono::{DateTime, TimeZone, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use crate::helpers::{
+    assert_is_redirected_to, create_unconfirmed_subscriber, spawn_app, TestApp, TestUser,
+};
+
+async fn store_subscriber(
+    app: &TestApp,
+    email: &str,
+    name: &str,
+    status: &str,
+    subscribed_at: DateTime<Utc>,
+) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+                VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        subscriber_id,
+        email,
+        name,
+        subscribed_at,
+        status,
+        Uuid::new_v4().to_string(),
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    subscriber_id
+}
+
+fn day(day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 7, day, 12, 0, 0).unwrap()
+}
+
+async fn get_status(app: &TestApp, subscriber_id: Uuid) -> Option<String> {
+    sqlx::query!("SELECT status FROM subscriptions WHERE id = $1", subscriber_id)
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap()
+        .map(|row| row.status)
+}
+
+async fn count_audit_events(app: &TestApp, event: &str) -> i64 {
+    sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM audit_log WHERE event = $1"#,
+        event
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_admin_subscribers("").await;
+
+    // Assert
+    assert_is_redirected_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn viewers_cannot_see_the_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login_as(&viewer).await;
+
+    // Act
+    let list = app.get_admin_subscribers("").await;
+    let export = app.get_admin_subscribers("/export").await;
+
+    // Assert
+    assert_eq!(list.status().as_u16(), 403);
+    assert_eq!(export.status().as_u16(), 403);
+    assert!(!app.get_admin_dashboard_html().await.contains("/admin/subscribers"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status_and_searched() {
+    // Arrange
+    let app = spawn_app().await;
+    store_subscriber(&app, "ursula@example.com", "ursula le guin", "confirmed", day(1)).await;
+    store_subscriber(&app, "octavia@example.com", "octavia butler", "confirmed", day(2)).await;
+    store_subscriber(&app, "ursula.k@example.com", "ursula k", "unsubscribed", day(3)).await;
+    app.login_as_test_user().await;
+
+    // Act
+    let html = app
+        .get_admin_subscribers("?status=confirmed&search=URSULA")
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html.contains("ursula@example.com"));
+    assert!(!html.contains("octavia@example.com"));
+    assert!(!html.contains("ursula.k@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_subscription_date() {
+    // Arrange
+    let app = spawn_app().await;
+    store_subscriber(&app, "first@example.com", "first", "confirmed", day(1)).await;
+    store_subscriber(&app, "second@example.com", "second", "confirmed", day(2)).await;
+    store_subscriber(&app, "third@example.com", "third", "confirmed", day(3)).await;
+    app.login_as_test_user().await;
+
+    // Act
+    let html = app
+        .get_admin_subscribers("?subscribed_from=2023-07-02&subscribed_until=2023-07-02")
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!html.contains("first@example.com"));
+    assert!(html.contains("second@example.com"));
+    assert!(!html.contains("third@example.com"));
+}
+
+#[tokio::test]
+async fn invalid_filters_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    for query in ["?status=bounced", "?subscribed_from=yesterday"] {
+        // Act
+        let response = app.get_admin_subscribers(query).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "{} was accepted.", query);
+    }
+}
+
+#[tokio::test]
+async fn the_subscribers_are_paginated_and_keep_their_filters() {
+    // Arrange
+    let app = spawn_app().await;
+    for i in 0..51 {
+        let email = format!("subscriber{}@example.com", i);
+        store_subscriber(&app, &email, "subscriber", "confirmed", day(1)).await;
+    }
+    app.login_as_test_user().await;
+
+    // Act
+    let first_page = app.get_admin_subscribers("?status=confirmed").await.text().await.unwrap();
+    let second_page = app
+        .get_admin_subscribers("?status=confirmed&page=2")
+        .await
+        .text()
+        .await
+        .unwrap();
+    let third_page = app.get_admin_subscribers("?status=confirmed&page=3").await;
+
+    // Assert
+    assert_eq!(first_page.matches("@example.com</a>").count(), 50);
+    assert!(first_page.contains(r#"href="/admin/subscribers?status=confirmed&amp;page=2""#));
+    assert_eq!(second_page.matches("@example.com</a>").count(), 1);
+    assert!(second_page.contains(r#"href="/admin/subscribers?status=confirmed&amp;page=1""#));
+    assert_eq!(third_page.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_looked_at() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.login_as_test_user().await;
+
+    // Act
+    let html = app
+        .get_admin_subscribers(&format!("/{}", subscriber_id))
+        .await
+        .text()
+        .await
+        .unwrap();
+    let unknown = app.get_admin_subscribers(&format!("/{}", Uuid::new_v4())).await;
+
+    // Assert
+    assert!(html.contains("ursula_le_guin@gmail.com"));
+    assert!(html.contains("pending_confirmation"));
+    assert!(html.contains("Not used"));
+    assert_eq!(unknown.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn editors_can_confirm_and_unsubscribe_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id =
+        store_subscriber(&app, "ursula@example.com", "ursula", "pending_confirmation", day(1)).await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+    let location = format!("/admin/subscribers/{}", subscriber_id);
+
+    // Act - Part 1 - Confirm
+    let response = app.post_admin_subscribers(&format!("/{}/confirm", subscriber_id)).await;
+
+    // Assert - Part 1
+    assert_is_redirected_to(&response, &location);
+    assert_eq!(get_status(&app, subscriber_id).await.unwrap(), "confirmed");
+
+    // Act - Part 2 - Unsubscribe
+    let response = app.post_admin_subscribers(&format!("/{}/unsubscribe", subscriber_id)).await;
+
+    // Assert - Part 2
+    assert_is_redirected_to(&response, &location);
+    assert_eq!(get_status(&app, subscriber_id).await.unwrap(), "unsubscribed");
+    let html = app.get_admin_subscribers(&location["/admin/subscribers".len()..]).await;
+    assert!(html.text().await.unwrap().contains("The subscriber has been unsubscribed."));
+    assert_eq!(count_audit_events(&app, "subscriber_status_changed").await, 2);
+}
+
+#[tokio::test]
+async fn deleting_a_subscriber_removes_their_tokens() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app.post_admin_subscribers(&format!("/{}/delete", subscriber_id)).await;
+
+    // Assert
+    assert_is_redirected_to(&response, "/admin/subscribers");
+    assert!(get_status(&app, subscriber_id).await.is_none());
+    let n_tokens = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tokens, 0);
+    assert_eq!(count_audit_events(&app, "subscriber_deleted").await, 1);
+}
+
+#[tokio::test]
+async fn only_admins_can_delete_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id =
+        store_subscriber(&app, "ursula@example.com", "ursula", "confirmed", day(1)).await;
+    let editor = TestUser::with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+
+    // Act
+    let response = app.post_admin_subscribers(&format!("/{}/delete", subscriber_id)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(get_status(&app, subscriber_id).await.is_some());
+}
+
+#[tokio::test]
+async fn subscribers_stored_before_stricter_validations_are_still_listed() {
+    // Arrange - Neither field would be accepted today
+    let app = spawn_app().await;
+    let subscriber_id =
+        store_subscriber(&app, "ursula-at-example.com", "le guin (ursula)", "confirmed", day(1)).await;
+    app.login_as_test_user().await;
+
+    // Act
+    let list = app.get_admin_subscribers("").await.text().await.unwrap();
+    let details = app.get_admin_subscribers(&format!("/{}", subscriber_id)).await;
+    let export = app.get_admin_subscribers("/export").await.text().await.unwrap();
+
+    // Assert
+    assert!(list.contains("ursula-at-example.com"));
+    assert_eq!(details.status().as_u16(), 200);
+    assert!(details.text().await.unwrap().contains("le guin (ursula)"));
+    assert!(export.contains(&format!("{},ursula-at-example.com,le guin (ursula)", subscriber_id)));
+}
+
+#[tokio::test]
+async fn the_filtered_subscribers_can_be_exported_as_csv() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id =
+        store_subscriber(&app, "ursula@example.com", "le guin, ursula", "confirmed", day(1)).await;
+    store_subscriber(&app, "octavia@example.com", "octavia", "unsubscribed", day(2)).await;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app.get_admin_subscribers("/export?status=confirmed").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "text/csv; charset=utf-8");
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment;"));
+    let csv = response.text().await.unwrap();
+    assert_eq!(
+        csv,
+        format!(
+            "id,email,name,status,subscribed_at\r\n\
+            {},ursula@example.com,\"le guin, ursula\",confirmed,2023-07-01T12:00:00+00:00\r\n",
+            subscriber_id
+        )
+    );
+    assert_eq!(count_audit_events(&app, "subscribers_exported").await, 1);
+}
+
+#[tokio::test]
+async fn imported_rows_are_validated_and_deduplicated() {
+    // Arrange
+    let app = spawn_app().await;
+    store_subscriber(&app, "octavia@example.com", "octavia", "unsubscribed", day(1)).await;
+    app.login_as_test_user().await;
+    let csv = "name,email\r\n\
+        \"le guin, ursula\",ursula@example.com\r\n\
+        octavia,octavia@example.com\r\n\
+        ursula again,ursula@example.com\r\n\
+        nobody,not-an-email\r\n";
+
+    // Act
+    let response = app.post_admin_subscribers_import("confirmed", csv).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("1 subscribers imported as confirmed, 2 duplicates skipped, 1 rows rejected."));
+    assert!(html.contains("not-an-email is not a valid subscriber email."));
+    let subscribers = sqlx::query!("SELECT email, name, status FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.len(), 2);
+    assert_eq!(subscribers[0].status, "unsubscribed");
+    assert_eq!(subscribers[1].email, "ursula@example.com");
+    assert_eq!(subscribers[1].name, "le guin, ursula");
+    assert_eq!(subscribers[1].status, "confirmed");
+    assert_eq!(count_audit_events(&app, "subscribers_imported").await, 1);
+}
+
+#[tokio::test]
+async fn pending_imports_are_sent_a_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_admin_subscribers_import(
+            "pending",
+            "email,name\nursula@example.com,ursula\noctavia@example.com,octavia\n",
+        )
+        .await;
+    app.dispatch_all_confirmation_emails().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let n_pending = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscriptions WHERE status = 'pending_confirmation'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_pending, 2);
+    let n_tokens = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tokens, 2);
+}
+
+#[tokio::test]
+async fn imports_without_the_expected_columns_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app
+        .post_admin_subscribers_import("confirmed", "address,full name\nursula@example.com,ursula\n")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn uploads_without_a_csrf_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/import", &app.address))
+        .header("Content-Type", "multipart/form-data; boundary=x")
+        .body("--x\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nemail,name\r\n--x--\r\n")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn upload_tokens_in_the_query_string_are_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/import", &app.address))
+        .query(&[("csrf_token", &app.csrf_token)])
+        .header("Content-Type", "multipart/form-data; boundary=x")
+        .body("--x\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nemail,name\r\n--x--\r\n")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_upload_token_must_come_before_the_file() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+    let body = format!(
+        "--x\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"subscribers.csv\"\r\n\r\n\
+        email,name\r\nursula_le_guin@gmail.com,le guin\r\n\
+        --x\r\n\
+        Content-Disposition: form-data; name=\"csrf_token\"\r\n\r\n\
+        {}\r\n\
+        --x--\r\n",
+        app.csrf_token
+    );
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/import", &app.address))
+        .header("Content-Type", "multipart/form-data; boundary=x")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
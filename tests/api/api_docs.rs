@@ -0,0 +1,42 @@
+use myweb::api_docs::ApiDoc;
+use utoipa::OpenApi;
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_openapi_document_is_served() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/api-docs/openapi.json", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let served: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(served, serde_json::to_value(ApiDoc::openapi()).unwrap());
+}
+
+#[tokio::test]
+async fn swagger_ui_loads_the_openapi_document_locally() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/api-docs", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("SwaggerUIBundle"));
+    assert!(html.contains(r#"url: "/api-docs/openapi.json""#));
+}
@@ -0,0 +1,133 @@
+use crate::helpers::{spawn_app, TestApp};
+use uuid::Uuid;
+
+async fn publish_issue(app: &TestApp, title: &str) {
+    let body = serde_json::json!({
+        "title": title,
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as <b>HTML</b></p>",
+        }
+    });
+    let response = app.post_newsletter(body).await;
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+async fn get_page(app: &TestApp, path: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}{}", app.address, path))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn published_issues_are_stored_with_their_author() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    publish_issue(&app, "First issue").await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT title, html_content, author_user_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the published issue.");
+    assert_eq!(saved.title, "First issue");
+    assert_eq!(saved.html_content, "<p>Newsletter body as <b>HTML</b></p>");
+    assert_eq!(saved.author_user_id, Some(app.test_user.user_id));
+}
+
+#[tokio::test]
+async fn the_archive_lists_published_issues_newest_first() {
+    // Arrange
+    let app = spawn_app().await;
+    publish_issue(&app, "First issue").await;
+    publish_issue(&app, "Second <issue>").await;
+
+    // Act
+    let response = get_page(&app, "/blog").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let first = html_page.find("First issue").unwrap();
+    let second = html_page.find("Second &lt;issue&gt;").unwrap();
+    assert!(second < first);
+}
+
+#[tokio::test]
+async fn the_archive_works_when_nothing_was_published() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = get_page(&app, "/blog").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Nothing has been published yet."));
+}
+
+#[tokio::test]
+async fn the_archive_is_paginated() {
+    // Arrange
+    let app = spawn_app().await;
+    for i in 0..12 {
+        publish_issue(&app, &format!("Issue #{:02}", i)).await;
+    }
+
+    // Act - Part 1 - First page
+    let html_page = get_page(&app, "/blog").await.text().await.unwrap();
+    assert!(html_page.contains("Issue #11"));
+    assert!(html_page.contains("Issue #02"));
+    assert!(!html_page.contains("Issue #01"));
+    assert!(html_page.contains(r#"href="/blog?page=2""#));
+
+    // Act - Part 2 - Second page
+    let html_page = get_page(&app, "/blog?page=2").await.text().await.unwrap();
+    assert!(html_page.contains("Issue #01"));
+    assert!(html_page.contains("Issue #00"));
+    assert!(!html_page.contains("Issue #02"));
+    assert!(html_page.contains(r#"href="/blog?page=1""#));
+    assert!(!html_page.contains(r#"href="/blog?page=3""#));
+
+    // Act - Part 3 - Past the last page
+    let response = get_page(&app, "/blog?page=3").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_single_issue_can_be_viewed() {
+    // Arrange
+    let app = spawn_app().await;
+    publish_issue(&app, "First issue").await;
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act
+    let response = get_page(&app, &format!("/blog/{}", issue_id)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<h1>First issue</h1>"));
+    assert!(html_page.contains("<p>Newsletter body as <b>HTML</b></p>"));
+    assert!(html_page.contains(&app.test_user.username));
+}
+
+#[tokio::test]
+async fn unknown_issues_return_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = get_page(&app, &format!("/blog/{}", Uuid::new_v4())).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
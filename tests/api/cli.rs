@@ -0,0 +1,170 @@
+use myweb::authentication::{create_user, reset_password};
+use myweb::domain::{NewPassword, Role};
+use myweb::subscribers::{get_subscribers, ImportStatus, SubscriberFilter, SubscriberImport};
+use secrecy::Secret;
+use crate::helpers::{
+    assert_is_redirected_to, create_confirmed_subscriber, create_unconfirmed_subscriber,
+    spawn_app,
+};
+
+fn new_password(password: &str) -> NewPassword {
+    NewPassword::parse(Secret::new(password.to_owned())).unwrap()
+}
+
+fn confirmed_only() -> SubscriberFilter {
+    SubscriberFilter { status: Some("confirmed".into()), ..Default::default() }
+}
+
+#[tokio::test]
+async fn users_created_from_the_command_line_can_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let password = "Correct-horse-1";
+
+    // Act
+    create_user(&app.db_pool, "ursula", new_password(password), Role::Editor, None)
+        .await
+        .unwrap();
+
+    // Assert
+    let response = app
+        .post_login(&serde_json::json!({ "username": "ursula", "password": password }))
+        .await;
+    assert_is_redirected_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn reset_passwords_replace_the_old_ones() {
+    // Arrange
+    let app = spawn_app().await;
+    let new = "Battery-staple-2";
+
+    // Act
+    let found = reset_password(&app.db_pool, &app.test_user.username, new_password(new), None)
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(found);
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirected_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({ "username": &app.test_user.username, "password": new }))
+        .await;
+    assert_is_redirected_to(&response, "/admin/dashboard");
+    let n_resets = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM audit_log WHERE event = 'password_reset' AND username = $1"#,
+        &app.test_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_resets, 1);
+}
+
+#[tokio::test]
+async fn resetting_a_password_unlocks_the_account() {
+    // Arrange
+    let app = spawn_app().await;
+    let new = "Battery-staple-2";
+    // `login_throttle.max_failures` in `configuration/base.yaml`
+    for _ in 0..5 {
+        app.post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "wrong-password",
+        }))
+        .await;
+    }
+
+    // Act
+    reset_password(&app.db_pool, &app.test_user.username, new_password(new), None)
+        .await
+        .unwrap();
+
+    // Assert
+    let response = app
+        .post_login(&serde_json::json!({ "username": &app.test_user.username, "password": new }))
+        .await;
+    assert_is_redirected_to(&response, "/admin/dashboard");
+    let details = sqlx::query!(
+        r#"SELECT details FROM audit_log WHERE event = 'password_reset' AND username = $1"#,
+        &app.test_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .details;
+    assert_eq!(details.as_deref(), Some("The account was unlocked."));
+}
+
+#[tokio::test]
+async fn resetting_the_password_of_an_unknown_user_changes_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let found = reset_password(&app.db_pool, "nobody", new_password("Battery-staple-2"), None)
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!found);
+}
+
+#[tokio::test]
+async fn subscribers_can_be_listed_by_status() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+
+    // Act
+    let all = get_subscribers(&app.db_pool, &SubscriberFilter::default(), None, 0).await.unwrap();
+    let confirmed = get_subscribers(&app.db_pool, &confirmed_only(), None, 0).await.unwrap();
+
+    // Assert
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].email, "ursula_le_guin@gmail.com");
+    assert_eq!(all[0].status, "pending_confirmation");
+    assert!(confirmed.is_empty());
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_are_listed_as_such() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let confirmed = get_subscribers(&app.db_pool, &confirmed_only(), None, 0).await.unwrap();
+
+    // Assert
+    assert_eq!(confirmed.len(), 1);
+    assert_eq!(confirmed[0].name, "le guin");
+}
+
+#[tokio::test]
+async fn imports_can_be_fed_in_arbitrary_chunks() {
+    // Arrange
+    let app = spawn_app().await;
+    let csv = "email,name\n\"ursula@example.com\",\"le guin, ursula\"\nnot-an-email,nobody\n";
+
+    // Act
+    let mut import = SubscriberImport::begin(&app.db_pool, ImportStatus::Confirmed).await.unwrap();
+    for chunk in csv.as_bytes().chunks(3) {
+        import.feed(chunk).await.unwrap();
+    }
+    let report = import.finish(None).await.unwrap();
+
+    // Assert
+    assert_eq!(report.n_imported, 1);
+    assert_eq!(report.rejected.len(), 1);
+    assert_eq!(report.rejected[0].row, 3);
+    let confirmed = get_subscribers(&app.db_pool, &confirmed_only(), None, 0).await.unwrap();
+    assert_eq!(confirmed[0].name, "le guin, ursula");
+}
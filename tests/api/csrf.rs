@@ -0,0 +1,144 @@
+use crate::helpers::{assert_is_redirected_to, extract_csrf_token, spawn_app};
+
+#[tokio::test]
+async fn form_posts_without_a_csrf_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_csrf_token_only_works_with_its_own_cookie() {
+    // Arrange
+    let app = spawn_app().await;
+    let other_client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let other_page = other_client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let other_token = extract_csrf_token(&other_page);
+    assert_ne!(other_token, app.csrf_token);
+
+    // Act
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "csrf_token": other_token,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn an_unsigned_csrf_cookie_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/login", &app.address))
+        .header("Cookie", "_csrf=forged-token")
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "csrf_token": "forged-token",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_token_can_be_sent_in_a_header() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-CSRF-Token", &app.csrf_token)
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirected_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn rendered_forms_carry_the_csrf_token() {
+    // Arrange
+    let app = spawn_app().await;
+    let field = format!(r#"<input type="hidden" name="csrf_token" value="{}">"#, app.csrf_token);
+
+    // Act
+    let home_page = app.api_client
+        .get(&app.address)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    app.login_as_test_user().await;
+    let dashboard = app.get_admin_dashboard_html().await;
+    let change_password = app.get_change_password_html().await;
+
+    // Assert
+    assert!(home_page.contains(&field));
+    assert!(dashboard.contains(&field));
+    assert!(change_password.contains(&field));
+}
+
+#[tokio::test]
+async fn oversized_form_posts_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.api_client
+        .post(format!("{}/login", &app.address))
+        .form(&app.with_csrf_token(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "a".repeat(3 * 1024 * 1024),
+        })))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 413);
+}
@@ -1,48 +1,60 @@
 use argon2::{Argon2, Algorithm, Params, PasswordHasher, Version, password_hash::SaltString};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 // Return on a better PC
-//use tera::Tera;
 use uuid::Uuid;
 use myweb::configuration::{get_configuration, DatabaseSettings};
+use myweb::confirmation_queue::try_send_confirmation_email;
+use myweb::email_client::EmailClient;
+use myweb::templates::Templates;
+use myweb::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
 use myweb::startup::{build, get_connection_pool};
-use myweb::telemetry::{get_subscriber, init_subscriber};
+use myweb::telemetry::{get_subscriber, init_subscriber, tracer, tracer_provider};
 use once_cell::sync::Lazy;
-use wiremock::MockServer;
+use opentelemetry::sdk::trace::TracerProvider;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
-static TRACING: Lazy<()> = Lazy::new(|| {
+// The provider `serve` uses without an OTLP endpoint: nothing is exported.
+// It has to outlive every test: its tracers only hold a weak reference.
+static TRACING: Lazy<TracerProvider> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
+    let provider = tracer_provider(None, subscriber_name.clone())
+        .expect("Failed to build the tracer provider.");
+    let tracer = Some(tracer(&provider));
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout, tracer);
         init_subscriber(subscriber);
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink, tracer);
         init_subscriber(subscriber);
     };
+    provider
 });
 
-// Return it after getting a better PC
-// Not sure if it even helps with something tho
-// static TEMPLATES: Lazy<()> = Lazy::new(|| {
-//     Tera::new("templates/**/*")
-//         .expect("Failed to parse templates");
-// });
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub role: &'static str,
 }
 
 impl TestUser {
+    /// An admin: they can do everything.
     pub fn generate() -> Self {
+        Self::with_role("admin")
+    }
+
+    pub fn with_role(role: &'static str) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
-            password: Uuid::new_v4().to_string()
+            password: Uuid::new_v4().to_string(),
+            role,
         }
     }
 
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut rand::thread_rng());
 
         // Match parameters of default password
@@ -56,11 +68,12 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-                VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role)
+                VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
             password_hash,
+            self.role,
         )
         .execute(pool)
         .await
@@ -78,6 +91,10 @@ pub struct TestApp {
     pub port: u16,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub templates: Templates,
+    pub base_url: String,
+    pub csrf_token: String,
 }
 
 impl TestApp {
@@ -85,7 +102,7 @@ impl TestApp {
     // we do not expose the underlying reqwest::Response
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -94,24 +111,206 @@ impl TestApp {
             .unwrap()
     }
 
+    /// A form body with the CSRF token that `csrf_middleware` expects.
+    pub fn with_csrf_token<Body>(&self, body: &Body) -> serde_json::Value
+    where
+        Body: serde::Serialize,
+    {
+        let mut body = serde_json::to_value(body).unwrap();
+        body["csrf_token"] = self.csrf_token.clone().into();
+        body
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             // This `reqwest` method makes sure that the body is URL-encoded
             // and the `Content-Type` header is set accordingly.
-            .form(body)
+            .form(&self.with_csrf_token(body))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_dashboard(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/dashboard", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_dashboard_html(&self) -> String {
+        self.get_admin_dashboard().await.text().await.unwrap()
+    }
+
+    pub async fn get_lockouts_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/lockouts", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_unlock(&self, username: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/lockouts/unlock", &self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({ "username": username })))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_change_password(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/password", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_change_password_html(&self) -> String {
+        self.get_change_password().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/password", &self.address))
+            .form(&self.with_csrf_token(body))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/logout", &self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({})))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn login_as_test_user(&self) {
+        self.login_as(&self.test_user).await;
+    }
+
+    pub async fn login_as(&self, user: &TestUser) {
+        let response = self.post_login(&serde_json::json!({
+            "username": &user.username,
+            "password": &user.password,
+        }))
+        .await;
+        assert_is_redirected_to(&response, "/admin/dashboard");
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users().await.text().await.unwrap()
+    }
+
+    /// `path` is relative to `/admin/users`, e.g. `/{user_id}/disable`.
+    pub async fn post_users<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users{}", &self.address, path))
+            .form(&self.with_csrf_token(body))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `path` is relative to `/admin/subscribers`, e.g. `?status=confirmed`.
+    pub async fn get_admin_subscribers(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `path` is relative to `/admin/subscribers`, e.g. `/{subscriber_id}/confirm`.
+    pub async fn post_admin_subscribers(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/subscribers{}", &self.address, path))
+            .form(&self.with_csrf_token(&serde_json::json!({})))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Upload `csv` to the import form, as a browser would.
+    pub async fn post_admin_subscribers_import(&self, status: &str, csv: &str) -> reqwest::Response {
+        let boundary = "----subscriber-import-boundary";
+        let body = format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"csrf_token\"\r\n\r\n\
+            {csrf_token}\r\n\
+            --{boundary}\r\n\
+            Content-Disposition: form-data; name=\"status\"\r\n\r\n\
+            {status}\r\n\
+            --{boundary}\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"subscribers.csv\"\r\n\
+            Content-Type: text/csv\r\n\r\n\
+            {csv}\r\n\
+            --{boundary}--\r\n",
+            csrf_token = self.csrf_token,
+        );
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .body(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
     pub async fn post_newsletter(&self, body: serde_json::Value) -> reqwest::Response {
+        self.post_newsletter_with_key(body, &Uuid::new_v4().to_string()).await
+    }
+
+    pub async fn post_newsletter_as(
+        &self,
+        user: &TestUser,
+        body: serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/newsletters", &self.address))
+            .basic_auth(&user.username, Some(&user.password))
+            .header("Idempotency-Key", Uuid::new_v4().to_string())
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_newsletter_with_key(
+        &self,
+        body: serde_json::Value,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/newsletters", &self.address))
+            .post(format!("{}/newsletters", &self.address))
             .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .header("Idempotency-Key", idempotency_key)
             .json(&body)
             .send()
             .await
@@ -120,13 +319,100 @@ impl TestApp {
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
-            .body(body)
+            .body(format!("{}&csrf_token={}", body, self.csrf_token))
             .send()
             .await
             .expect("Failed to execute request.")
     }
+
+    pub async fn post_api_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/v1/subscriptions", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_api_subscriptions_form(&self, body: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/v1/subscriptions", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(format!("{}&csrf_token={}", body, self.csrf_token))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Drain the delivery queue.
+    /// The background worker spawned by `build` may be holding some of the
+    /// tasks, so we also wait for the queue to be empty before returning.
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_execute_task(&self.db_pool, &self.email_client, &self.templates, &self.base_url)
+                    .await
+                    .unwrap()
+            {
+                let pending = sqlx::query!(
+                    r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue
+                        WHERE execute_after <= now()"#
+                )
+                .fetch_one(&self.db_pool)
+                .await
+                .unwrap()
+                .count;
+                if pending == 0 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        }
+    }
+
+    /// Drain the confirmation email queue, like `dispatch_all_pending_emails`.
+    pub async fn dispatch_all_confirmation_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_send_confirmation_email(
+                &self.db_pool,
+                &self.email_client,
+                &self.templates,
+                &self.base_url,
+            )
+            .await
+            .unwrap()
+            {
+                let pending = sqlx::query!(
+                    r#"SELECT COUNT(*) AS "count!" FROM confirmation_email_queue
+                        WHERE execute_after <= now()"#
+                )
+                .fetch_one(&self.db_pool)
+                .await
+                .unwrap()
+                .count;
+                if pending == 0 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        }
+    }
+
+    /// Wait for the email server to have received `n` requests, for
+    /// emails that handlers send in the background, and return them.
+    pub async fn wait_for_emails(&self, n: usize) -> Vec<wiremock::Request> {
+        for _ in 0..100 {
+            let received = self.email_server.received_requests().await.unwrap();
+            if received.len() >= n {
+                return received;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("The email server did not receive {} requests in time.", n);
+    }
+
     pub fn get_confirmation_links(
         &self,
         email_request: &wiremock::Request
@@ -147,15 +433,14 @@ impl TestApp {
             confirmation_link
         };
     
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
-        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
         ConfirmationLinks { html, plain_text }
     }
 }
 
 pub async fn spawn_app() -> TestApp {
     Lazy::force(&TRACING);
-    //Lazy::force(&TEMPLATES);
 
     let email_server = MockServer::start().await;
     let configuration = {
@@ -163,6 +448,19 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Failed deliveries are retried by the issue delivery queue,
+        // tests expect a single request per attempt.
+        c.email_client.max_retries = 0;
+        // Keep failed logins from slowing the test suite down.
+        c.login_throttle.base_delay_milliseconds = 10;
+        c.login_throttle.max_delay_milliseconds = 50;
+        // Keep the configured bursts, but never refill a token during a
+        // test: a slow run must not slip an extra request through.
+        for limits in c.application.rate_limits.values_mut() {
+            for settings in [&mut limits.per_ip, &mut limits.per_email].into_iter().flatten() {
+                settings.period_seconds = 3600;
+            }
+        }
         c
     };
     configure_database(&configuration.database).await;
@@ -177,7 +475,18 @@ pub async fn spawn_app() -> TestApp {
         .build()
         .unwrap();
 
-    let _ = tokio::spawn(server);
+    tokio::spawn(server);
+
+    // Any page with a form sets the CSRF cookie in the client's cookie store.
+    let login_page = client
+        .get(format!("{}/login", &address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    let csrf_token = extract_csrf_token(&login_page);
 
     let test_app = TestApp {
         address,
@@ -186,6 +495,10 @@ pub async fn spawn_app() -> TestApp {
         port,
         test_user: TestUser::generate(),
         api_client: client,
+        email_client: configuration.email_client.client(),
+        templates: Templates::new(false).expect("Failed to load the templates"),
+        base_url: configuration.application.base_url,
+        csrf_token,
     };
 
     test_app.test_user.store(&test_app.db_pool).await;
@@ -213,7 +526,54 @@ pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
     connection_pool
 }
 
+/// Use public API of the application under test to create
+/// an unconfirmed subscriber
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // We now inspect the requests received by the mock Postmark server
+    // to retrieve confirmation link and return it
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(email_request)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    // We can reuse the sampler helper and just add
+    // an extra step to actually call the confirmation link!
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
 pub fn assert_is_redirected_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
 }
+
+pub fn extract_csrf_token(html: &str) -> String {
+    let (_, rest) = html
+        .split_once(r#"name="csrf_token" value=""#)
+        .expect("The page has no CSRF token.");
+    rest.split('"').next().unwrap().to_owned()
+}
@@ -1,4 +1,9 @@
+use axum::response::IntoResponse;
+use axum_extra::extract::cookie::{Cookie, Key, SignedCookieJar};
+use base64::Engine;
 use crate::helpers::{spawn_app, assert_is_redirected_to};
+use myweb::configuration::get_configuration;
+use myweb::startup::HmacSecret;
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -20,13 +25,92 @@ async fn an_error_flash_message_is_set_on_failure() {
         .cookies()
         .find(|c| c.name() == "_flash")
         .unwrap();
-    assert_eq!(flash_cookie.value(), "Authentication%20failed");
+    // The message is signed, so it is not stored as plain text
+    assert_ne!(flash_cookie.value(), "Authentication%20failed");
 
     // Act 2: Follow the redirect
     let html_page = app.get_login_html().await;
-    assert!(html_page.contains(r#"<p><i>Authentication failed</i></p>"#));
+    assert!(html_page.contains(r#"<p class="flash-error"><i>Authentication failed</i></p>"#));
 
     // Act 3: Reload the login page
     let html_page = app.get_login_html().await;
-    assert!(!html_page.contains(r#"<p><i>Authentication failed</i></p>"#));
-}
\ No newline at end of file
+    assert!(!html_page.contains("Authentication failed"));
+}
+#[tokio::test]
+async fn redirect_to_admin_dashboard_after_login_success() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - Login
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+    let response = app.post_login(&login_body).await;
+    assert_is_redirected_to(&response, "/admin/dashboard");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn forged_flash_messages_are_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Set a `_flash` cookie that was not signed by the server
+    let html_page = reqwest::Client::new()
+        .get(format!("{}/login", &app.address))
+        .header("Cookie", "_flash=<script>alert('pwned')</script>")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!html_page.contains("<script>"));
+    assert!(!html_page.contains("pwned"));
+}
+
+/// The `Cookie` header value of a `_flash` cookie signed with `key`,
+/// carrying a single error message.
+fn signed_flash_cookie(key: Key, content: &str) -> String {
+    let messages = serde_json::json!([{ "level": "error", "content": content }]);
+    let value = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(messages.to_string());
+    let response = SignedCookieJar::new(key)
+        .add(Cookie::new("_flash", value))
+        .into_response();
+    let set_cookie = response.headers()["Set-Cookie"].to_str().unwrap();
+    set_cookie.split(';').next().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn flash_messages_signed_with_another_key_are_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+    let configuration = get_configuration().expect("Failed to read configuration");
+    let own_key = HmacSecret(configuration.application.hmac_secret).cookie_key();
+    let foreign_key = HmacSecret(secrecy::Secret::new("a".repeat(64))).cookie_key();
+    let get_login_page = |cookie: String| async {
+        reqwest::Client::new()
+            .get(format!("{}/login", &app.address))
+            .header("Cookie", cookie)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    };
+
+    // Act
+    let genuine_page = get_login_page(signed_flash_cookie(own_key, "Genuine")).await;
+    let forged_page = get_login_page(signed_flash_cookie(foreign_key, "Forged")).await;
+
+    // Assert - Only the signature sets the two cookies apart
+    assert!(genuine_page.contains("Genuine"));
+    assert!(!forged_page.contains("Forged"));
+}
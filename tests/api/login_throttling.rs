@@ -0,0 +1,226 @@
+use std::time::Duration;
+use myweb::authentication::delete_stale_login_failures;
+use uuid::Uuid;
+use crate::helpers::{assert_is_redirected_to, spawn_app, TestApp, TestUser};
+
+// Matches `login_throttle.max_failures` in `configuration/base.yaml`.
+const MAX_FAILURES: usize = 5;
+
+async fn fail_to_log_in(app: &TestApp, username: &str, n_times: usize) {
+    for _ in 0..n_times {
+        let response = app.post_login(&serde_json::json!({
+            "username": username,
+            "password": "wrong-password",
+        }))
+        .await;
+        assert_is_redirected_to(&response, "/login");
+    }
+}
+
+async fn lockout_exists(app: &TestApp, username: &str) -> bool {
+    sqlx::query!(
+        "SELECT username FROM account_lockouts WHERE username = $1 AND locked_until > now()",
+        username
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .is_some()
+}
+
+#[tokio::test]
+async fn an_account_is_locked_after_too_many_failed_logins() {
+    // Arrange
+    let app = spawn_app().await;
+    fail_to_log_in(&app, &app.test_user.username, MAX_FAILURES).await;
+
+    // Act - Part 1 - The right password does not help anymore
+    let response = app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    assert_is_redirected_to(&response, "/login");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p class=\"flash-error\"><i>Too many failed login attempts, try again later.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_locked_account_cannot_publish_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    fail_to_log_in(&app, &app.test_user.username, MAX_FAILURES).await;
+
+    // Act
+    let response = app.post_newsletter(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0);
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_locked_as_well() {
+    // Arrange
+    let app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+
+    // Act
+    fail_to_log_in(&app, &username, MAX_FAILURES).await;
+
+    // Assert
+    assert!(lockout_exists(&app, &username).await);
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failure_count() {
+    // Arrange
+    let app = spawn_app().await;
+    fail_to_log_in(&app, &app.test_user.username, MAX_FAILURES - 1).await;
+    app.login_as_test_user().await;
+
+    // Act
+    fail_to_log_in(&app, &app.test_user.username, MAX_FAILURES - 1).await;
+
+    // Assert
+    assert!(!lockout_exists(&app, &app.test_user.username).await);
+}
+
+#[tokio::test]
+async fn failed_logins_are_audit_logged() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    fail_to_log_in(&app, &app.test_user.username, MAX_FAILURES).await;
+
+    // Assert
+    let events = sqlx::query!(
+        "SELECT event, ip_address FROM audit_log WHERE username = $1 ORDER BY occurred_at",
+        &app.test_user.username
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let n_failed = events.iter().filter(|e| e.event == "login_failed").count();
+    assert_eq!(n_failed, MAX_FAILURES);
+    assert_eq!(events.last().unwrap().event, "account_locked");
+    assert!(events.iter().all(|e| e.ip_address.as_deref() == Some("127.0.0.1")));
+}
+
+#[tokio::test]
+async fn an_admin_can_unlock_an_account() {
+    // Arrange
+    let app = spawn_app().await;
+    let locked_user = TestUser::generate();
+    locked_user.store(&app.db_pool).await;
+    fail_to_log_in(&app, &locked_user.username, MAX_FAILURES).await;
+    app.login_as_test_user().await;
+
+    // Act - Part 1 - The account is listed
+    let html_page = app.get_lockouts_html().await;
+    assert!(html_page.contains(&locked_user.username));
+
+    // Act - Part 2 - Unlock it
+    let response = app.post_unlock(&locked_user.username).await;
+    assert_is_redirected_to(&response, "/admin/lockouts");
+    let html_page = app.get_lockouts_html().await;
+    assert!(html_page.contains(&format!("<p class=\"flash-info\"><i>{} has been unlocked.</i></p>", locked_user.username)));
+    assert!(html_page.contains("No account is locked."));
+
+    // Assert
+    assert!(!lockout_exists(&app, &locked_user.username).await);
+    let unlocked_by = sqlx::query!(
+        "SELECT actor_user_id FROM audit_log WHERE event = 'account_unlocked' AND username = $1",
+        &locked_user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .actor_user_id;
+    assert_eq!(unlocked_by, Some(app.test_user.user_id));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_unlock_accounts() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_unlock(&app.test_user.username).await;
+
+    // Assert
+    assert_is_redirected_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn stale_failed_login_attempts_are_cleaned_up() {
+    // Arrange
+    let app = spawn_app().await;
+    fail_to_log_in(&app, "nobody-by-that-name", 2).await;
+    sqlx::query!("UPDATE failed_login_attempts SET attempted_at = now() - interval '1 day'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    fail_to_log_in(&app, "nobody-by-that-name", 1).await;
+
+    // Act
+    let n_deleted = delete_stale_login_failures(&app.db_pool, Duration::from_secs(900))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(n_deleted, 2);
+    let remaining = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM failed_login_attempts")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.count, 1);
+}
+
+#[tokio::test]
+async fn concurrent_guesses_cannot_get_past_the_lockout() {
+    // Arrange
+    let app = spawn_app().await;
+    let n_guesses = 2 * MAX_FAILURES;
+
+    // Act - All the guesses are in flight at once
+    let guesses: Vec<_> = (0..n_guesses)
+        .map(|_| {
+            let request = app.api_client
+                .post(format!("{}/newsletters", &app.address))
+                .basic_auth(&app.test_user.username, Some("wrong-password"))
+                .header("Idempotency-Key", Uuid::new_v4().to_string())
+                .json(&serde_json::json!({
+                    "title": "Newsletter title",
+                    "content": { "text": "Plain text", "html": "<p>HTML</p>" },
+                }));
+            tokio::spawn(request.send())
+        })
+        .collect();
+    let mut statuses = Vec::new();
+    for guess in guesses {
+        statuses.push(guess.await.unwrap().expect("Failed to execute request.").status().as_u16());
+    }
+
+    // Assert - Only `MAX_FAILURES` of them were checked
+    let n_checked = statuses.iter().filter(|&&status| status == 401).count();
+    let n_refused = statuses.iter().filter(|&&status| status == 429).count();
+    assert_eq!(n_checked, MAX_FAILURES, "{:?}", statuses);
+    assert_eq!(n_refused, n_guesses - MAX_FAILURES, "{:?}", statuses);
+    assert!(lockout_exists(&app, &app.test_user.username).await);
+}
@@ -1,6 +1,22 @@
 mod helpers;
+mod api_docs;
 mod health_check;
 mod subscriptions;
+mod subscriptions_api;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod telemetry;
 mod newsletter;
-mod login;
\ No newline at end of file
+mod rate_limiting;
+mod login;
+mod login_throttling;
+mod metrics;
+mod admin_dashboard;
+mod blog;
+mod change_password;
+mod csrf;
+mod reviews;
+mod roles;
+mod cli;
+mod admin_subscribers;
+mod privacy;
@@ -0,0 +1,82 @@
+use crate::helpers::spawn_app;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn metrics_are_exposed_in_prometheus_format() {
+    // Arrange
+    let app = spawn_app().await;
+    app.api_client
+        .get(format!("{}/blog/{}", &app.address, Uuid::new_v4()))
+        .send()
+        .await
+        .unwrap();
+    app.get_admin_dashboard().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/metrics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("Content-Type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = response.text().await.unwrap();
+    // Requests are labelled by route template, not by raw path
+    assert!(body.contains(r#"http_requests_total{method="GET",route="/blog/:issue_id",status="404"}"#));
+    assert!(body.contains(r#"http_requests_total{method="GET",route="/admin/dashboard",status="303"}"#));
+    assert!(body.contains(r#"http_request_duration_seconds_bucket{method="GET",route="/blog/:issue_id",status="404""#));
+    for counter in [
+        "subscriptions_created_total",
+        "subscriptions_confirmed_total",
+        "newsletters_published_total",
+        "emails_sent_total",
+        "emails_failed_total",
+        "login_failures_total",
+    ] {
+        assert!(body.contains(&format!("# TYPE {} counter", counter)), "{} is missing", counter);
+    }
+}
+
+#[tokio::test]
+async fn failed_logins_are_counted() {
+    // Arrange
+    let app = spawn_app().await;
+    let failures_before = login_failures(&app).await;
+
+    // Act
+    app.post_login(&serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    }))
+    .await;
+
+    // Assert
+    // Counters are shared by every app spawned by the test suite
+    assert!(login_failures(&app).await > failures_before);
+}
+
+async fn login_failures(app: &crate::helpers::TestApp) -> u64 {
+    let body = app
+        .api_client
+        .get(format!("{}/metrics", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    body.lines()
+        .find_map(|line| line.strip_prefix("login_failures_total "))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,230 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const SUBSCRIBER_EMAIL: &str = "ursula_le_guin@gmail.com";
+
+async fn post_privacy_request(app: &TestApp, email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/privacy/request", &app.address))
+        .form(&app.with_csrf_token(&serde_json::json!({ "email": email })))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Ask for a privacy link as the subscriber and return its token.
+async fn get_privacy_token(app: &TestApp) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let n_received = app.email_server.received_requests().await.unwrap().len();
+    post_privacy_request(app, SUBSCRIBER_EMAIL)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = app.wait_for_emails(n_received + 1).await.pop().unwrap();
+    let link = app.get_confirmation_links(&email_request).html;
+    assert_eq!(link.path(), "/privacy/manage");
+    link.query_pairs().find(|(key, _)| key == "token").unwrap().1.into_owned()
+}
+
+async fn get_export(app: &TestApp, token: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/privacy/export?token={}", &app.address, token))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn post_erase(app: &TestApp, token: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/privacy/erase?token={}", &app.address, token))
+        .form(&app.with_csrf_token(&serde_json::json!({})))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn count_rows(app: &TestApp, table: &str) -> i64 {
+    sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {}", table))
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn unknown_addresses_get_the_same_answer_and_no_email() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = post_privacy_request(&app, "nobody@example.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("If we know this address"));
+}
+
+#[tokio::test]
+async fn email_failures_get_the_same_answer() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = post_privacy_request(&app, SUBSCRIBER_EMAIL).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("If we know this address"));
+}
+
+#[tokio::test]
+async fn the_export_holds_the_subscription_tokens_and_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = get_privacy_token(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>",
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    let response = get_export(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment;"));
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["subscriber"]["email"], SUBSCRIBER_EMAIL);
+    assert_eq!(export["subscriber"]["status"], "confirmed");
+    assert_eq!(export["confirmation_tokens"].as_array().unwrap().len(), 1);
+    assert!(export["confirmation_tokens"][0].get("subscription_token").is_none());
+    assert_eq!(export["deliveries"][0]["title"], "Newsletter title");
+    assert_eq!(export["deliveries"][0]["outcome"], "delivered");
+    let n_exports = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM audit_log WHERE event = 'subscriber_data_exported'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(n_exports, 1);
+}
+
+#[tokio::test]
+async fn erasure_removes_the_subscriber_from_every_table() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = get_privacy_token(&app).await;
+
+    // Act
+    let response = post_erase(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    for table in ["subscriptions", "subscription_tokens", "privacy_tokens", "issue_deliveries"] {
+        assert_eq!(count_rows(&app, table).await, 0, "{} still has rows.", table);
+    }
+    let details = sqlx::query!(
+        r#"SELECT details FROM audit_log WHERE event = 'subscriber_erased'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .details
+    .unwrap();
+    assert!(!details.contains(SUBSCRIBER_EMAIL));
+}
+
+#[tokio::test]
+async fn privacy_links_stop_working_after_erasure() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = get_privacy_token(&app).await;
+    post_erase(&app, &token).await.error_for_status().unwrap();
+
+    // Act
+    let export = get_export(&app, &token).await;
+    let erase = post_erase(&app, &token).await;
+
+    // Assert
+    assert_eq!(export.status().as_u16(), 401);
+    assert_eq!(erase.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn expired_privacy_links_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = get_privacy_token(&app).await;
+    sqlx::query!("UPDATE privacy_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let export = get_export(&app, &token).await;
+    let erase = post_erase(&app, &token).await;
+
+    // Assert
+    assert_eq!(export.status().as_u16(), 410);
+    assert_eq!(erase.status().as_u16(), 410);
+    assert_eq!(count_rows(&app, "subscriptions").await, 1);
+}
+
+#[tokio::test]
+async fn erasing_requires_a_csrf_token() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = get_privacy_token(&app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/privacy/erase?token={}", &app.address, token))
+        .form(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(count_rows(&app, "subscriptions").await, 1);
+}
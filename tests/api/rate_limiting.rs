@@ -0,0 +1,131 @@
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use crate::helpers::spawn_app;
+
+// Match `application.rate_limits` in `configuration/base.yaml`;
+// `spawn_app` stretches their periods so that no token comes back mid-test.
+const PER_EMAIL_BURST: usize = 3;
+const PER_IP_BURST: usize = 10;
+
+#[tokio::test]
+async fn subscribing_the_same_email_too_often_is_rate_limited() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(PER_EMAIL_BURST as u64)
+        .mount(&app.email_server)
+        .await;
+    // The same address, however it is spelled
+    let emails = ["ursula_le_guin%40gmail.com", "Ursula_Le_Guin%40gmail.com", "ursula_le_guin%40GMAIL.com"];
+    for email in emails {
+        let response = app.post_subscriptions(format!("name=le%20guin&email={}", email)).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Act
+    let response = app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0);
+    // Mock verifies on Drop that no more emails were sent
+}
+
+#[tokio::test]
+async fn too_many_subscriptions_from_the_same_ip_address_are_rate_limited() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    for i in 0..PER_IP_BURST {
+        let response = app.post_subscriptions(format!("name=le%20guin&email=ursula{}%40gmail.com", i)).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Act
+    let response = app.post_subscriptions("name=le%20guin&email=someone_else%40gmail.com".into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "Too many requests, try again later.");
+}
+
+#[tokio::test]
+async fn review_link_requests_are_rate_limited_by_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let request = || {
+        app.api_client
+            .post(format!("{}/reviews/request", &app.address))
+            .form(&app.with_csrf_token(&serde_json::json!({ "email": "ursula_le_guin@gmail.com" })))
+            .send()
+    };
+    for _ in 0..PER_EMAIL_BURST {
+        let response = request().await.unwrap();
+        assert_ne!(response.status().as_u16(), 429);
+    }
+
+    // Act
+    let response = request().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn both_subscription_endpoints_share_the_limit_by_email() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(PER_EMAIL_BURST as u64)
+        .mount(&app.email_server)
+        .await;
+    for _ in 0..PER_EMAIL_BURST - 1 {
+        let response = app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into()).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    let body = serde_json::json!({ "name": "le guin", "email": "Ursula_Le_Guin@gmail.com" });
+    let response = app.post_api_subscriptions_json(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act
+    let response = app.post_api_subscriptions_json(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn oversized_bodies_are_rejected_before_looking_for_the_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "name": "a".repeat(3 * 1024 * 1024),
+        "email": "ursula_le_guin@gmail.com",
+    });
+
+    // Act
+    let response = app.api_client
+        .post(format!("{}/api/v1/subscriptions", &app.address))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 413);
+}
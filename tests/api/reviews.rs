@@ -0,0 +1,302 @@
+use crate::helpers::{spawn_app, assert_is_redirected_to, create_confirmed_subscriber, TestApp};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const SUBSCRIBER_EMAIL: &str = "ursula_le_guin@gmail.com";
+
+async fn post_review_request(app: &TestApp, email: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/reviews/request", &app.address))
+        .form(&app.with_csrf_token(&serde_json::json!({ "email": email })))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Ask for a review link as the confirmed subscriber and return it.
+async fn get_review_link(app: &TestApp) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let n_received = app.email_server.received_requests().await.unwrap().len();
+    post_review_request(app, SUBSCRIBER_EMAIL)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = app.wait_for_emails(n_received + 1).await.pop().unwrap();
+    app.get_confirmation_links(&email_request).html
+}
+
+async fn post_review(app: &TestApp, link: &reqwest::Url, rating: &str, content: &str) -> reqwest::Response {
+    app.api_client
+        .post(link.clone())
+        .form(&app.with_csrf_token(&serde_json::json!({ "rating": rating, "content": content })))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_reviews_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/reviews", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn submit_pending_review(app: &TestApp, rating: &str, content: &str) -> uuid::Uuid {
+    let link = get_review_link(app).await;
+    post_review(app, &link, rating, content)
+        .await
+        .error_for_status()
+        .unwrap();
+    sqlx::query!("SELECT review_id FROM reviews WHERE status = 'pending'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .review_id
+}
+
+async fn moderate(app: &TestApp, review_id: uuid::Uuid, decision: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/reviews/{}/{}", &app.address, review_id, decision))
+        .form(&app.with_csrf_token(&serde_json::json!({})))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_receive_a_review_link() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let link = get_review_link(&app).await;
+
+    // Assert
+    assert_eq!(link.path(), "/reviews/new");
+    let response = app.api_client.get(link).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(r#"name="rating""#));
+}
+
+#[tokio::test]
+async fn unknown_addresses_get_the_same_answer_but_no_email() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = post_review_request(&app, "nobody@example.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("If this address is subscribed"));
+}
+
+#[tokio::test]
+async fn email_failures_get_the_same_answer() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = post_review_request(&app, SUBSCRIBER_EMAIL).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("If this address is subscribed"));
+}
+
+#[tokio::test]
+async fn submitted_reviews_are_held_for_moderation() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    submit_pending_review(&app, "4", "A lovely newsletter.").await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT rating, content, status FROM reviews")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the saved review.");
+    assert_eq!(saved.rating, 4);
+    assert_eq!(saved.content, "A lovely newsletter.");
+    assert_eq!(saved.status, "pending");
+    assert!(!get_reviews_html(&app).await.contains("A lovely newsletter."));
+}
+
+#[tokio::test]
+async fn a_review_link_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let link = get_review_link(&app).await;
+    post_review(&app, &link, "5", "Great!").await.error_for_status().unwrap();
+
+    // Act
+    let response = post_review(&app, &link, "1", "Changed my mind.").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn invalid_reviews_are_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let link = get_review_link(&app).await;
+    let test_cases = vec![
+        ("0", "Some text", "rating too low"),
+        ("6", "Some text", "rating too high"),
+        ("3", "   ", "empty review"),
+    ];
+
+    for (rating, content, description) in test_cases {
+        // Act
+        let response = post_review(&app, &link, rating, content).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn unknown_or_expired_review_links_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let link = get_review_link(&app).await;
+
+    // Act - Part 1 - Unknown token
+    let response = app
+        .api_client
+        .get(format!("{}/reviews/new?token=doesnotexist", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Act - Part 2 - Expired token
+    sqlx::query!("UPDATE review_tokens SET expires_at = now() - interval '1 hour'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = post_review(&app, &link, "5", "Great!").await;
+    assert_eq!(response.status().as_u16(), 410);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_moderate_reviews() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/reviews", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirected_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn approved_reviews_are_listed_with_the_average_rating() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let first = submit_pending_review(&app, "5", "Great <b>stuff</b>!").await;
+    app.login_as_test_user().await;
+
+    // Act - Part 1 - The review shows up in the moderation queue
+    let queue_html = app
+        .api_client
+        .get(format!("{}/admin/reviews", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(queue_html.contains("Great &lt;b&gt;stuff&lt;/b&gt;!"));
+
+    // Act - Part 2 - Approve it
+    let response = moderate(&app, first, "approve").await;
+    assert_is_redirected_to(&response, "/admin/reviews");
+    let second = submit_pending_review(&app, "2", "Meh.").await;
+    moderate(&app, second, "approve").await;
+
+    // Act - Part 3 - Both reviews are public
+    let html_page = get_reviews_html(&app).await;
+    assert!(html_page.contains("Great &lt;b&gt;stuff&lt;/b&gt;!"));
+    assert!(html_page.contains("Meh."));
+    assert!(html_page.contains("Average rating: 3.5 / 5 (2 reviews)"));
+
+    // Assert
+    let saved = sqlx::query!("SELECT moderated_by FROM reviews WHERE review_id = $1", first)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.moderated_by, Some(app.test_user.user_id));
+}
+
+#[tokio::test]
+async fn rejected_reviews_are_not_listed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let review_id = submit_pending_review(&app, "1", "Spam spam spam.").await;
+    app.login_as_test_user().await;
+
+    // Act - Part 1 - Reject it
+    let response = moderate(&app, review_id, "reject").await;
+    assert_is_redirected_to(&response, "/admin/reviews");
+
+    // Act - Part 2 - Follow the redirect
+    let queue_html = app
+        .api_client
+        .get(format!("{}/admin/reviews", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(queue_html.contains("<p class=\"flash-info\"><i>The review has been rejected.</i></p>"));
+    assert!(queue_html.contains("There are no reviews to moderate."));
+
+    // Act - Part 3 - Moderating it again is a no-op
+    moderate(&app, review_id, "approve").await;
+
+    // Assert
+    assert!(!get_reviews_html(&app).await.contains("Spam spam spam."));
+}
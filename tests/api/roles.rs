@@ -0,0 +1,217 @@
+use uuid::Uuid;
+use crate::helpers::{assert_is_redirected_to, spawn_app, TestApp, TestUser};
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+async fn store_user(app: &TestApp, role: &'static str) -> TestUser {
+    let user = TestUser::with_role(role);
+    user.store(&app.db_pool).await;
+    user
+}
+
+#[tokio::test]
+async fn viewers_cannot_publish_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = store_user(&app, "viewer").await;
+
+    // Act
+    let response = app.post_newsletter_as(&viewer, newsletter_request_body()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn editors_can_publish_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = store_user(&app, "editor").await;
+
+    // Act
+    let response = app.post_newsletter_as(&editor, newsletter_request_body()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[tokio::test]
+async fn only_admins_can_manage_users() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = store_user(&app, "editor").await;
+    app.login_as(&editor).await;
+
+    // Act - Part 1 - Pages
+    let users_page = app.get_users().await;
+    let dashboard = app.get_admin_dashboard_html().await;
+
+    // Act - Part 2 - Forms
+    let response = app.post_users("", &serde_json::json!({
+        "username": Uuid::new_v4().to_string(),
+        "password": Uuid::new_v4().to_string(),
+        "role": "admin",
+    }))
+    .await;
+
+    // Assert
+    assert_eq!(users_page.status().as_u16(), 403);
+    assert!(!dashboard.contains(r#"href="/admin/users""#));
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn an_admin_can_add_a_user_who_can_then_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+    let new_user = TestUser::with_role("viewer");
+
+    // Act - Part 1 - Add the user
+    let response = app.post_users("", &serde_json::json!({
+        "username": &new_user.username,
+        "password": &new_user.password,
+        "role": "viewer",
+    }))
+    .await;
+    assert_is_redirected_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!("<p class=\"flash-info\"><i>{} has been added.</i></p>", new_user.username)));
+
+    // Act - Part 2 - Log in as the new user
+    app.post_logout().await;
+    app.login_as(&new_user).await;
+
+    // Assert
+    let dashboard = app.get_admin_dashboard_html().await;
+    assert!(dashboard.contains("You are logged in as viewer."));
+}
+
+#[tokio::test]
+async fn usernames_must_be_unique() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app.post_users("", &serde_json::json!({
+        "username": &app.test_user.username,
+        "password": Uuid::new_v4().to_string(),
+        "role": "viewer",
+    }))
+    .await;
+
+    // Assert
+    assert_is_redirected_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!("<p class=\"flash-error\"><i>{} is already taken.</i></p>", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn an_admin_can_change_the_role_of_a_user() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = store_user(&app, "viewer").await;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app
+        .post_users(&format!("/{}/role", user.user_id), &serde_json::json!({ "role": "editor" }))
+        .await;
+
+    // Assert
+    assert_is_redirected_to(&response, "/admin/users");
+    let response = app.post_newsletter_as(&user, newsletter_request_body()).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let changed_by = sqlx::query!(
+        "SELECT actor_user_id FROM audit_log WHERE event = 'user_role_changed' AND username = $1",
+        &user.username
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .actor_user_id;
+    assert_eq!(changed_by, Some(app.test_user.user_id));
+}
+
+#[tokio::test]
+async fn disabled_users_cannot_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = store_user(&app, "editor").await;
+    app.login_as_test_user().await;
+
+    // Act
+    let response = app
+        .post_users(&format!("/{}/disable", user.user_id), &serde_json::json!({}))
+        .await;
+    assert_is_redirected_to(&response, "/admin/users");
+    app.post_logout().await;
+
+    // Assert
+    let response = app.post_login(&serde_json::json!({
+        "username": &user.username,
+        "password": &user.password,
+    }))
+    .await;
+    assert_is_redirected_to(&response, "/login");
+    let response = app.post_newsletter_as(&user, newsletter_request_body()).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn disabled_users_lose_their_session() {
+    // Arrange
+    let app = spawn_app().await;
+    let user = store_user(&app, "editor").await;
+    app.login_as(&user).await;
+
+    // Act
+    sqlx::query!("UPDATE users SET disabled_at = now() WHERE user_id = $1", user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirected_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p class=\"flash-error\"><i>Your account has been disabled.</i></p>"));
+    // The session is gone, re-enabling the account does not log them back in.
+    sqlx::query!("UPDATE users SET disabled_at = NULL WHERE user_id = $1", user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    assert_is_redirected_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn admins_cannot_lock_themselves_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as_test_user().await;
+    let own_path = format!("/{}", app.test_user.user_id);
+
+    // Act
+    app.post_users(&format!("{}/disable", own_path), &serde_json::json!({})).await;
+    app.post_users(&format!("{}/role", own_path), &serde_json::json!({ "role": "viewer" })).await;
+
+    // Assert
+    let row = sqlx::query!(
+        "SELECT role, disabled_at FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(row.role, "admin");
+    assert!(row.disabled_at.is_none());
+}
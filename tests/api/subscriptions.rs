@@ -20,7 +20,7 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
 }
 
 #[tokio::test]
-async fn pressing_subscribe_again_sends_confirmation_email() {
+async fn pressing_subscribe_again_sends_a_new_confirmation_link() {
     // Arrange
     let app = spawn_app().await;
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
@@ -46,13 +46,23 @@ async fn pressing_subscribe_again_sends_confirmation_email() {
 
     // Get the first intercepted request
     let first_request = &requests[0];
-    let first_confirmation_links = app.get_confirmation_links(&first_request);
+    let first_confirmation_links = app.get_confirmation_links(first_request);
     // Get the second intercepted request
     let second_request = &requests[1];
-    let second_confirmation_links = app.get_confirmation_links(&second_request);
-    // The links should be indentical
-    assert_eq!(first_confirmation_links.html, second_confirmation_links.plain_text);
-    assert_eq!(first_confirmation_links.plain_text, second_confirmation_links.html);
+    let second_confirmation_links = app.get_confirmation_links(second_request);
+    // Re-subscribing rotates the token
+    assert_ne!(first_confirmation_links.html, second_confirmation_links.html);
+    assert_eq!(second_confirmation_links.html, second_confirmation_links.plain_text);
+    // and the old link stops working
+    let first_link_response = reqwest::get(first_confirmation_links.html)
+        .await
+        .unwrap();
+    assert_eq!(first_link_response.status().as_u16(), 401);
+    reqwest::get(second_confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
 }
 
 #[tokio::test]
@@ -189,7 +199,7 @@ async fn subscribe_sends_a_confirmation_email_with_link() {
     // Assert
     // Get the first intercepted request
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     // The two links should be indentical
     assert_eq!(confirmation_links.html, confirmation_links.plain_text);
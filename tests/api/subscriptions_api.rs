@@ -0,0 +1,197 @@
+use wiremock::{ResponseTemplate, Mock, matchers::{path, method}};
+use crate::helpers::{spawn_app, TestApp};
+
+async fn mock_email_server(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+}
+
+fn field_codes(body: &serde_json::Value) -> Vec<(String, String)> {
+    body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| {
+            (
+                error["field"].as_str().unwrap().to_owned(),
+                error["code"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn subscribing_with_json_persists_the_subscriber_and_sends_a_confirmation() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_api_subscriptions_json(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "pending_confirmation");
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribing_with_a_form_works_too() {
+    // Arrange
+    let app = spawn_app().await;
+    mock_email_server(&app).await;
+
+    // Act
+    let response = app
+        .post_api_subscriptions_form("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "pending_confirmation");
+}
+
+#[tokio::test]
+async fn every_invalid_field_is_reported_with_its_code() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "name": "", "email": "ursula_le_guin@gmail.com" }),
+            vec![("name", "name_empty")],
+        ),
+        (
+            serde_json::json!({ "name": "a".repeat(257), "email": "ursula_le_guin@gmail.com" }),
+            vec![("name", "name_too_long")],
+        ),
+        (
+            serde_json::json!({ "name": "<Ursula>", "email": "ursula_le_guin@gmail.com" }),
+            vec![("name", "name_forbidden_characters")],
+        ),
+        (
+            serde_json::json!({ "name": "le guin", "email": "definitely-not-an-email" }),
+            vec![("email", "email_invalid")],
+        ),
+        (
+            serde_json::json!({ "name": " ", "email": "" }),
+            vec![("name", "name_empty"), ("email", "email_invalid")],
+        ),
+    ];
+
+    for (body, expected) in test_cases {
+        // Act
+        let response = app.post_api_subscriptions_json(&body).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "The payload was {}.", body);
+        let body: serde_json::Value = response.json().await.unwrap();
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(field, code)| (field.to_owned(), code.to_owned()))
+            .collect();
+        assert_eq!(field_codes(&body), expected);
+    }
+    let n_subscribers = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn form_fields_are_validated_the_same_way() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_api_subscriptions_form("name=&email=not-an-email").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        field_codes(&body),
+        vec![
+            ("name".to_owned(), "name_empty".to_owned()),
+            ("email".to_owned(), "email_invalid".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn missing_fields_are_rejected_in_json() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_api_subscriptions_json(&serde_json::json!({ "name": "le guin" }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 422);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("email"));
+}
+
+#[tokio::test]
+async fn malformed_json_is_rejected_in_json() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/api/v1/subscriptions", &app.address))
+        .header("Content-Type", "application/json")
+        .body(r#"{"name": "le guin","#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"].is_string());
+}
+
+#[tokio::test]
+async fn other_content_types_are_not_supported() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/api/v1/subscriptions", &app.address))
+        .header("Content-Type", "application/xml")
+        .body("<subscription><name>le guin</name></subscription>")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 415);
+}
@@ -0,0 +1,83 @@
+use myweb::telemetry::{get_subscriber, tracer, tracer_provider};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_callers_trace_context_is_forwarded_to_the_email_api() {
+    // Arrange
+    let app = spawn_app().await;
+    let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("traceparent", format!("00-{}-00f067aa0ba902b7-01", trace_id))
+        .body(format!("name=le%20guin&email=ursula_le_guin%40gmail.com&csrf_token={}", app.csrf_token))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let traceparent = header(email_request, "traceparent")
+        .expect("The email request carries no trace context.");
+    // Same trace, but the parent is one of our own spans
+    assert!(traceparent.starts_with(&format!("00-{}-", trace_id)));
+    assert!(!traceparent.contains("00f067aa0ba902b7"));
+}
+
+#[tokio::test]
+async fn spans_are_exported_to_the_otlp_collector() {
+    // Arrange
+    let collector = MockServer::start().await;
+    Mock::given(path("/v1/traces"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&collector)
+        .await;
+    let provider = tracer_provider(
+        Some(&format!("{}/v1/traces", collector.uri())),
+        "test".into(),
+    )
+    .expect("Failed to build the OTLP exporter.");
+    let subscriber = get_subscriber(
+        "test".into(),
+        "info".into(),
+        std::io::sink,
+        Some(tracer(&provider)),
+    );
+
+    // Act
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("exported span").in_scope(|| {});
+    });
+    // Flushing blocks until the batch has been sent.
+    tokio::task::spawn_blocking(move || provider.force_flush())
+        .await
+        .unwrap();
+
+    // Assert
+    let exports = collector.received_requests().await.unwrap();
+    assert_eq!(exports.len(), 1);
+    assert_eq!(
+        header(&exports[0], "content-type").as_deref(),
+        Some("application/x-protobuf")
+    );
+    assert!(!exports[0].body.is_empty());
+}
+
+fn header(request: &Request, name: &str) -> Option<String> {
+    request.headers
+        .iter()
+        .find(|(header_name, _)| header_name.as_str().eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.last().as_str().to_owned())
+}